@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 target;
+
+// premultiplied alpha
+layout(set = 1, binding = 0) uniform sampler2D tex;
+
+void main() {
+    target = texture(tex, uv) * vec4(color.rgb * color.a, color.a);
+}
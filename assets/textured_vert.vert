@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 color;
+
+out gl_PerVertex { out vec4 gl_Position; };
+layout(location = 0) out vec2 o_uv;
+layout(location = 1) out vec4 o_color;
+
+layout(push_constant) uniform draw {
+    mat4 transform;
+    vec4 tint;
+};
+
+void main() {
+    gl_Position = transform * vec4(pos, 1.0);
+    o_uv = uv;
+    o_color = color * tint;
+}
@@ -9,10 +9,14 @@ layout(location = 0) out vec4 o_color;
 layout(set = 0, binding = 0) uniform timer {
     float time;
 };
+layout(push_constant) uniform draw {
+    mat4 transform;
+    vec4 tint;
+};
 
 void main() {
     const vec2 cs = vec2(cos(time * 2.0), sin(time * 2.0));
     const mat2 matrix = mat2(vec2(cs.x, -cs.y), vec2(cs.y, cs.x));
-    gl_Position = vec4(matrix * pos.xy, pos.zw);
-    o_color = color;
+    gl_Position = transform * vec4(matrix * pos.xy, pos.zw);
+    o_color = color * tint;
 }
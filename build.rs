@@ -1,3 +1,7 @@
-fn main() {
-    println!("cargo:rustc-link-search=static={}/Lib", env!("VK_SDK_PATH"));
-}
\ No newline at end of file
+fn main() {
+    // the Vulkan SDK provides the import library on Windows; elsewhere the system loader is linked
+    if std::env::var("CARGO_CFG_TARGET_OS").is_ok_and(|os| os == "windows") {
+        println!("cargo:rerun-if-env-changed=VK_SDK_PATH");
+        println!("cargo:rustc-link-search=static={}/Lib", std::env::var("VK_SDK_PATH").expect("VK_SDK_PATH not set"));
+    }
+}
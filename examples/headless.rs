@@ -0,0 +1,42 @@
+use vk_noredirect_render::{DrawList, DrawParams, HeadlessTarget, Indices, Mesh, Renderer, RendererOptions, Vertex};
+
+fn main() {
+    let mut renderer = Renderer::new(&RendererOptions {
+        validation: std::env::var_os("VK_NOREDIRECT_VALIDATION").is_some(),
+        .. Default::default()
+    }).expect("Renderer initialization failed");
+    let mut target = HeadlessTarget::new(&renderer).expect("Headless target initialization failed");
+    renderer.attach(&target).expect("Attaching backbuffers failed");
+
+    let quad = Mesh::new(&mut renderer, &[
+        Vertex { pos: [-0.25, -0.25, 0.5, 1.0], color: [1.0, 0.0, 0.0, 1.0] },
+        Vertex { pos: [0.25, -0.25, 0.5, 1.0], color: [0.0, 1.0, 0.0, 1.0] },
+        Vertex { pos: [0.25, 0.25, 0.5, 1.0], color: [0.0, 0.0, 1.0, 1.0] },
+        Vertex { pos: [-0.25, 0.25, 0.5, 1.0], color: [1.0, 1.0, 1.0, 1.0] }
+    ], Indices::U16(&[0, 1, 2, 0, 2, 3])).expect("Creating quad mesh failed");
+    let triangle = Mesh::new(&mut renderer, &[
+        Vertex { pos: [0.0, 0.3, 0.5, 1.0], color: [1.0, 1.0, 0.0, 0.8] },
+        Vertex { pos: [0.3, -0.3, 0.5, 1.0], color: [0.0, 1.0, 1.0, 0.8] },
+        Vertex { pos: [-0.3, -0.3, 0.5, 1.0], color: [1.0, 0.0, 1.0, 0.8] }
+    ], Indices::U32(&[0, 1, 2])).expect("Creating triangle mesh failed");
+
+    let mut list = DrawList::new();
+    let timer = std::time::Instant::now();
+    for frame in 0..120 {
+        let t = frame as f32 / 60.0;
+        renderer.set_time(t).expect("Timer update failed");
+        list.clear();
+        list.bind_pipeline(renderer.default_pipeline());
+        for (n, mesh) in [&quad, &triangle].iter().enumerate() {
+            let mut params = DrawParams::default();
+            params.transform[3][0] = if n == 0 { -0.5 } else { 0.5 } * t.cos();
+            list.set_params(0, &params).draw_mesh(mesh);
+        }
+        renderer.render_list(&mut target, &list).expect("Rendering frame failed");
+    }
+    renderer.flush(&mut target).expect("Presenting last frame failed");
+    println!("rendered 120 frames in {:?}", timer.elapsed());
+
+    let covered = target.pixels().chunks(4).filter(|p| p[3] != 0).count();
+    println!("last frame: {} of {} pixels covered", covered, target.pixels().len() / 4);
+}
@@ -0,0 +1,90 @@
+#[cfg(windows)] use winapi::um::winuser::*;
+#[cfg(windows)] use winapi::um::libloaderapi::GetModuleHandleA;
+#[cfg(windows)] use winapi::shared::windef::{HWND};
+#[cfg(windows)] use winapi::shared::minwindef::{UINT, WPARAM, LPARAM, LRESULT, LOWORD, HIWORD};
+#[cfg(windows)] use vk_noredirect_render::{DxgiPresenter, Renderer, RendererOptions};
+
+// WM_SIZE arrives in the window procedure, but the renderer lives in the main loop
+#[cfg(windows)]
+thread_local!(static PENDING_SIZE: std::cell::Cell<Option<(u32, u32)>> = std::cell::Cell::new(None));
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("this example requires Windows (DirectComposition)");
+}
+
+#[cfg(windows)]
+fn main() {
+    let wce = WNDCLASSEXA {
+        cbSize: std::mem::size_of::<WNDCLASSEXA>() as _,
+        lpszClassName: b"jp.ct2.experimental.vkNoRedirectRender\0".as_ptr() as _,
+        lpfnWndProc: Some(wcb),
+        hInstance: unsafe { GetModuleHandleA(std::ptr::null_mut()) },
+        .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+    };
+    if unsafe { RegisterClassExA(&wce) == 0 } {
+        panic!("RegisterClassEx failed: {:?}", std::io::Error::last_os_error());
+    }
+
+    let w = unsafe {
+        CreateWindowExA(
+            WS_EX_APPWINDOW | WS_EX_OVERLAPPEDWINDOW | WS_EX_NOREDIRECTIONBITMAP,
+            wce.lpszClassName, b"vkNoRedirectRender\0".as_ptr() as _,
+            WS_OVERLAPPEDWINDOW | WS_VISIBLE, CW_USEDEFAULT, CW_USEDEFAULT, CW_USEDEFAULT, CW_USEDEFAULT,
+            std::ptr::null_mut(), std::ptr::null_mut(), wce.hInstance, std::ptr::null_mut()
+        )
+    };
+    if w.is_null() {
+        panic!("CreateWindowEx failed: {:?}", std::io::Error::last_os_error());
+    }
+
+    let mut renderer = Renderer::new(&RendererOptions {
+        device_extensions: DxgiPresenter::DEVICE_EXTENSIONS,
+        .. Default::default()
+    }).expect("Renderer initialization failed");
+    let mut presenter = DxgiPresenter::new(w, &renderer).expect("DXGI Presenter initialization failed");
+    renderer.attach(&presenter).expect("Attaching backbuffers failed");
+
+    let mut msg = unsafe { std::mem::MaybeUninit::uninit().assume_init() };
+    let mut timer = std::time::Instant::now();
+    let mut time = 0.0;
+    'brk: loop {
+        while unsafe { PeekMessageA(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 } {
+            if msg.message == WM_QUIT { break 'brk; }
+
+            unsafe {
+                TranslateMessage(&msg);
+                DispatchMessageA(&msg);
+            }
+        }
+        if let Some((width, height)) = PENDING_SIZE.with(|s| s.take()) {
+            renderer.resize(&mut presenter, width, height).expect("Resizing backbuffers failed");
+        }
+
+        if renderer.is_frame_ready().expect("Querying frame status failed") {
+            // update/render
+            let dtms = timer.elapsed().as_micros() as f32 / 1_000_000.0;
+            timer = std::time::Instant::now();
+
+            time += dtms;
+            renderer.set_time(time).expect("Timer update failed");
+            renderer.render_frame(&mut presenter).expect("Rendering frame failed");
+        }
+    }
+
+    renderer.wait_idle().expect("vkDeviceWaitIdle failed");
+}
+
+#[cfg(windows)]
+extern "system" fn wcb(hwnd: HWND, msg: UINT, wp: WPARAM, lp: LPARAM) -> LRESULT {
+    match msg {
+        WM_DESTROY => unsafe { PostQuitMessage(0); return 0; },
+        WM_SIZE => {
+            PENDING_SIZE.with(|s| s.set(Some((LOWORD(lp as _) as _, HIWORD(lp as _) as _))));
+            return 0;
+        },
+        _ => ()
+    }
+
+    unsafe { DefWindowProcA(hwnd, msg, wp, lp) }
+}
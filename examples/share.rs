@@ -0,0 +1,104 @@
+//! Run `cargo run --example share --features share,headless -- consume /tmp/noredirect.sock` first,
+//! then `cargo run --example share --features share,headless -- produce /tmp/noredirect.sock` in another shell.
+
+use bedrock as br;
+use vk_noredirect_render::{
+    DrawList, DrawParams, FdHandleType, HeadlessTarget, Indices, Mesh, ModelVertex, Renderer, RendererOptions, Sampler,
+    SamplerOptions, SocketConsumer, SocketProducer, TextureSet, TimelineSemaphore
+};
+
+const HANDLE_TYPE: FdHandleType = FdHandleType::OpaqueFd;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let (mode, path) = match (args.next(), args.next()) {
+        (Some(mode), Some(path)) => (mode, path),
+        _ => {
+            eprintln!("usage: share (produce|consume) <socket path>");
+            std::process::exit(2);
+        }
+    };
+    let device_extensions = [HANDLE_TYPE.device_extensions(), TimelineSemaphore::FD_DEVICE_EXTENSIONS].concat();
+    let mut renderer = Renderer::new(&RendererOptions {
+        validation: std::env::var_os("VK_NOREDIRECT_VALIDATION").is_some(),
+        device_extensions: &device_extensions,
+        .. Default::default()
+    }).expect("Renderer initialization failed");
+
+    match mode.as_str() {
+        "produce" => {
+            let mut target = SocketProducer::connect(&renderer, &path, HANDLE_TYPE, renderer.buffer_count() as _).expect("Connecting to consumer failed");
+            renderer.attach(&target).expect("Attaching backbuffers failed");
+
+            let timer = std::time::Instant::now();
+            loop {
+                renderer.set_time(timer.elapsed().as_secs_f32()).expect("Timer update failed");
+                if let Err(e) = renderer.render_frame(&mut target) {
+                    println!("consumer went away: {}", e);
+                    break;
+                }
+            }
+        },
+        "consume" => {
+            // the compositor side: every received frame is sampled onto a full-screen quad and presented
+            // (read back here, as a stand-in for a window system target)
+            let mut target = HeadlessTarget::new(&renderer).expect("Headless target initialization failed");
+            renderer.attach(&target).expect("Attaching backbuffers failed");
+            let sampler = Sampler::new(&renderer, &SamplerOptions::default()).expect("Creating sampler failed");
+            let corner = |x: f32, y: f32| ModelVertex {
+                position: [x * 2.0 - 1.0, y * 2.0 - 1.0, 0.5], normal: [0.0, 0.0, -1.0], uv: [x, y], color: [1.0; 4]
+            };
+            let quad = Mesh::new(&mut renderer, &[corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0)],
+                Indices::U16(&[0, 1, 2, 0, 2, 3])).expect("Creating quad mesh failed");
+
+            let _ = std::fs::remove_file(&path);
+            let listener = std::os::unix::net::UnixListener::bind(&path).expect("Binding socket failed");
+            let mut consumer = SocketConsumer::accept(&renderer, &listener).expect("Accepting producer failed");
+            println!("imported {} images", consumer.images().len());
+
+            // one set per imported image, rebuilt whenever the producer re-sends its images
+            let mut imported = Vec::new();
+            let mut sets = Vec::new();
+            let mut list = DrawList::new();
+            let mut frames = 0;
+            let timer = std::time::Instant::now();
+            while frames < 600 {
+                let latest = match consumer.poll().expect("Receiving frames failed") {
+                    Some(index) => index,
+                    None => {
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                        continue;
+                    }
+                };
+                if consumer.images().iter().map(|i| i.image()).ne(imported.iter().copied()) {
+                    imported = consumer.images().iter().map(|i| i.image()).collect();
+                    // the producer leaves presented images in GENERAL
+                    sets = consumer.images().iter().map(|i| {
+                        TextureSet::for_view(&renderer, i.view(), br::vk::VK_IMAGE_LAYOUT_GENERAL, &sampler)
+                    }).collect::<Result<Vec<_>, _>>().expect("Binding imported images failed");
+                }
+
+                // a real consumer would make its own submission wait for this point instead
+                let point = consumer.latest_point().expect("no frame");
+                consumer.timeline().expect("no timeline").wait(point.value, u64::MAX).expect("Waiting for frame failed");
+                list.clear();
+                list.bind_pipeline(renderer.textured_pipeline())
+                    .bind_descriptor_set(1, sets[latest].native_ptr())
+                    .set_params(0, &DrawParams::default())
+                    .draw_mesh(&quad);
+                renderer.render_list(&mut target, &list).expect("Rendering frame failed");
+                // the image is released back to the producer on the next poll, so sampling must be done by then
+                renderer.flush(&mut target).expect("Presenting frame failed");
+                frames += 1;
+            }
+            println!("presented {} frames in {:?}", frames, timer.elapsed());
+
+            let covered = target.pixels().chunks(4).filter(|p| p[3] != 0).count();
+            println!("last frame: {} of {} pixels covered", covered, target.pixels().len() / 4);
+        },
+        _ => {
+            eprintln!("unknown mode: {}", mode);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Runs against any xdg_shell compositor, including a headless one:
+//! `weston --backend=headless-backend.so --socket=wayland-test & WAYLAND_DISPLAY=wayland-test cargo run --example wayland --features wayland`
+
+use vk_noredirect_render::{Renderer, RendererOptions, SwapchainTarget, WaylandWindow, WindowEvent};
+
+fn main() {
+    let mut window = WaylandWindow::new("vkNoRedirectRender", 640, 480).expect("Creating window failed");
+    let (width, height) = window.size();
+    let mut renderer = Renderer::new(&RendererOptions {
+        instance_extensions: WaylandWindow::INSTANCE_EXTENSIONS,
+        device_extensions: SwapchainTarget::DEVICE_EXTENSIONS,
+        .. Default::default()
+    }).expect("Renderer initialization failed");
+    let surface = window.create_surface(&renderer).expect("Creating surface failed");
+    let mut target = SwapchainTarget::new(&renderer, surface, width, height).expect("Swapchain initialization failed");
+    renderer.attach(&target).expect("Attaching backbuffers failed");
+
+    let mut timer = std::time::Instant::now();
+    let mut time = 0.0;
+    'brk: loop {
+        for e in window.poll_events() {
+            match e {
+                WindowEvent::CloseRequested => break 'brk,
+                WindowEvent::Resized(w, h) => renderer.resize(&mut target, w, h).expect("Resizing swapchain failed")
+            }
+        }
+
+        if renderer.is_frame_ready().expect("Querying frame status failed") {
+            let dtms = timer.elapsed().as_micros() as f32 / 1_000_000.0;
+            timer = std::time::Instant::now();
+
+            time += dtms;
+            renderer.set_time(time).expect("Timer update failed");
+            renderer.render_frame(&mut target).expect("Rendering frame failed");
+        }
+    }
+
+    renderer.wait_idle().expect("vkDeviceWaitIdle failed");
+}
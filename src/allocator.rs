@@ -0,0 +1,498 @@
+//! Device memory: memory type selection and sub-allocation from large blocks.
+
+use bedrock as br;
+use crate::{vk_check, Error, Result};
+use std::cell::RefCell;
+
+/// Size of the blocks sub-allocations are made from (smaller on small heaps).
+const BLOCK_SIZE: u64 = 64 << 20;
+
+/// Memory property flags a resource needs, and those it would like to have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub required: br::vk::VkMemoryPropertyFlags,
+    pub preferred: br::vk::VkMemoryPropertyFlags
+}
+impl MemoryUsage {
+    /// Only accessed by the device.
+    pub const DEVICE_LOCAL: Self = MemoryUsage { required: br::vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT, preferred: 0 };
+    /// Written by the host and read by the device (staging and per-frame data).
+    pub const UPLOAD: Self = MemoryUsage {
+        required: br::vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT,
+        preferred: br::vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT
+    };
+    /// Written by the device and read back by the host; cached memory is much faster to read from.
+    pub const READBACK: Self = MemoryUsage {
+        required: br::vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT,
+        preferred: br::vk::VK_MEMORY_PROPERTY_HOST_CACHED_BIT
+    };
+}
+
+/// Picks the memory type for a resource accepting `type_bits` (`VkMemoryRequirements::memoryTypeBits`).
+///
+/// Among the types having all `required` flags, the one with the most `preferred` flags wins, then the one with
+/// the fewest other flags (so e.g. plain device-local memory is not spent on host-visible types), then the lowest index.
+pub fn select_memory_type(properties: &br::vk::VkPhysicalDeviceMemoryProperties, type_bits: u32, usage: MemoryUsage) -> Option<u32> {
+    let types = &properties.memoryTypes[..properties.memoryTypeCount as usize];
+
+    select_from(types.iter().map(|t| t.propertyFlags), type_bits, usage)
+}
+
+fn select_from(
+    type_flags: impl Iterator<Item = br::vk::VkMemoryPropertyFlags>, type_bits: u32, usage: MemoryUsage
+) -> Option<u32> {
+    type_flags.enumerate()
+        .filter(|&(n, flags)| (type_bits & (1 << n)) != 0 && (flags & usage.required) == usage.required)
+        .min_by_key(|&(n, flags)| {
+            let preferred = (flags & usage.preferred).count_ones();
+            let extra = (flags & !(usage.required | usage.preferred)).count_ones();
+            (std::cmp::Reverse(preferred), extra, n)
+        })
+        .map(|(n, _)| n as _)
+}
+
+/// Memory bound to one resource: a range of a shared block, or a `VkDeviceMemory` of its own.
+///
+/// Must be given back with [`Allocator::free`]; dropping it leaks the range until the device is destroyed.
+/// The default value holds no memory (for resources not created yet), and freeing it does nothing.
+#[derive(Debug)]
+pub struct Allocation {
+    memory: br::vk::VkDeviceMemory,
+    offset: u64,
+    size: u64,
+    memory_type: u32,
+    mapped: *mut u8,
+    block: Option<usize>
+}
+impl Default for Allocation {
+    fn default() -> Self {
+        Allocation {
+            memory: br::vk::VK_NULL_HANDLE as _,
+            offset: 0,
+            size: 0,
+            memory_type: 0,
+            mapped: std::ptr::null_mut(),
+            block: None
+        }
+    }
+}
+impl Allocation {
+    pub fn memory(&self) -> br::vk::VkDeviceMemory { self.memory }
+    pub fn offset(&self) -> u64 { self.offset }
+    pub fn size(&self) -> u64 { self.size }
+    pub fn memory_type(&self) -> u32 { self.memory_type }
+    /// Whether the allocation owns its `VkDeviceMemory` instead of sharing a block.
+    pub fn is_dedicated(&self) -> bool { self.block.is_none() }
+    /// Host address of the start of the allocation, for host visible memory. Stays mapped until freed.
+    pub fn mapped_ptr(&self) -> Option<*mut u8> { if self.mapped.is_null() { None } else { Some(self.mapped) } }
+}
+
+/// Memory usage summary returned by [`Allocator::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Blocks sub-allocations are made from, and their total size.
+    pub block_count: usize,
+    pub block_bytes: u64,
+    /// Live sub-allocations, and the bytes they occupy in the blocks.
+    pub allocation_count: usize,
+    pub allocated_bytes: u64,
+    /// Live dedicated allocations, and their total size.
+    pub dedicated_count: usize,
+    pub dedicated_bytes: u64
+}
+
+/// Placement of sub-allocations within blocks of device memory, and the usage totals, without the memory itself.
+///
+/// The [`Allocator`] keeps one for its blocks. Blocks hold either linear resources (buffers and linear images) or
+/// optimal-tiling images, so the two never share a `bufferImageGranularity` page; with a granularity of 1 they mix.
+/// Reachable from outside only for tests, not part of the public API.
+#[derive(Debug)]
+pub struct SubAllocator {
+    buffer_image_granularity: u64,
+    blocks: Vec<BlockRanges>,
+    allocated_bytes: u64,
+    dedicated_count: usize,
+    dedicated_bytes: u64
+}
+#[derive(Debug)]
+struct BlockRanges {
+    memory_type: u32,
+    linear: bool,
+    size: u64,
+    /// Unused `(offset, size)` ranges, sorted by offset and never adjacent.
+    free: Vec<(u64, u64)>,
+    allocation_count: usize
+}
+impl BlockRanges {
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let (n, offset) = self.free.iter().enumerate().find_map(|(n, &(start, len))| {
+            let offset = align_up(start, alignment);
+            if offset + size <= start + len { Some((n, offset)) } else { None }
+        })?;
+        let (start, len) = self.free[n];
+        let mut rest = Vec::with_capacity(2);
+        if offset > start { rest.push((start, offset - start)); }
+        if offset + size < start + len { rest.push((offset + size, start + len - offset - size)); }
+        self.free.splice(n..=n, rest);
+        self.allocation_count += 1;
+
+        Some(offset)
+    }
+
+    fn release(&mut self, offset: u64, size: u64) {
+        let n = self.free.iter().position(|&(start, _)| start > offset).unwrap_or(self.free.len());
+        self.free.insert(n, (offset, size));
+        if n + 1 < self.free.len() && offset + size == self.free[n + 1].0 {
+            self.free[n].1 += self.free[n + 1].1;
+            self.free.remove(n + 1);
+        }
+        if n > 0 && self.free[n - 1].0 + self.free[n - 1].1 == offset {
+            self.free[n - 1].1 += self.free[n].1;
+            self.free.remove(n);
+        }
+        self.allocation_count -= 1;
+    }
+}
+impl SubAllocator {
+    pub fn new(buffer_image_granularity: u64) -> Self {
+        SubAllocator {
+            buffer_image_granularity: buffer_image_granularity.max(1),
+            blocks: Vec::new(),
+            allocated_bytes: 0,
+            dedicated_count: 0,
+            dedicated_bytes: 0
+        }
+    }
+
+    /// Places `size` bytes aligned to `alignment` in the first block of `memory_type` (and linearity) with room,
+    /// returning the block index and offset. `None` asks for a new block ([`SubAllocator::add_block`]).
+    pub fn allocate(&mut self, memory_type: u32, linear: bool, size: u64, alignment: u64) -> Option<(usize, u64)> {
+        let linear = self.is_linear(linear);
+        let (block, offset) = self.blocks.iter_mut().enumerate()
+            .filter(|(_, b)| b.memory_type == memory_type && b.linear == linear)
+            .find_map(|(n, b)| b.allocate(size, alignment.max(1)).map(|o| (n, o)))?;
+        self.allocated_bytes += size;
+
+        Some((block, offset))
+    }
+
+    /// Adds an empty block of `size` bytes, returning its index.
+    pub fn add_block(&mut self, memory_type: u32, linear: bool, size: u64) -> usize {
+        let linear = self.is_linear(linear);
+        self.blocks.push(BlockRanges { memory_type, linear, size, free: vec![(0, size)], allocation_count: 0 });
+
+        self.blocks.len() - 1
+    }
+
+    /// Gives back `size` bytes at `offset` of `block`, merging them with neighbouring unused ranges.
+    pub fn release(&mut self, block: usize, offset: u64, size: u64) {
+        self.blocks[block].release(offset, size);
+        self.allocated_bytes -= size;
+    }
+
+    /// Counts a dedicated allocation of `size` bytes into the stats.
+    pub fn add_dedicated(&mut self, size: u64) {
+        self.dedicated_count += 1;
+        self.dedicated_bytes += size;
+    }
+    pub fn release_dedicated(&mut self, size: u64) {
+        self.dedicated_count -= 1;
+        self.dedicated_bytes -= size;
+    }
+
+    /// Unused `(offset, size)` ranges of `block`, sorted by offset; adjacent ones are always merged.
+    pub fn free_ranges(&self, block: usize) -> &[(u64, u64)] { &self.blocks[block].free }
+
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            block_count: self.blocks.len(),
+            block_bytes: self.blocks.iter().map(|b| b.size).sum(),
+            allocation_count: self.blocks.iter().map(|b| b.allocation_count).sum(),
+            allocated_bytes: self.allocated_bytes,
+            dedicated_count: self.dedicated_count,
+            dedicated_bytes: self.dedicated_bytes
+        }
+    }
+
+    fn is_linear(&self, linear: bool) -> bool { linear || self.buffer_image_granularity == 1 }
+}
+
+/// Device memory of a block of the [`SubAllocator`], at the same index.
+struct BlockMemory {
+    memory: br::vk::VkDeviceMemory,
+    mapped: *mut u8
+}
+
+struct State {
+    ranges: SubAllocator,
+    blocks: Vec<BlockMemory>
+}
+
+/// Hands out device memory for buffers and images, owned by the [`Device`](crate::Device).
+///
+/// Small resources share large blocks of each memory type (kept until the device is destroyed); large ones and
+/// those the driver prefers to keep apart get a dedicated allocation. Host visible memory is mapped persistently.
+pub struct Allocator {
+    device: br::vk::VkDevice,
+    type_flags: Vec<br::vk::VkMemoryPropertyFlags>,
+    block_sizes: Vec<u64>,
+    non_coherent_atom_size: u64,
+    state: RefCell<State>
+}
+impl Allocator {
+    pub(crate) fn new(
+        device: br::vk::VkDevice,
+        memory_properties: &br::vk::VkPhysicalDeviceMemoryProperties,
+        limits: &br::vk::VkPhysicalDeviceLimits
+    ) -> Self {
+        let types = &memory_properties.memoryTypes[..memory_properties.memoryTypeCount as usize];
+
+        Allocator {
+            device,
+            type_flags: types.iter().map(|t| t.propertyFlags).collect(),
+            block_sizes: types.iter()
+                .map(|t| BLOCK_SIZE.min(memory_properties.memoryHeaps[t.heapIndex as usize].size / 8).max(1 << 20))
+                .collect(),
+            non_coherent_atom_size: limits.nonCoherentAtomSize.max(1),
+            state: RefCell::new(State { ranges: SubAllocator::new(limits.bufferImageGranularity), blocks: Vec::new() })
+        }
+    }
+
+    /// Allocates memory for a resource with `requirements`, out of a block unless it is large.
+    ///
+    /// `linear` tells buffers and linear images apart from optimal-tiling images, which must not share a
+    /// `bufferImageGranularity` page with them.
+    pub fn allocate(&self, requirements: &br::vk::VkMemoryRequirements, usage: MemoryUsage, linear: bool) -> Result<Allocation> {
+        let memory_type = self.select(requirements.memoryTypeBits, usage)?;
+        let block_size = self.block_sizes[memory_type as usize];
+        if requirements.size > block_size / 2 {
+            return self.allocate_separate(requirements.size, memory_type, br::vk::VK_NULL_HANDLE as _, std::ptr::null());
+        }
+        let (size, alignment) = if self.is_non_coherent(memory_type) {
+            (align_up(requirements.size, self.non_coherent_atom_size), requirements.alignment.max(self.non_coherent_atom_size))
+        } else {
+            (requirements.size, requirements.alignment.max(1))
+        };
+
+        let mut state = self.state.borrow_mut();
+        let (block, offset) = match state.ranges.allocate(memory_type, linear, size, alignment) {
+            Some(f) => f,
+            None => {
+                let b = match self.create_block(memory_type, block_size) {
+                    Ok(b) => b,
+                    // no room for another block; the resource may still fit on its own
+                    Err(_) => {
+                        drop(state);
+                        return self.allocate_separate(requirements.size, memory_type, br::vk::VK_NULL_HANDLE as _, std::ptr::null());
+                    }
+                };
+                state.blocks.push(b);
+                state.ranges.add_block(memory_type, linear, block_size);
+                state.ranges.allocate(memory_type, linear, size, alignment).expect("fresh block too small")
+            }
+        };
+        let b = &state.blocks[block];
+
+        Ok(Allocation {
+            memory: b.memory,
+            offset,
+            size,
+            memory_type,
+            mapped: if b.mapped.is_null() { std::ptr::null_mut() } else { unsafe { b.mapped.add(offset as usize) } },
+            block: Some(block)
+        })
+    }
+
+    /// Allocates a `VkDeviceMemory` of its own, dedicated to `image` unless it is null.
+    ///
+    /// `p_next` is chained into the `VkMemoryAllocateInfo`, for export or import information. Such memory is not
+    /// mapped, and `size` is passed on unchanged, as imports require.
+    ///
+    /// # Safety
+    /// `p_next` must be null or point to a valid structure chain that outlives the call.
+    pub unsafe fn allocate_dedicated(
+        &self, size: u64, type_bits: u32, usage: MemoryUsage, image: br::vk::VkImage, p_next: *const libc::c_void
+    ) -> Result<Allocation> {
+        let memory_type = self.select(type_bits, usage)?;
+
+        self.allocate_separate(size, memory_type, image, p_next)
+    }
+
+    /// Allocates and binds memory for `buffer`.
+    ///
+    /// # Safety
+    /// `buffer` must be a buffer of this device without memory bound.
+    pub unsafe fn allocate_buffer(&self, buffer: br::vk::VkBuffer, usage: MemoryUsage) -> Result<Allocation> {
+        let mut requirements = std::mem::MaybeUninit::uninit();
+        br::vk::vkGetBufferMemoryRequirements(self.device, buffer, requirements.as_mut_ptr());
+        let requirements = requirements.assume_init();
+        let allocation = self.allocate(&requirements, usage, true)?;
+        let r = br::vk::vkBindBufferMemory(self.device, buffer, allocation.memory, allocation.offset);
+        if let Err(e) = vk_check(r, "vkBindBufferMemory failed") {
+            self.free(allocation);
+            return Err(e);
+        }
+
+        Ok(allocation)
+    }
+
+    /// Allocates and binds memory for `image`, dedicated when the driver prefers it that way.
+    ///
+    /// # Safety
+    /// `image` must be an image of this device without memory bound.
+    pub unsafe fn allocate_image(&self, image: br::vk::VkImage, usage: MemoryUsage, linear: bool) -> Result<Allocation> {
+        let vk_get_image_memory_requirements2: br::vk::PFN_vkGetImageMemoryRequirements2 = std::mem::transmute(
+            br::vk::vkGetDeviceProcAddr(self.device, b"vkGetImageMemoryRequirements2\0".as_ptr() as _)
+                .ok_or(Error::Unsupported("vkGetImageMemoryRequirements2 not found?"))?
+        );
+        let info = br::vk::VkImageMemoryRequirementsInfo2 {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_REQUIREMENTS_INFO_2,
+            pNext: std::ptr::null(),
+            image
+        };
+        let mut dedicated = br::vk::VkMemoryDedicatedRequirements {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_DEDICATED_REQUIREMENTS,
+            pNext: std::ptr::null_mut(),
+            prefersDedicatedAllocation: false as _,
+            requiresDedicatedAllocation: false as _
+        };
+        let mut requirements = br::vk::VkMemoryRequirements2 {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_REQUIREMENTS_2,
+            pNext: &mut dedicated as *mut _ as _,
+            memoryRequirements: std::mem::MaybeUninit::zeroed().assume_init()
+        };
+        (vk_get_image_memory_requirements2)(self.device, &info, &mut requirements);
+        let requirements = requirements.memoryRequirements;
+
+        let allocation = if dedicated.prefersDedicatedAllocation != 0 || dedicated.requiresDedicatedAllocation != 0 {
+            self.allocate_dedicated(requirements.size, requirements.memoryTypeBits, usage, image, std::ptr::null())?
+        } else {
+            self.allocate(&requirements, usage, linear)?
+        };
+        let r = br::vk::vkBindImageMemory(self.device, image, allocation.memory, allocation.offset);
+        if let Err(e) = vk_check(r, "vkBindImageMemory failed") {
+            self.free(allocation);
+            return Err(e);
+        }
+
+        Ok(allocation)
+    }
+
+    /// Gives `allocation` back. The resource bound to it must have been destroyed (or never be used again).
+    pub fn free(&self, allocation: Allocation) {
+        let mut state = self.state.borrow_mut();
+        match allocation.block {
+            Some(n) => state.ranges.release(n, allocation.offset, allocation.size),
+            None if allocation.memory != br::vk::VK_NULL_HANDLE as _ => {
+                unsafe { br::vk::vkFreeMemory(self.device, allocation.memory, std::ptr::null()) };
+                state.ranges.release_dedicated(allocation.size);
+            },
+            None => ()
+        }
+    }
+
+    /// Makes host writes to `size` bytes at `offset` into `allocation` visible to the device.
+    pub fn flush(&self, allocation: &Allocation, offset: u64, size: u64) -> Result<()> {
+        if !self.is_non_coherent(allocation.memory_type) { return Ok(()); }
+        let ranges = &[self.mapped_range(allocation, offset, size)];
+        let r = unsafe { br::vk::vkFlushMappedMemoryRanges(self.device, ranges.len() as _, ranges.as_ptr()) };
+        vk_check(r, "vkFlushMappedMemoryRanges failed")
+    }
+
+    /// Makes device writes to `size` bytes at `offset` into `allocation` visible to the host.
+    pub fn invalidate(&self, allocation: &Allocation, offset: u64, size: u64) -> Result<()> {
+        if !self.is_non_coherent(allocation.memory_type) { return Ok(()); }
+        let ranges = &[self.mapped_range(allocation, offset, size)];
+        let r = unsafe { br::vk::vkInvalidateMappedMemoryRanges(self.device, ranges.len() as _, ranges.as_ptr()) };
+        vk_check(r, "vkInvalidateMappedMemoryRanges failed")
+    }
+
+    pub fn stats(&self) -> MemoryStats { self.state.borrow().ranges.stats() }
+
+    /// Frees all blocks; called by the device right before it is destroyed.
+    pub(crate) fn release(&mut self) {
+        for b in self.state.get_mut().blocks.drain(..) {
+            unsafe { br::vk::vkFreeMemory(self.device, b.memory, std::ptr::null()) };
+        }
+    }
+
+    fn select(&self, type_bits: u32, usage: MemoryUsage) -> Result<u32> {
+        select_from(self.type_flags.iter().copied(), type_bits, usage)
+            .ok_or(Error::Unsupported("no memory type with the required properties"))
+    }
+
+    fn is_non_coherent(&self, memory_type: u32) -> bool {
+        let flags = self.type_flags[memory_type as usize];
+        (flags & br::vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT) != 0 && (flags & br::vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT) == 0
+    }
+
+    fn mapped_range(&self, allocation: &Allocation, offset: u64, size: u64) -> br::vk::VkMappedMemoryRange {
+        let start = (allocation.offset + offset) / self.non_coherent_atom_size * self.non_coherent_atom_size;
+        let end = align_up(allocation.offset + offset + size, self.non_coherent_atom_size).min(allocation.offset + allocation.size);
+
+        br::vk::VkMappedMemoryRange {
+            sType: br::vk::VK_STRUCTURE_TYPE_MAPPED_MEMORY_RANGE,
+            pNext: std::ptr::null(),
+            memory: allocation.memory,
+            offset: start,
+            size: end - start
+        }
+    }
+
+    fn allocate_memory(&self, size: u64, memory_type: u32, p_next: *const libc::c_void) -> Result<br::vk::VkDeviceMemory> {
+        let ainfo = br::vk::VkMemoryAllocateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+            pNext: p_next,
+            allocationSize: size,
+            memoryTypeIndex: memory_type
+        };
+        let mut memory = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkAllocateMemory(self.device, &ainfo, std::ptr::null(), &mut memory) };
+        vk_check(r, "vkAllocateMemory failed")?;
+
+        Ok(memory)
+    }
+
+    fn map(&self, memory: br::vk::VkDeviceMemory, memory_type: u32) -> Result<*mut u8> {
+        if (self.type_flags[memory_type as usize] & br::vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT) == 0 {
+            return Ok(std::ptr::null_mut());
+        }
+        let mut p = std::ptr::null_mut();
+        let r = unsafe { br::vk::vkMapMemory(self.device, memory, 0, br::vk::VK_WHOLE_SIZE, 0, &mut p) };
+        if let Err(e) = vk_check(r, "vkMapMemory failed") {
+            unsafe { br::vk::vkFreeMemory(self.device, memory, std::ptr::null()) };
+            return Err(e);
+        }
+
+        Ok(p as _)
+    }
+
+    fn create_block(&self, memory_type: u32, size: u64) -> Result<BlockMemory> {
+        let memory = self.allocate_memory(size, memory_type, std::ptr::null())?;
+        let mapped = self.map(memory, memory_type)?;
+
+        Ok(BlockMemory { memory, mapped })
+    }
+
+    fn allocate_separate(
+        &self, size: u64, memory_type: u32, image: br::vk::VkImage, p_next: *const libc::c_void
+    ) -> Result<Allocation> {
+        let external = !p_next.is_null();
+        let size = if !external && self.is_non_coherent(memory_type) { align_up(size, self.non_coherent_atom_size) } else { size };
+        let dedicated_info = br::vk::VkMemoryDedicatedAllocateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_DEDICATED_ALLOCATE_INFO,
+            pNext: p_next,
+            image,
+            buffer: br::vk::VK_NULL_HANDLE as _
+        };
+        let p_next = if image != br::vk::VK_NULL_HANDLE as _ { &dedicated_info as *const _ as _ } else { p_next };
+        let memory = self.allocate_memory(size, memory_type, p_next)?;
+        let mapped = if external { std::ptr::null_mut() } else { self.map(memory, memory_type)? };
+
+        self.state.borrow_mut().ranges.add_dedicated(size);
+
+        Ok(Allocation { memory, offset: 0, size, memory_type, mapped, block: None })
+    }
+}
+
+fn align_up(x: u64, a: u64) -> u64 { x.div_ceil(a) * a }
@@ -0,0 +1,412 @@
+use bedrock as br;
+use uninit::extension_traits::*;
+use crate::{vk_check, Allocator, Error, Result, UniqueObject};
+use std::ffi::CString;
+
+/// Environment variable that overrides [`DeviceSelector::Default`]; parsed with [`DeviceSelector::parse`].
+pub const DEVICE_ENV_VAR: &str = "VK_NOREDIRECT_DEVICE";
+
+/// Identification of a physical device, as far as selecting one is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhysicalDeviceInfo {
+    pub name: String,
+    pub device_type: br::vk::VkPhysicalDeviceType,
+    /// `deviceUUID` of `VkPhysicalDeviceIDProperties`.
+    pub uuid: [u8; br::vk::VK_UUID_SIZE],
+    /// `deviceLUID` of `VkPhysicalDeviceIDProperties`, if the driver reports one (Windows only). Same bytes as the
+    /// `LUID` DXGI reports for the adapter.
+    pub luid: Option<[u8; br::vk::VK_LUID_SIZE]>
+}
+
+/// Policy for picking the physical device to run on. Only devices with a graphics queue are considered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceSelector<'a> {
+    /// [`DEVICE_ENV_VAR`] if set; otherwise the first discrete GPU, or the first device if there is none.
+    #[default]
+    Default,
+    /// Position among the considered devices, in enumeration order.
+    Index(usize),
+    Luid([u8; br::vk::VK_LUID_SIZE]),
+    Uuid([u8; br::vk::VK_UUID_SIZE]),
+    /// First device whose name contains the string, ignoring case.
+    Name(&'a str),
+    /// First device of the type.
+    Type(br::vk::VkPhysicalDeviceType)
+}
+impl<'a> DeviceSelector<'a> {
+    /// Reads a selector from text: 16 hex digits are a LUID and 32 (dashes allowed) a UUID, both in byte order;
+    /// a number is an index; `discrete`, `integrated`, `virtual` and `cpu` select by type; anything else by name.
+    pub fn parse(s: &'a str) -> Self {
+        let s = s.trim();
+        let hex = s.chars().filter(|&c| c != '-').collect::<String>();
+        if let Some(luid) = parse_hex_bytes(&hex) { return DeviceSelector::Luid(luid); }
+        if let Some(uuid) = parse_hex_bytes(&hex) { return DeviceSelector::Uuid(uuid); }
+        if let Ok(n) = s.parse() { return DeviceSelector::Index(n); }
+
+        match s.to_ascii_lowercase().as_str() {
+            "discrete" => DeviceSelector::Type(br::vk::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU),
+            "integrated" => DeviceSelector::Type(br::vk::VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU),
+            "virtual" => DeviceSelector::Type(br::vk::VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU),
+            "cpu" => DeviceSelector::Type(br::vk::VK_PHYSICAL_DEVICE_TYPE_CPU),
+            _ => DeviceSelector::Name(s)
+        }
+    }
+
+    /// Index of the selected device in `devices`. [`DeviceSelector::Default`] does not look at the environment here.
+    pub fn select(&self, devices: &[PhysicalDeviceInfo]) -> Option<usize> {
+        match *self {
+            DeviceSelector::Default => devices.iter()
+                .position(|d| d.device_type == br::vk::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU)
+                .or(if devices.is_empty() { None } else { Some(0) }),
+            DeviceSelector::Index(n) => if n < devices.len() { Some(n) } else { None },
+            DeviceSelector::Luid(luid) => devices.iter().position(|d| d.luid == Some(luid)),
+            DeviceSelector::Uuid(uuid) => devices.iter().position(|d| d.uuid == uuid),
+            DeviceSelector::Name(name) => {
+                let name = name.to_lowercase();
+                devices.iter().position(|d| d.name.to_lowercase().contains(&name))
+            },
+            DeviceSelector::Type(t) => devices.iter().position(|d| d.device_type == t)
+        }
+    }
+}
+
+fn parse_hex_bytes<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) { return None; }
+    let mut bytes = [0; N];
+    for (n, b) in bytes.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&hex[n * 2..n * 2 + 2], 16).ok()?;
+    }
+
+    Some(bytes)
+}
+
+/// Vulkan instance, physical/logical device pair and the graphics queue everything is submitted to.
+pub struct Device {
+    instance: br::vk::VkInstance,
+    debug_report: br::vk::VkDebugReportCallbackEXT,
+    destroy_debug_report: Option<br::vk::PFN_vkDestroyDebugReportCallbackEXT>,
+    adapter: br::vk::VkPhysicalDevice,
+    handle: br::vk::VkDevice,
+    queue: br::vk::VkQueue,
+    queue_family_index: u32,
+    memory_properties: br::vk::VkPhysicalDeviceMemoryProperties,
+    properties: br::vk::VkPhysicalDeviceProperties,
+    info: PhysicalDeviceInfo,
+    timeline_semaphores: bool,
+    allocator: Allocator
+}
+impl Device {
+    pub(crate) fn new(
+        application_name: &str, validation: bool, instance_extensions: &[&str], device_extensions: &[&str],
+        selector: DeviceSelector
+    ) -> Result<Self> {
+        let application_name = CString::new(application_name).expect("ffi encoding failed");
+        let mut instance_layers = Vec::new();
+        let mut instance_extensions = instance_extensions.iter()
+            .map(|&s| CString::new(s).expect("ffi encoding failed"))
+            .collect::<Vec<_>>();
+        if validation {
+            instance_layers.push(b"VK_LAYER_KHRONOS_validation\0".as_ptr() as _);
+            instance_extensions.push(CString::new("VK_EXT_debug_report").expect("ffi encoding failed"));
+        }
+        let instance_extension_ptrs = instance_extensions.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        let app_info = br::vk::VkApplicationInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_APPLICATION_INFO,
+            pNext: std::ptr::null(),
+            apiVersion: br::vk::VK_API_VERSION_1_1,
+            pApplicationName: application_name.as_ptr(),
+            applicationVersion: br::VK_MAKE_VERSION!(0, 1, 0),
+            pEngineName: b"RawRender\0".as_ptr() as _,
+            engineVersion: br::VK_MAKE_VERSION!(0, 1, 0)
+        };
+        let instance_cinfo = br::vk::VkInstanceCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_INSTANCE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            pApplicationInfo: &app_info,
+            ppEnabledLayerNames: instance_layers.as_ptr(),
+            enabledLayerCount: instance_layers.len() as _,
+            ppEnabledExtensionNames: instance_extension_ptrs.as_ptr(),
+            enabledExtensionCount: instance_extension_ptrs.len() as _
+        };
+        let mut instance = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkCreateInstance(&instance_cinfo, std::ptr::null(), &mut instance) };
+        vk_check(r, "vkCreateInstance failed")?;
+        // from here on, partially initialized objects are released by Drop
+        let mut this = Device {
+            instance,
+            debug_report: br::vk::VK_NULL_HANDLE as _,
+            destroy_debug_report: None,
+            adapter: br::vk::VK_NULL_HANDLE as _,
+            handle: br::vk::VK_NULL_HANDLE as _,
+            queue: br::vk::VK_NULL_HANDLE as _,
+            queue_family_index: 0,
+            memory_properties: unsafe { std::mem::MaybeUninit::zeroed().assume_init() },
+            properties: unsafe { std::mem::MaybeUninit::zeroed().assume_init() },
+            info: PhysicalDeviceInfo { name: String::new(), device_type: br::vk::VK_PHYSICAL_DEVICE_TYPE_OTHER, uuid: [0; 16], luid: None },
+            timeline_semaphores: false,
+            allocator: Allocator::new(
+                br::vk::VK_NULL_HANDLE as _,
+                unsafe { &std::mem::MaybeUninit::zeroed().assume_init() },
+                unsafe { &std::mem::MaybeUninit::zeroed().assume_init() }
+            )
+        };
+
+        if validation {
+            let dbg_cinfo = br::vk::VkDebugReportCallbackCreateInfoEXT {
+                sType: br::vk::VK_STRUCTURE_TYPE_DEBUG_REPORT_CALLBACK_CREATE_INFO_EXT,
+                pNext: std::ptr::null(),
+                flags: br::vk::VK_DEBUG_REPORT_ERROR_BIT_EXT | br::vk::VK_DEBUG_REPORT_WARNING_BIT_EXT,
+                pfnCallback: vkcb,
+                pUserData: std::ptr::null_mut()
+            };
+            let ccb_ext_fn: br::vk::PFN_vkCreateDebugReportCallbackEXT = unsafe {
+                std::mem::transmute(
+                    br::vk::vkGetInstanceProcAddr(this.instance, b"vkCreateDebugReportCallbackEXT\0".as_ptr() as _)
+                        .ok_or(Error::Unsupported("vkCreateDebugReportCallbackEXT not found?"))?
+                )
+            };
+            let dcb_ext_fn: br::vk::PFN_vkDestroyDebugReportCallbackEXT = unsafe {
+                std::mem::transmute(
+                    br::vk::vkGetInstanceProcAddr(this.instance, b"vkDestroyDebugReportCallbackEXT\0".as_ptr() as _)
+                        .ok_or(Error::Unsupported("vkDestroyDebugReportCallbackEXT not found?"))?
+                )
+            };
+            let r = (ccb_ext_fn)(this.instance, &dbg_cinfo, std::ptr::null(), &mut this.debug_report);
+            vk_check(r, "vkCreateDebugReportCallback failed")?;
+            this.destroy_debug_report = Some(dcb_ext_fn);
+        }
+
+        let mut adapter_count = 0;
+        let r = unsafe { br::vk::vkEnumeratePhysicalDevices(this.instance, &mut adapter_count, std::ptr::null_mut()) };
+        vk_check(r, "vkEnumeratePhysicalDevices failed")?;
+        let mut adapters = vec![br::vk::VK_NULL_HANDLE as _; adapter_count as _];
+        let r = unsafe { br::vk::vkEnumeratePhysicalDevices(this.instance, &mut adapter_count, adapters.as_mut_ptr()) };
+        vk_check(r, "vkEnumeratePhysicalDevices failed")?;
+        adapters.truncate(adapter_count as _);
+        let vk_get_physical_device_properties2: br::vk::PFN_vkGetPhysicalDeviceProperties2 = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetInstanceProcAddr(this.instance, b"vkGetPhysicalDeviceProperties2\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkGetPhysicalDeviceProperties2 not found?"))?
+            )
+        };
+        // (adapter, graphics queue family) of every device that can render at all
+        let mut candidates = Vec::with_capacity(adapters.len());
+        let mut candidate_infos = Vec::with_capacity(adapters.len());
+        for adapter in adapters {
+            if let Some(queue_family_index) = graphics_queue_family(adapter) {
+                candidates.push((adapter, queue_family_index));
+                candidate_infos.push(physical_device_info(vk_get_physical_device_properties2, adapter));
+            }
+        }
+        if candidates.is_empty() { return Err(Error::Unsupported("no physical devices with a graphics queue?")); }
+        let env_selector = std::env::var(DEVICE_ENV_VAR).ok();
+        let selector = match (selector, &env_selector) {
+            (DeviceSelector::Default, Some(s)) => DeviceSelector::parse(s),
+            (s, _) => s
+        };
+        let selected = selector.select(&candidate_infos).ok_or(Error::Unsupported("no physical device matches the selection"))?;
+        let (adapter, queue_family_index) = candidates[selected];
+        this.adapter = adapter;
+        this.queue_family_index = queue_family_index;
+        this.info = candidate_infos.swap_remove(selected);
+        let queue_priorities = &[0.0];
+        let queue_create_info = br::vk::VkDeviceQueueCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DEVICE_QUEUE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            queueFamilyIndex: this.queue_family_index,
+            queueCount: 1,
+            pQueuePriorities: queue_priorities.as_ptr()
+        };
+        let device_extensions = device_extensions.iter()
+            .map(|&s| CString::new(s).expect("ffi encoding failed"))
+            .collect::<Vec<_>>();
+        let device_extension_ptrs = device_extensions.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        // the extension alone does not make timeline semaphores usable
+        let timeline_features = br::vk::VkPhysicalDeviceTimelineSemaphoreFeatures {
+            sType: br::vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_TIMELINE_SEMAPHORE_FEATURES,
+            pNext: std::ptr::null_mut(),
+            timelineSemaphore: true as _
+        };
+        let enable_timeline = device_extensions.iter().any(|s| s.as_bytes() == b"VK_KHR_timeline_semaphore");
+        let device_cinfo = br::vk::VkDeviceCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DEVICE_CREATE_INFO,
+            pNext: if enable_timeline { &timeline_features as *const _ as _ } else { std::ptr::null() },
+            flags: 0,
+            ppEnabledLayerNames: std::ptr::null(),
+            enabledLayerCount: 0,
+            ppEnabledExtensionNames: device_extension_ptrs.as_ptr(),
+            enabledExtensionCount: device_extension_ptrs.len() as _,
+            pQueueCreateInfos: &queue_create_info,
+            queueCreateInfoCount: 1,
+            pEnabledFeatures: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkCreateDevice(this.adapter, &device_cinfo, std::ptr::null(), &mut this.handle) };
+        vk_check(r, "vkCreateDevice failed")?;
+        unsafe { br::vk::vkGetDeviceQueue(this.handle, this.queue_family_index, 0, &mut this.queue) };
+        this.timeline_semaphores = enable_timeline;
+
+        unsafe { br::vk::vkGetPhysicalDeviceMemoryProperties(this.adapter, &mut this.memory_properties) };
+        unsafe { br::vk::vkGetPhysicalDeviceProperties(this.adapter, &mut this.properties) };
+        this.allocator = Allocator::new(this.handle, &this.memory_properties, &this.properties.limits);
+
+        Ok(this)
+    }
+
+    pub fn instance(&self) -> br::vk::VkInstance { self.instance }
+    pub fn adapter(&self) -> br::vk::VkPhysicalDevice { self.adapter }
+    pub fn native_ptr(&self) -> br::vk::VkDevice { self.handle }
+    pub fn queue(&self) -> br::vk::VkQueue { self.queue }
+    pub fn queue_family_index(&self) -> u32 { self.queue_family_index }
+    pub fn memory_properties(&self) -> &br::vk::VkPhysicalDeviceMemoryProperties { &self.memory_properties }
+    pub fn properties(&self) -> &br::vk::VkPhysicalDeviceProperties { &self.properties }
+    /// Name, type and identifiers of the selected physical device.
+    pub fn physical_device_info(&self) -> &PhysicalDeviceInfo { &self.info }
+    /// Whether the device was created with `VK_KHR_timeline_semaphore` (and the feature enabled).
+    pub fn timeline_semaphores(&self) -> bool { self.timeline_semaphores }
+    /// Device memory for buffers and images.
+    pub fn allocator(&self) -> &Allocator { &self.allocator }
+    /// Features the physical device supports for `format`, by tiling.
+    pub fn format_properties(&self, format: br::vk::VkFormat) -> br::vk::VkFormatProperties {
+        let mut props = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+        unsafe { br::vk::vkGetPhysicalDeviceFormatProperties(self.adapter, format, &mut props) };
+
+        props
+    }
+
+    /// Records commands with `f` into a one-time command buffer, submits it and waits for completion.
+    ///
+    /// `fence` must not be in use by another submission; it is left signaled.
+    pub(crate) fn immediate_submit(&self, fence: br::vk::VkFence, f: impl FnOnce(br::vk::VkCommandBuffer)) -> Result<()> {
+        let vk_device = self.handle;
+
+        let transfer_cp_cinfo = br::vk::VkCommandPoolCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: br::vk::VK_COMMAND_POOL_CREATE_TRANSIENT_BIT,
+            queueFamilyIndex: self.queue_family_index
+        };
+        let mut cp = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkCreateCommandPool(vk_device, &transfer_cp_cinfo, std::ptr::null(), &mut cp) };
+        vk_check(r, "vkCreateCommandPool failed")?;
+        let cp = UniqueObject(cp, |p| unsafe { br::vk::vkDestroyCommandPool(vk_device, p, std::ptr::null()); });
+        let transfer_cmd_ainfo = br::vk::VkCommandBufferAllocateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO,
+            pNext: std::ptr::null(),
+            commandPool: cp.as_ptr(),
+            level: br::vk::VK_COMMAND_BUFFER_LEVEL_PRIMARY,
+            commandBufferCount: 1
+        };
+        let mut transfer_cmd = vec![br::vk::VK_NULL_HANDLE as _];
+        let r = unsafe { br::vk::vkAllocateCommandBuffers(vk_device, &transfer_cmd_ainfo, transfer_cmd.as_mut_ptr()) };
+        vk_check(r, "vkAllocateCommandBuffers failed")?;
+        let cmd_begin_info = br::vk::VkCommandBufferBeginInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+            pNext: std::ptr::null(),
+            flags: br::vk::VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT,
+            pInheritanceInfo: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkBeginCommandBuffer(transfer_cmd[0], &cmd_begin_info) };
+        vk_check(r, "vkBeginCommandBuffer failed")?;
+        f(transfer_cmd[0]);
+        let r = unsafe { br::vk::vkEndCommandBuffer(transfer_cmd[0]) };
+        vk_check(r, "Recording TransferCommands failed")?;
+        let transfer_submit_infos = &[
+            br::vk::VkSubmitInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_SUBMIT_INFO,
+                pNext: std::ptr::null(),
+                commandBufferCount: transfer_cmd.len() as _,
+                pCommandBuffers: transfer_cmd.as_ptr(),
+                waitSemaphoreCount: 0,
+                pWaitSemaphores: std::ptr::null(),
+                pWaitDstStageMask: std::ptr::null(),
+                signalSemaphoreCount: 0,
+                pSignalSemaphores: std::ptr::null()
+            }
+        ];
+        let r = unsafe { br::vk::vkResetFences(vk_device, 1, &fence) };
+        vk_check(r, "vkResetFences failed")?;
+        let r = unsafe { br::vk::vkQueueSubmit(self.queue, transfer_submit_infos.len() as _, transfer_submit_infos.as_ptr(), fence) };
+        vk_check(r, "vkQueueSubmit failed")?;
+        let r = unsafe { br::vk::vkWaitForFences(vk_device, 1, &fence, false as _, u64::MAX) };
+        vk_check(r, "vkWaitForFences failed")?;
+        unsafe { br::vk::vkFreeCommandBuffers(vk_device, cp.as_ptr(), transfer_cmd.len() as _, transfer_cmd.as_ptr()) };
+
+        Ok(())
+    }
+
+    pub fn wait_idle(&self) -> Result<()> {
+        let r = unsafe { br::vk::vkDeviceWaitIdle(self.handle) };
+        vk_check(r, "vkDeviceWaitIdle failed")
+    }
+}
+impl Drop for Device {
+    fn drop(&mut self) {
+        self.allocator.release();
+        unsafe { br::vk::vkDestroyDevice(self.handle, std::ptr::null()); }
+        if let Some(f) = self.destroy_debug_report {
+            (f)(self.instance, self.debug_report, std::ptr::null());
+        }
+        unsafe { br::vk::vkDestroyInstance(self.instance, std::ptr::null()); }
+    }
+}
+
+fn graphics_queue_family(adapter: br::vk::VkPhysicalDevice) -> Option<u32> {
+    let mut queue_family_property_count = 0;
+    unsafe { br::vk::vkGetPhysicalDeviceQueueFamilyProperties(adapter, &mut queue_family_property_count, std::ptr::null_mut()) };
+    let mut queue_family_properties = Vec::new();
+    unsafe {
+        br::vk::vkGetPhysicalDeviceQueueFamilyProperties(
+            adapter, &mut queue_family_property_count,
+            queue_family_properties.reserve_uninit(queue_family_property_count as _).as_mut_ptr()
+        )
+    };
+    unsafe { queue_family_properties.set_len(queue_family_properties.len() + queue_family_property_count as usize); }
+
+    queue_family_properties.iter()
+        .position(|p: &br::vk::VkQueueFamilyProperties| p.queueCount > 0 && (p.queueFlags & br::vk::VK_QUEUE_GRAPHICS_BIT) != 0)
+        .map(|n| n as _)
+}
+
+fn physical_device_info(
+    vk_get_physical_device_properties2: br::vk::PFN_vkGetPhysicalDeviceProperties2, adapter: br::vk::VkPhysicalDevice
+) -> PhysicalDeviceInfo {
+    let mut id_props = br::vk::VkPhysicalDeviceIDProperties {
+        sType: br::vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_ID_PROPERTIES,
+        pNext: std::ptr::null_mut(),
+        .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+    };
+    let mut props = br::vk::VkPhysicalDeviceProperties2 {
+        sType: br::vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PROPERTIES_2,
+        pNext: &mut id_props as *mut _ as _,
+        properties: unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+    };
+    (vk_get_physical_device_properties2)(adapter, &mut props);
+
+    PhysicalDeviceInfo {
+        name: unsafe { std::ffi::CStr::from_ptr(props.properties.deviceName.as_ptr()) }.to_string_lossy().into_owned(),
+        device_type: props.properties.deviceType,
+        uuid: id_props.deviceUUID,
+        luid: if id_props.deviceLUIDValid != 0 { Some(id_props.deviceLUID) } else { None }
+    }
+}
+
+extern "system" fn vkcb(
+    flags: br::vk::VkDebugReportFlagsEXT,
+    _: br::vk::VkDebugReportObjectTypeEXT,
+    _: u64,
+    _: libc::size_t,
+    _: i32,
+    layer_prefix: *const libc::c_char,
+    message: *const libc::c_char,
+    _: *mut libc::c_void
+) -> br::vk::VkBool32 {
+    println!(
+        "vkcb: [{}] {}",
+        unsafe { std::ffi::CStr::from_ptr(layer_prefix).to_string_lossy() },
+        unsafe { std::ffi::CStr::from_ptr(message).to_string_lossy() }
+    );
+
+    ((flags & br::vk::VK_DEBUG_REPORT_ERROR_BIT_EXT) != 0) as _
+}
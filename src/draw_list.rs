@@ -0,0 +1,132 @@
+//! Draw commands filled by the application each frame and recorded by the renderer.
+
+use bedrock as br;
+use crate::{DrawRegion, Error, Mesh, Result, ShaderData, PUSH_CONSTANT_SIZE};
+
+/// A pipeline together with the layout its resources are bound through.
+///
+/// Every layout the renderer creates starts with the per-frame uniforms in set 0 and reserves
+/// [`PUSH_CONSTANT_SIZE`] bytes of push constants. Handles change when [`Renderer::attach`](crate::Renderer::attach)
+/// switches formats, so fetch them again for every list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphicsPipeline {
+    pub pipeline: br::vk::VkPipeline,
+    pub layout: br::vk::VkPipelineLayout
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum DrawCommand {
+    BindPipeline(GraphicsPipeline),
+    /// Set index and set; set 0 is reserved for the per-frame uniforms.
+    BindDescriptorSet(u32, br::vk::VkDescriptorSet),
+    BindVertexBuffer { binding: u32, buffer: br::vk::VkBuffer, offset: u64 },
+    BindIndexBuffer { buffer: br::vk::VkBuffer, offset: u64, index_type: br::vk::VkIndexType },
+    /// Push constant offset and range of the list's parameter bytes.
+    SetParams { offset: u32, start: usize, len: usize },
+    SetRegion(DrawRegion),
+    Draw { vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32 },
+    DrawIndexed { index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32 }
+}
+
+/// Commands for one frame, replayed in order inside the render pass by [`Renderer::render_list`](crate::Renderer::render_list).
+///
+/// The viewport and scissor start out covering the whole backbuffer. Buffers and sets are referenced, not owned:
+/// they have to stay alive until the frame has finished rendering.
+#[derive(Debug, Clone, Default)]
+pub struct DrawList {
+    pub(crate) commands: Vec<DrawCommand>,
+    pub(crate) params: Vec<u8>,
+    pipeline_bound: bool
+}
+impl DrawList {
+    pub fn new() -> Self { DrawList::default() }
+
+    /// Empties the list, keeping its allocations for the next frame.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.params.clear();
+        self.pipeline_bound = false;
+    }
+
+    pub fn is_empty(&self) -> bool { self.commands.is_empty() }
+
+    /// Fails when a region set on the list has an empty viewport, which [`Renderer::render_list`](crate::Renderer::render_list)
+    /// refuses to record (as [`Renderer::set_draw_regions`](crate::Renderer::set_draw_regions) refuses to store).
+    pub fn validate(&self) -> Result<()> {
+        let valid = self.commands.iter().all(|c| match c {
+            DrawCommand::SetRegion(region) => region.has_valid_viewport(),
+            _ => true
+        });
+        if !valid { return Err(Error::Unsupported("draw region viewports must have a positive width and height")); }
+
+        Ok(())
+    }
+
+    pub fn bind_pipeline(&mut self, pipeline: GraphicsPipeline) -> &mut Self {
+        self.commands.push(DrawCommand::BindPipeline(pipeline));
+        self.pipeline_bound = true;
+        self
+    }
+
+    /// Binds `set` at index `index` (1 or higher) of the current pipeline's layout.
+    pub fn bind_descriptor_set(&mut self, index: u32, set: br::vk::VkDescriptorSet) -> &mut Self {
+        assert!(index > 0, "set 0 holds the per-frame uniforms");
+        assert!(self.pipeline_bound, "descriptor sets are bound through the current pipeline's layout");
+        self.commands.push(DrawCommand::BindDescriptorSet(index, set));
+        self
+    }
+
+    pub fn bind_vertex_buffer(&mut self, binding: u32, buffer: br::vk::VkBuffer, offset: u64) -> &mut Self {
+        self.commands.push(DrawCommand::BindVertexBuffer { binding, buffer, offset });
+        self
+    }
+
+    pub fn bind_index_buffer(&mut self, buffer: br::vk::VkBuffer, offset: u64, index_type: br::vk::VkIndexType) -> &mut Self {
+        self.commands.push(DrawCommand::BindIndexBuffer { buffer, offset, index_type });
+        self
+    }
+
+    /// Pushes `params` as constants at `offset` bytes, for the draws that follow.
+    pub fn set_params<T: ShaderData>(&mut self, offset: u32, params: &T) -> &mut Self {
+        let bytes = params.as_bytes();
+        assert!(offset as usize + bytes.len() <= PUSH_CONSTANT_SIZE as usize, "parameters exceed the push constant range");
+        self.commands.push(DrawCommand::SetParams { offset, start: self.params.len(), len: bytes.len() });
+        self.params.extend_from_slice(bytes);
+        self
+    }
+
+    /// Sets the viewport and scissor of the draws that follow; the region's parameters are not pushed.
+    ///
+    /// The viewport must not be empty, or the list fails to render (see [`DrawList::validate`]).
+    pub fn set_region(&mut self, region: &DrawRegion) -> &mut Self {
+        self.commands.push(DrawCommand::SetRegion(*region));
+        self
+    }
+
+    pub fn draw(&mut self, vertex_count: u32, first_vertex: u32) -> &mut Self {
+        self.draw_instanced(vertex_count, 1, first_vertex, 0)
+    }
+
+    pub fn draw_instanced(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) -> &mut Self {
+        self.commands.push(DrawCommand::Draw { vertex_count, instance_count, first_vertex, first_instance });
+        self
+    }
+
+    pub fn draw_indexed(&mut self, index_count: u32, first_index: u32, vertex_offset: i32) -> &mut Self {
+        self.draw_indexed_instanced(index_count, 1, first_index, vertex_offset, 0)
+    }
+
+    /// Binds the vertices of `mesh` at binding 0 and its indices, and draws all of them.
+    pub fn draw_mesh(&mut self, mesh: &Mesh) -> &mut Self {
+        self.bind_vertex_buffer(0, mesh.buffer(), 0)
+            .bind_index_buffer(mesh.buffer(), mesh.index_offset(), mesh.index_type())
+            .draw_indexed(mesh.index_count(), 0, 0)
+    }
+
+    pub fn draw_indexed_instanced(
+        &mut self, index_count: u32, instance_count: u32, first_index: u32, vertex_offset: i32, first_instance: u32
+    ) -> &mut Self {
+        self.commands.push(DrawCommand::DrawIndexed { index_count, instance_count, first_index, vertex_offset, first_instance });
+        self
+    }
+}
@@ -0,0 +1,404 @@
+use winapi::um::d3d12::*;
+use winapi::shared::windef::HWND;
+use winapi::shared::ntdef::HANDLE;
+use winapi::Interface;
+use bedrock as br;
+use crate::{vk_check, AcquiredImage, Allocation, Device, Error, MemoryUsage, PresentTarget, Renderer, Result, TimelinePoint, TimelineSemaphore};
+use crate::renderer::BACKBUFFER_FORMAT;
+use std::rc::Rc;
+
+#[repr(transparent)]
+pub struct ComPtr<T>(std::ptr::NonNull<T>);
+impl<T> From<*mut T> for ComPtr<T> { fn from(p: *mut T) -> Self { ComPtr(unsafe { std::ptr::NonNull::new_unchecked(p) }) } }
+impl<T> Drop for ComPtr<T> {
+    fn drop(&mut self) {
+        unsafe {
+            (*(self.0.as_ptr() as *mut winapi::um::unknwnbase::IUnknown)).Release();
+        }
+    }
+}
+impl<T> Clone for ComPtr<T> {
+    fn clone(&self) -> Self {
+        unsafe {
+            (*(self.0.as_ptr() as *mut winapi::um::unknwnbase::IUnknown)).AddRef();
+        }
+        ComPtr(self.0)
+    }
+}
+impl<T> std::ops::Deref for ComPtr<T> {
+    type Target = T;
+    fn deref(&self) -> &T { unsafe { self.0.as_ref() } }
+}
+impl<T> std::ops::DerefMut for ComPtr<T> {
+    fn deref_mut(&mut self) -> &mut T { unsafe { self.0.as_mut() } }
+}
+impl<T> ComPtr<T> {
+    pub fn as_ptr(&self) -> *mut T { self.0.as_ptr() }
+}
+
+fn hr_to_ioresult(hr: winapi::shared::winerror::HRESULT) -> std::io::Result<()> {
+    if winapi::shared::winerror::FAILED(hr) { Err(std::io::Error::from_raw_os_error(hr)) } else { Ok(()) }
+}
+fn hr_check(hr: winapi::shared::winerror::HRESULT, ctx: &'static str) -> Result<()> {
+    hr_to_ioresult(hr).map_err(|e| Error::Os(ctx, e))
+}
+
+/// Adapter whose LUID is `luid`, in the byte order of `VkPhysicalDeviceIDProperties::deviceLUID`.
+fn find_adapter(factory: &winapi::shared::dxgi1_2::IDXGIFactory2, luid: [u8; 8]) -> Result<ComPtr<winapi::shared::dxgi::IDXGIAdapter1>> {
+    for n in 0.. {
+        let mut adapter = std::ptr::null_mut();
+        let hr = unsafe { factory.EnumAdapters1(n, &mut adapter) };
+        if hr == winapi::shared::winerror::DXGI_ERROR_NOT_FOUND { break; }
+        hr_check(hr, "IDXGIAdapter1 Enumeration failed")?;
+        let adapter = ComPtr::from(adapter);
+        let mut desc: winapi::shared::dxgi::DXGI_ADAPTER_DESC1 = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+        let hr = unsafe { adapter.GetDesc1(&mut desc) };
+        hr_check(hr, "IDXGIAdapter1 GetDesc1 failed")?;
+        let mut adapter_luid = [0; 8];
+        adapter_luid[..4].copy_from_slice(&desc.AdapterLuid.LowPart.to_le_bytes());
+        adapter_luid[4..].copy_from_slice(&desc.AdapterLuid.HighPart.to_le_bytes());
+        if adapter_luid == luid { return Ok(adapter); }
+    }
+
+    Err(Error::Unsupported("no DXGI adapter matches the Vulkan device"))
+}
+
+struct ImportedBackbuffer {
+    shared_handle: HANDLE,
+    memory: Allocation,
+    image: br::vk::VkImage
+}
+
+/// Composition swapchain presented through DirectComposition, with its backbuffers imported into Vulkan.
+///
+/// The window should be created with `WS_EX_NOREDIRECTIONBITMAP` so that only the composition content is visible.
+///
+/// Both directions are synchronized on the GPU: the D3D12 queue waits for a Vulkan timeline semaphore opened as a
+/// fence before presenting, and Vulkan rendering waits for the D3D12 fence signaled after each present.
+pub struct DxgiPresenter {
+    device: Rc<Device>,
+    device12: ComPtr<ID3D12Device>,
+    cq: ComPtr<ID3D12CommandQueue>,
+    sc: ComPtr<winapi::shared::dxgi1_4::IDXGISwapChain3>,
+    sc_waitable: HANDLE,
+    /// Signaled by the D3D12 queue after each present, and imported into Vulkan as `present_timeline`.
+    fence12: ComPtr<ID3D12Fence>,
+    fence_event: HANDLE,
+    fence_value: u64,
+    present_timeline: TimelineSemaphore,
+    /// `fence12` value signaled after each backbuffer was last presented.
+    present_values: Vec<u64>,
+    /// Signaled by Vulkan when rendering finishes, and opened in D3D12 as `render_fence12`.
+    render_timeline: TimelineSemaphore,
+    render_fence12: ComPtr<ID3D12Fence>,
+    render_value: u64,
+    render_values: Vec<u64>,
+    buffer_count: u32,
+    /// Buffer the next acquire hands out; runs ahead of the swapchain's current buffer while frames are in flight.
+    next: usize,
+    extent: br::vk::VkExtent2D,
+    _comp_device: ComPtr<winapi::um::dcomp::IDCompositionDesktopDevice>,
+    _target: ComPtr<winapi::um::dcomp::IDCompositionTarget>,
+    _root: ComPtr<winapi::um::dcomp::IDCompositionVisual2>,
+    backbuffers: Vec<ImportedBackbuffer>
+}
+impl DxgiPresenter {
+    /// Device extensions the renderer must be created with to import the backbuffers.
+    pub const DEVICE_EXTENSIONS: &'static [&'static str] = &[
+        "VK_KHR_external_memory_win32", "VK_KHR_external_semaphore_win32", "VK_KHR_timeline_semaphore"
+    ];
+
+    pub fn new(w: HWND, renderer: &Renderer) -> Result<Self> {
+        let extent = renderer.extent();
+
+        // Initialize DXGI
+        let mut factory = std::ptr::null_mut();
+        let hr = unsafe { winapi::shared::dxgi1_3::CreateDXGIFactory2(winapi::shared::dxgi1_3::DXGI_CREATE_FACTORY_DEBUG, &winapi::shared::dxgi1_2::IDXGIFactory2::uuidof(), &mut factory) };
+        hr_check(hr, "CreateDXGIFactory2 failed")?;
+        let factory = ComPtr::from(factory as *mut winapi::shared::dxgi1_2::IDXGIFactory2);
+        // the backbuffer memory can only be imported on the same GPU
+        let luid = renderer.device().physical_device_info().luid
+            .ok_or(Error::Unsupported("Vulkan device reports no LUID to match a DXGI adapter with"))?;
+        let adapter = find_adapter(&factory, luid)?;
+
+        // Initialize Direct3D12
+        let mut dbg = std::ptr::null_mut();
+        let hr = unsafe { D3D12GetDebugInterface(&winapi::um::d3d12sdklayers::ID3D12Debug::uuidof(), &mut dbg) };
+        hr_check(hr, "D3D12GetDebugInterface failed")?;
+        unsafe { ComPtr::from(dbg as *mut winapi::um::d3d12sdklayers::ID3D12Debug).EnableDebugLayer(); }
+
+        let mut device12 = std::ptr::null_mut();
+        let hr = unsafe { D3D12CreateDevice(adapter.as_ptr() as _, winapi::um::d3dcommon::D3D_FEATURE_LEVEL_12_0, &winapi::um::d3d12::ID3D12Device::uuidof(), &mut device12) };
+        hr_check(hr, "D3D12CreateDevice failed")?;
+        let device12 = ComPtr::from(device12 as *mut winapi::um::d3d12::ID3D12Device);
+        let cqdesc = D3D12_COMMAND_QUEUE_DESC {
+            Type: D3D12_COMMAND_LIST_TYPE_DIRECT,
+            .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+        };
+        let mut cq = std::ptr::null_mut();
+        let hr = unsafe { device12.CreateCommandQueue(&cqdesc, &winapi::um::d3d12::ID3D12CommandQueue::uuidof(), &mut cq) };
+        hr_check(hr, "D3D12 CreateCommandQueue failed")?;
+        let cq = ComPtr::from(cq as *mut ID3D12CommandQueue);
+
+        // Initialize SwapChain
+        let scdesc = winapi::shared::dxgi1_2::DXGI_SWAP_CHAIN_DESC1 {
+            Width: extent.width, Height: extent.height, Format: winapi::shared::dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM,
+            SampleDesc: winapi::shared::dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            BufferCount: renderer.buffer_count(), BufferUsage: winapi::shared::dxgitype::DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            Scaling: winapi::shared::dxgi1_2::DXGI_SCALING_STRETCH,
+            SwapEffect: winapi::shared::dxgi::DXGI_SWAP_EFFECT_FLIP_DISCARD,
+            AlphaMode: winapi::shared::dxgi1_2::DXGI_ALPHA_MODE_PREMULTIPLIED,
+            Flags: winapi::shared::dxgi::DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
+            .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+        };
+        let mut sc = std::ptr::null_mut();
+        let hr = unsafe { factory.CreateSwapChainForComposition(cq.as_ptr() as _, &scdesc, std::ptr::null_mut(), &mut sc) };
+        hr_check(hr, "DXGI CreateSwapChainForComposition failed")?;
+        let sc = ComPtr::from(sc);
+        let mut sc3 = std::ptr::null_mut();
+        let hr = unsafe { sc.QueryInterface(&winapi::shared::dxgi1_4::IDXGISwapChain3::uuidof(), &mut sc3) };
+        hr_check(hr, "Querying IDXGISwapChain3 failed")?;
+        let sc = ComPtr::from(sc3 as *mut winapi::shared::dxgi1_4::IDXGISwapChain3);
+        let hr = unsafe { sc.SetMaximumFrameLatency(renderer.max_frame_latency()) };
+        hr_check(hr, "SwapChain SetMaximumFrameLatency failed")?;
+        let sc_waitable = unsafe { sc.GetFrameLatencyWaitableObject() };
+        let mut fence = std::ptr::null_mut();
+        let hr = unsafe { device12.CreateFence(0, D3D12_FENCE_FLAG_SHARED, &ID3D12Fence::uuidof(), &mut fence) };
+        hr_check(hr, "D3D12 CreateFence failed")?;
+        let fence12 = ComPtr::from(fence as *mut ID3D12Fence);
+        let mut fence_handle = std::ptr::null_mut();
+        let hr = unsafe { device12.CreateSharedHandle(fence12.as_ptr() as _, std::ptr::null(), winapi::um::winnt::GENERIC_ALL, std::ptr::null(), &mut fence_handle) };
+        hr_check(hr, "D3D12 CreateSharedHandle for Fence failed")?;
+        let present_timeline = TimelineSemaphore::import_d3d12_fence(renderer.device().clone(), fence_handle);
+        unsafe { winapi::um::handleapi::CloseHandle(fence_handle); }
+        let present_timeline = present_timeline?;
+
+        let render_timeline = TimelineSemaphore::create(renderer.device().clone(), br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_D3D12_FENCE_BIT)?;
+        let render_fence_handle = render_timeline.export_d3d12_fence()?;
+        let mut render_fence = std::ptr::null_mut();
+        let hr = unsafe { device12.OpenSharedHandle(render_fence_handle, &ID3D12Fence::uuidof(), &mut render_fence) };
+        unsafe { winapi::um::handleapi::CloseHandle(render_fence_handle); }
+        hr_check(hr, "D3D12 OpenSharedHandle for Fence failed")?;
+        let render_fence12 = ComPtr::from(render_fence as *mut ID3D12Fence);
+
+        // Initialize DirectComposition
+        let mut comp_device = std::ptr::null_mut();
+        let hr = unsafe { winapi::um::dcomp::DCompositionCreateDevice2(std::ptr::null(), &winapi::um::dcomp::IDCompositionDesktopDevice::uuidof(), &mut comp_device) };
+        hr_check(hr, "DCompositionCreateDevice2 failed")?;
+        let comp_device = ComPtr::from(comp_device as *mut winapi::um::dcomp::IDCompositionDesktopDevice);
+        let mut target = std::ptr::null_mut();
+        let hr = unsafe { comp_device.CreateTargetForHwnd(w, 0, &mut target) };
+        hr_check(hr, "DComposition CreateTargetForHwnd failed")?;
+        let target = ComPtr::from(target);
+        let mut root = std::ptr::null_mut();
+        let hr = unsafe { comp_device.CreateVisual(&mut root) };
+        hr_check(hr, "DComposition CreateVisual failed")?;
+        let root = ComPtr::from(root);
+        let hr = unsafe { root.SetContent(sc.as_ptr() as _) };
+        hr_check(hr, "DComposition SetContent for Visual failed")?;
+        let hr = unsafe { target.SetRoot(root.as_ptr() as _) };
+        hr_check(hr, "DComposition SetRoot for Target failed")?;
+        let hr = unsafe { comp_device.Commit() };
+        hr_check(hr, "DComposition Commit failed")?;
+
+        let fence_event = unsafe { winapi::um::synchapi::CreateEventA(std::ptr::null_mut(), false as _, false as _, std::ptr::null()) };
+
+        let mut this = DxgiPresenter {
+            device: renderer.device().clone(),
+            device12,
+            cq,
+            sc,
+            sc_waitable,
+            fence12,
+            fence_event,
+            fence_value: 0,
+            present_timeline,
+            present_values: vec![0; renderer.buffer_count() as _],
+            render_timeline,
+            render_fence12,
+            render_value: 0,
+            render_values: vec![0; renderer.buffer_count() as _],
+            buffer_count: renderer.buffer_count(),
+            next: 0,
+            extent: br::vk::VkExtent2D { width: extent.width, height: extent.height },
+            _comp_device: comp_device,
+            _target: target,
+            _root: root,
+            backbuffers: Vec::with_capacity(renderer.buffer_count() as _)
+        };
+        this.import_backbuffers()?;
+
+        Ok(this)
+    }
+
+    // Create Shared Object from Swapchain Backbuffers
+    fn import_backbuffers(&mut self) -> Result<()> {
+        let vk_device = self.device.native_ptr();
+
+        let vk_get_memory_win32_handle_properties_khr: br::vk::PFN_vkGetMemoryWin32HandlePropertiesKHR = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetDeviceProcAddr(vk_device, b"vkGetMemoryWin32HandlePropertiesKHR\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkGetMemoryWin32HandlePropertiesKHR not found?"))?
+            )
+        };
+        for n in 0..self.buffer_count {
+            let mut res = std::ptr::null_mut();
+            let hr = unsafe { self.sc.GetBuffer(n as _, &winapi::um::d3d12::ID3D12Resource::uuidof(), &mut res) };
+            hr_check(hr, "SwapChain GetBuffer failed")?;
+            let res = ComPtr::from(res as *mut winapi::um::d3d12::ID3D12Resource);
+            let mut sh = std::ptr::null_mut();
+            let name = widestring::WideCString::from_str(format!("LocalSharedBackBufferResource{}", n)).expect("WideCString encoding failed");
+            let hr = unsafe { self.device12.CreateSharedHandle(res.as_ptr() as _, std::ptr::null(), winapi::um::winnt::GENERIC_ALL, name.as_ptr(), &mut sh) };
+            hr_check(hr, "D3D12 CreateSharedHandle failed")?;
+            // registered first so that failures below release what has been created so far
+            self.backbuffers.push(ImportedBackbuffer {
+                shared_handle: sh,
+                memory: Allocation::default(),
+                image: br::vk::VK_NULL_HANDLE as _
+            });
+            let bb = self.backbuffers.last_mut().expect("no backbuffers");
+
+            let image_extmem_info = br::vk::VkExternalMemoryImageCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_EXTERNAL_MEMORY_IMAGE_CREATE_INFO,
+                pNext: std::ptr::null(),
+                handleTypes: br::vk::VK_EXTERNAL_MEMORY_HANDLE_TYPE_D3D12_RESOURCE_BIT
+            };
+            let image_cinfo = br::vk::VkImageCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+                pNext: &image_extmem_info as *const _ as _,
+                imageType: br::vk::VK_IMAGE_TYPE_2D,
+                format: BACKBUFFER_FORMAT,
+                extent: br::vk::VkExtent3D { width: self.extent.width, height: self.extent.height, depth: 1 },
+                mipLevels: 1,
+                arrayLayers: 1,
+                samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+                tiling: br::vk::VK_IMAGE_TILING_OPTIMAL,
+                usage: br::vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT,
+                sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+                queueFamilyIndexCount: 0,
+                pQueueFamilyIndices: std::ptr::null(),
+                initialLayout: br::vk::VK_IMAGE_LAYOUT_PREINITIALIZED,
+                flags: 0
+            };
+            let r = unsafe { br::vk::vkCreateImage(vk_device, &image_cinfo, std::ptr::null(), &mut bb.image) };
+            vk_check(r, "vkCreateImage failed")?;
+            let mut img_requirements = std::mem::MaybeUninit::uninit();
+            unsafe { br::vk::vkGetImageMemoryRequirements(vk_device, bb.image, img_requirements.as_mut_ptr()) };
+            let img_requirements = unsafe { img_requirements.assume_init() };
+
+            let mut props = br::vk::VkMemoryWin32HandlePropertiesKHR {
+                sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_WIN32_HANDLE_PROPERTIES_KHR,
+                pNext: std::ptr::null_mut(),
+                .. unsafe { std::mem::MaybeUninit::uninit().assume_init() }
+            };
+            let r = (vk_get_memory_win32_handle_properties_khr)(vk_device, br::vk::VK_EXTERNAL_MEMORY_HANDLE_TYPE_D3D12_RESOURCE_BIT, sh, &mut props);
+            vk_check(r, "vkGetMemoryWin32HandlePropertiesKHR failed")?;
+            let import_memory_info = br::vk::VkImportMemoryWin32HandleInfoKHR {
+                sType: br::vk::VK_STRUCTURE_TYPE_IMPORT_MEMORY_WIN32_HANDLE_INFO_KHR,
+                pNext: std::ptr::null(),
+                handleType: br::vk::VK_EXTERNAL_MEMORY_HANDLE_TYPE_D3D12_RESOURCE_BIT,
+                handle: sh,
+                name: name.as_ptr()
+            };
+            // the size is ignored for D3D12 resources
+            bb.memory = unsafe {
+                self.device.allocator().allocate_dedicated(
+                    img_requirements.size, props.memoryTypeBits & img_requirements.memoryTypeBits, MemoryUsage::DEVICE_LOCAL,
+                    br::vk::VK_NULL_HANDLE as _, &import_memory_info as *const _ as _
+                )?
+            };
+            let r = unsafe { br::vk::vkBindImageMemory(vk_device, bb.image, bb.memory.memory(), 0) };
+            vk_check(r, "vkBindImageMemory failed")?;
+        }
+        self.next = unsafe { self.sc.GetCurrentBackBufferIndex() as _ };
+
+        Ok(())
+    }
+
+    /// Blocks until the D3D12 queue has processed every present so far.
+    fn wait_presents(&self) -> Result<()> {
+        let hr = unsafe { self.fence12.SetEventOnCompletion(self.fence_value, self.fence_event) };
+        hr_check(hr, "Fence Event Setting failed")?;
+        unsafe { winapi::um::synchapi::WaitForSingleObject(self.fence_event, winapi::um::winbase::INFINITE) };
+
+        Ok(())
+    }
+
+    fn release_backbuffers(&mut self) {
+        let vk_device = self.device.native_ptr();
+
+        for bb in self.backbuffers.drain(..) {
+            unsafe {
+                br::vk::vkDestroyImage(vk_device, bb.image, std::ptr::null());
+                winapi::um::handleapi::CloseHandle(bb.shared_handle);
+            }
+            self.device.allocator().free(bb.memory);
+        }
+    }
+}
+impl PresentTarget for DxgiPresenter {
+    fn format(&self) -> br::vk::VkFormat { BACKBUFFER_FORMAT }
+    fn extent(&self) -> br::vk::VkExtent2D {
+        br::vk::VkExtent2D { width: self.extent.width, height: self.extent.height }
+    }
+    /// Imported backbuffer images, in swapchain buffer order.
+    fn backbuffer_images(&self) -> Vec<br::vk::VkImage> {
+        self.backbuffers.iter().map(|b| b.image).collect()
+    }
+
+    /// Waits for the swapchain to accept a new frame; rendering waits on the GPU until the backbuffer's last present.
+    fn acquire_next_image(&mut self) -> Result<AcquiredImage> {
+        // paces the CPU only, the queues keep running
+        unsafe { winapi::um::synchapi::WaitForSingleObjectEx(self.sc_waitable, winapi::um::winbase::INFINITE, false as _) };
+        let index = self.next;
+        self.next = (index + 1) % self.backbuffers.len();
+
+        Ok(AcquiredImage { index, wait_semaphore: self.present_timeline.native_ptr(), wait_value: self.present_values[index] })
+    }
+    fn render_complete_timeline(&mut self, index: usize) -> Option<TimelinePoint> {
+        self.render_value += 1;
+        self.render_values[index] = self.render_value;
+
+        Some(self.render_timeline.point(self.render_value))
+    }
+    /// Presents the current backbuffer once the D3D12 queue has seen its rendering finish.
+    fn present(&mut self, index: usize) -> Result<()> {
+        debug_assert_eq!(index, unsafe { self.sc.GetCurrentBackBufferIndex() as usize });
+        let hr = unsafe { self.cq.Wait(self.render_fence12.as_ptr(), self.render_values[index]) };
+        hr_check(hr, "Fence waiting failed")?;
+        let hr = unsafe { self.sc.Present(0, 0) };
+        hr_check(hr, "SwapChain Present failed")?;
+        self.fence_value += 1;
+        let hr = unsafe { self.cq.Signal(self.fence12.as_ptr(), self.fence_value) };
+        hr_check(hr, "Fence signaling failed")?;
+        self.present_values[index] = self.fence_value;
+
+        Ok(())
+    }
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        // every reference to the swapchain buffers has to be gone before ResizeBuffers
+        self.wait_presents()?;
+        self.release_backbuffers();
+        let hr = unsafe {
+            self.sc.ResizeBuffers(
+                self.buffer_count, width, height, winapi::shared::dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM,
+                winapi::shared::dxgi::DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT
+            )
+        };
+        hr_check(hr, "SwapChain ResizeBuffers failed")?;
+        self.extent = br::vk::VkExtent2D { width, height };
+        // the new buffers have not been presented yet
+        for v in &mut self.present_values { *v = 0; }
+
+        self.import_backbuffers()
+    }
+}
+impl Drop for DxgiPresenter {
+    fn drop(&mut self) {
+        let _ = self.device.wait_idle();
+        let _ = self.wait_presents();
+
+        self.release_backbuffers();
+        unsafe { winapi::um::handleapi::CloseHandle(self.fence_event); }
+    }
+}
@@ -0,0 +1,429 @@
+use bedrock as br;
+use crate::{vk_check, AcquiredImage, Allocation, Device, Error, MemoryUsage, PresentTarget, Renderer, Result, TimelinePoint, TimelineSemaphore};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::rc::Rc;
+
+/// Kind of file descriptor shared images are exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdHandleType {
+    /// Driver-private handle; importable only by the same driver on the same physical device.
+    OpaqueFd,
+    /// Linux dma-buf, importable by other drivers and APIs as well. Images are linearly tiled,
+    /// since without DRM format modifiers there is no other layout both sides can agree on.
+    DmaBuf
+}
+impl FdHandleType {
+    /// Device extensions the renderer must be created with to share images through this handle type.
+    pub fn device_extensions(self) -> &'static [&'static str] {
+        match self {
+            FdHandleType::OpaqueFd => &["VK_KHR_external_memory_fd"],
+            FdHandleType::DmaBuf => &["VK_KHR_external_memory_fd", "VK_EXT_external_memory_dma_buf"]
+        }
+    }
+
+    fn bits(self) -> br::vk::VkExternalMemoryHandleTypeFlags {
+        match self {
+            FdHandleType::OpaqueFd => br::vk::VK_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD_BIT,
+            FdHandleType::DmaBuf => br::vk::VK_EXTERNAL_MEMORY_HANDLE_TYPE_DMA_BUF_BIT
+        }
+    }
+    fn tiling(self) -> br::vk::VkImageTiling {
+        match self {
+            FdHandleType::OpaqueFd => br::vk::VK_IMAGE_TILING_OPTIMAL,
+            FdHandleType::DmaBuf => br::vk::VK_IMAGE_TILING_LINEAR
+        }
+    }
+}
+
+/// Everything besides the fd that the importing side needs to recreate a shared image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedImageDesc {
+    pub handle_type: FdHandleType,
+    pub format: br::vk::VkFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the exported allocation; imports have to allocate exactly this much.
+    pub allocation_size: u64,
+    /// Whether the memory is a dedicated allocation; imports have to match.
+    pub dedicated: bool
+}
+
+/// 2D color image whose memory is exported to, or imported from, a file descriptor.
+pub struct SharedImage {
+    device: Rc<Device>,
+    desc: SharedImageDesc,
+    image: br::vk::VkImage,
+    view: br::vk::VkImageView,
+    memory: Allocation
+}
+impl SharedImage {
+    /// Usage of every shared image. Opaque fd imports must be created exactly like the exported image,
+    /// so both sides use the same set.
+    pub const USAGE: br::vk::VkImageUsageFlags = br::vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT
+        | br::vk::VK_IMAGE_USAGE_SAMPLED_BIT
+        | br::vk::VK_IMAGE_USAGE_TRANSFER_SRC_BIT
+        | br::vk::VK_IMAGE_USAGE_TRANSFER_DST_BIT;
+
+    /// Allocates an image whose memory can be handed out with [`SharedImage::export_fd`].
+    pub fn new_exportable(
+        renderer: &Renderer, handle_type: FdHandleType, format: br::vk::VkFormat, width: u32, height: u32
+    ) -> Result<Self> {
+        SharedImage::exportable(renderer.device().clone(), handle_type, format, width, height)
+    }
+
+    fn exportable(device: Rc<Device>, handle_type: FdHandleType, format: br::vk::VkFormat, width: u32, height: u32) -> Result<Self> {
+        let dedicated = dedicated_only(&device, handle_type, format, br::vk::VK_EXTERNAL_MEMORY_FEATURE_EXPORTABLE_BIT)?;
+        let mut this = SharedImage::create(
+            device, SharedImageDesc { handle_type, format, width, height, allocation_size: 0, dedicated }
+        )?;
+        let vk_device = this.device.native_ptr();
+
+        let mut img_requirements = std::mem::MaybeUninit::uninit();
+        unsafe { br::vk::vkGetImageMemoryRequirements(vk_device, this.image, img_requirements.as_mut_ptr()) };
+        let img_requirements: br::vk::VkMemoryRequirements = unsafe { img_requirements.assume_init() };
+        this.desc.allocation_size = img_requirements.size;
+
+        let export_info = br::vk::VkExportMemoryAllocateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_EXPORT_MEMORY_ALLOCATE_INFO,
+            pNext: std::ptr::null(),
+            handleTypes: handle_type.bits()
+        };
+        this.memory = unsafe {
+            this.device.allocator().allocate_dedicated(
+                img_requirements.size, img_requirements.memoryTypeBits, MemoryUsage::DEVICE_LOCAL,
+                if dedicated { this.image } else { br::vk::VK_NULL_HANDLE as _ }, &export_info as *const _ as _
+            )?
+        };
+        let r = unsafe { br::vk::vkBindImageMemory(vk_device, this.image, this.memory.memory(), 0) };
+        vk_check(r, "vkBindImageMemory failed")?;
+        this.create_view()?;
+
+        Ok(this)
+    }
+
+    /// Imports an image exported as `desc` from `fd`. The fd is consumed, even on failure.
+    pub fn import(renderer: &Renderer, desc: &SharedImageDesc, fd: OwnedFd) -> Result<Self> {
+        SharedImage::imported(renderer.device().clone(), desc, fd)
+    }
+
+    pub(crate) fn imported(device: Rc<Device>, desc: &SharedImageDesc, fd: OwnedFd) -> Result<Self> {
+        if dedicated_only(&device, desc.handle_type, desc.format, br::vk::VK_EXTERNAL_MEMORY_FEATURE_IMPORTABLE_BIT)? && !desc.dedicated {
+            return Err(Error::Unsupported("handle type requires a dedicated allocation, but the exporter did not make one"));
+        }
+        let mut this = SharedImage::create(device, *desc)?;
+        let vk_device = this.device.native_ptr();
+
+        let mut img_requirements = std::mem::MaybeUninit::uninit();
+        unsafe { br::vk::vkGetImageMemoryRequirements(vk_device, this.image, img_requirements.as_mut_ptr()) };
+        let img_requirements: br::vk::VkMemoryRequirements = unsafe { img_requirements.assume_init() };
+        let mut memory_type_bits = img_requirements.memoryTypeBits;
+        // opaque fds carry no properties of their own; they are only valid for the exporting device anyway
+        if desc.handle_type == FdHandleType::DmaBuf {
+            let vk_get_memory_fd_properties_khr: br::vk::PFN_vkGetMemoryFdPropertiesKHR = unsafe {
+                std::mem::transmute(
+                    br::vk::vkGetDeviceProcAddr(vk_device, b"vkGetMemoryFdPropertiesKHR\0".as_ptr() as _)
+                        .ok_or(Error::Unsupported("vkGetMemoryFdPropertiesKHR not found?"))?
+                )
+            };
+            let mut props = br::vk::VkMemoryFdPropertiesKHR {
+                sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_FD_PROPERTIES_KHR,
+                pNext: std::ptr::null_mut(),
+                memoryTypeBits: 0
+            };
+            let r = (vk_get_memory_fd_properties_khr)(vk_device, desc.handle_type.bits(), fd.as_raw_fd(), &mut props);
+            vk_check(r, "vkGetMemoryFdPropertiesKHR failed")?;
+            memory_type_bits &= props.memoryTypeBits;
+        }
+
+        let import_info = br::vk::VkImportMemoryFdInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMPORT_MEMORY_FD_INFO_KHR,
+            pNext: std::ptr::null(),
+            handleType: desc.handle_type.bits(),
+            fd: fd.as_raw_fd()
+        };
+        this.memory = unsafe {
+            this.device.allocator().allocate_dedicated(
+                desc.allocation_size, memory_type_bits, MemoryUsage::DEVICE_LOCAL,
+                if desc.dedicated { this.image } else { br::vk::VK_NULL_HANDLE as _ }, &import_info as *const _ as _
+            )?
+        };
+        // a successful import takes ownership of the fd
+        let _ = fd.into_raw_fd();
+        let r = unsafe { br::vk::vkBindImageMemory(vk_device, this.image, this.memory.memory(), 0) };
+        vk_check(r, "vkBindImageMemory failed")?;
+        this.create_view()?;
+
+        Ok(this)
+    }
+
+    fn create(device: Rc<Device>, desc: SharedImageDesc) -> Result<Self> {
+        let mut this = SharedImage {
+            device,
+            desc,
+            image: br::vk::VK_NULL_HANDLE as _,
+            view: br::vk::VK_NULL_HANDLE as _,
+            memory: Allocation::default()
+        };
+
+        let image_extmem_info = br::vk::VkExternalMemoryImageCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_EXTERNAL_MEMORY_IMAGE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            handleTypes: desc.handle_type.bits()
+        };
+        let image_cinfo = br::vk::VkImageCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+            pNext: &image_extmem_info as *const _ as _,
+            imageType: br::vk::VK_IMAGE_TYPE_2D,
+            format: desc.format,
+            extent: br::vk::VkExtent3D { width: desc.width, height: desc.height, depth: 1 },
+            mipLevels: 1,
+            arrayLayers: 1,
+            samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+            tiling: desc.handle_type.tiling(),
+            usage: Self::USAGE,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null(),
+            initialLayout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED,
+            flags: 0
+        };
+        let r = unsafe { br::vk::vkCreateImage(this.device.native_ptr(), &image_cinfo, std::ptr::null(), &mut this.image) };
+        vk_check(r, "vkCreateImage failed")?;
+
+        Ok(this)
+    }
+
+    /// Color view of the whole image, for sampling it; created once the memory is bound.
+    fn create_view(&mut self) -> Result<()> {
+        let iv_cinfo = br::vk::VkImageViewCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            image: self.image,
+            viewType: br::vk::VK_IMAGE_VIEW_TYPE_2D,
+            format: self.desc.format,
+            components: br::vk::VkComponentMapping {
+                r: br::vk::VK_COMPONENT_SWIZZLE_R,
+                g: br::vk::VK_COMPONENT_SWIZZLE_G,
+                b: br::vk::VK_COMPONENT_SWIZZLE_B,
+                a: br::vk::VK_COMPONENT_SWIZZLE_A
+            },
+            subresourceRange: br::vk::VkImageSubresourceRange {
+                aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+                baseMipLevel: 0,
+                levelCount: 1,
+                baseArrayLayer: 0,
+                layerCount: 1
+            }
+        };
+        let r = unsafe { br::vk::vkCreateImageView(self.device.native_ptr(), &iv_cinfo, std::ptr::null(), &mut self.view) };
+        vk_check(r, "vkCreateImageView for SharedImage failed")
+    }
+
+    /// Exports a new fd referring to the image memory. Each call returns a separate descriptor.
+    pub fn export_fd(&self) -> Result<OwnedFd> {
+        let vk_device = self.device.native_ptr();
+        let vk_get_memory_fd_khr: br::vk::PFN_vkGetMemoryFdKHR = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetDeviceProcAddr(vk_device, b"vkGetMemoryFdKHR\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkGetMemoryFdKHR not found?"))?
+            )
+        };
+        let get_info = br::vk::VkMemoryGetFdInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_GET_FD_INFO_KHR,
+            pNext: std::ptr::null(),
+            memory: self.memory.memory(),
+            handleType: self.desc.handle_type.bits()
+        };
+        let mut fd = -1;
+        let r = (vk_get_memory_fd_khr)(vk_device, &get_info, &mut fd);
+        vk_check(r, "vkGetMemoryFdKHR failed")?;
+
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    pub fn desc(&self) -> &SharedImageDesc { &self.desc }
+    pub fn image(&self) -> br::vk::VkImage { self.image }
+    /// View of the image for sampling, e.g. with [`TextureSet::for_view`](crate::TextureSet::for_view).
+    pub fn view(&self) -> br::vk::VkImageView { self.view }
+}
+impl Drop for SharedImage {
+    fn drop(&mut self) {
+        let vk_device = self.device.native_ptr();
+
+        unsafe {
+            br::vk::vkDestroyImageView(vk_device, self.view, std::ptr::null());
+            br::vk::vkDestroyImage(vk_device, self.image, std::ptr::null());
+        }
+        self.device.allocator().free(std::mem::take(&mut self.memory));
+    }
+}
+
+/// Checks that shared images of `format` support `feature` for `handle_type`, and whether they need a dedicated allocation.
+fn dedicated_only(
+    device: &Device, handle_type: FdHandleType, format: br::vk::VkFormat, feature: br::vk::VkExternalMemoryFeatureFlags
+) -> Result<bool> {
+    let vk_get_physical_device_image_format_properties2: br::vk::PFN_vkGetPhysicalDeviceImageFormatProperties2 = unsafe {
+        std::mem::transmute(
+            br::vk::vkGetInstanceProcAddr(device.instance(), b"vkGetPhysicalDeviceImageFormatProperties2\0".as_ptr() as _)
+                .ok_or(Error::Unsupported("vkGetPhysicalDeviceImageFormatProperties2 not found?"))?
+        )
+    };
+    let external_info = br::vk::VkPhysicalDeviceExternalImageFormatInfo {
+        sType: br::vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_EXTERNAL_IMAGE_FORMAT_INFO,
+        pNext: std::ptr::null(),
+        handleType: handle_type.bits()
+    };
+    let format_info = br::vk::VkPhysicalDeviceImageFormatInfo2 {
+        sType: br::vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_IMAGE_FORMAT_INFO_2,
+        pNext: &external_info as *const _ as _,
+        format,
+        _type: br::vk::VK_IMAGE_TYPE_2D,
+        tiling: handle_type.tiling(),
+        usage: SharedImage::USAGE,
+        flags: 0
+    };
+    let mut external_props = br::vk::VkExternalImageFormatProperties {
+        sType: br::vk::VK_STRUCTURE_TYPE_EXTERNAL_IMAGE_FORMAT_PROPERTIES,
+        pNext: std::ptr::null_mut(),
+        .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+    };
+    let mut props = br::vk::VkImageFormatProperties2 {
+        sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_FORMAT_PROPERTIES_2,
+        pNext: &mut external_props as *mut _ as _,
+        .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+    };
+    let r = (vk_get_physical_device_image_format_properties2)(device.adapter(), &format_info, &mut props);
+    if r == br::vk::VK_ERROR_FORMAT_NOT_SUPPORTED {
+        return Err(Error::Unsupported("format cannot be shared through the handle type"));
+    }
+    vk_check(r, "vkGetPhysicalDeviceImageFormatProperties2 failed")?;
+    let features = external_props.externalMemoryProperties.externalMemoryFeatures;
+    if (features & feature) == 0 {
+        return Err(Error::Unsupported("handle type cannot be exported/imported for the format"));
+    }
+
+    Ok((features & br::vk::VK_EXTERNAL_MEMORY_FEATURE_DEDICATED_ONLY_BIT) != 0)
+}
+
+/// Backbuffers in [`SharedImage`]s: the renderer draws into memory shared with another owner.
+///
+/// Nothing is shown; once an image is presented, it holds a finished frame (see [`SharedImageTarget::last_presented`]).
+/// With [`SharedImageTarget::enable_timeline`], images are presented at submission instead, and the frame is finished
+/// once the timeline reaches [`SharedImageTarget::last_presented_point`].
+pub struct SharedImageTarget {
+    device: Rc<Device>,
+    images: Vec<SharedImage>,
+    exported: bool,
+    next: usize,
+    last_presented: Option<usize>,
+    timeline: Option<TimelineSemaphore>,
+    /// Timeline value signaled by the latest frame rendered into each image.
+    frame_values: Vec<u64>,
+    timeline_value: u64
+}
+impl SharedImageTarget {
+    /// Allocates `image_count` exportable backbuffers in the renderer's extent.
+    pub fn new(renderer: &Renderer, handle_type: FdHandleType, image_count: usize) -> Result<Self> {
+        if image_count == 0 { return Err(Error::Unsupported("no images to render into")); }
+        let extent = renderer.extent();
+        let images = (0..image_count)
+            .map(|_| SharedImage::new_exportable(renderer, handle_type, crate::BACKBUFFER_FORMAT, extent.width, extent.height))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SharedImageTarget::with_images(renderer, images, true))
+    }
+
+    /// Renders into images imported from another owner. All of them must have the same format and size.
+    pub fn from_imported(renderer: &Renderer, images: Vec<SharedImage>) -> Result<Self> {
+        let first = images.first().ok_or(Error::Unsupported("no images to render into"))?.desc;
+        if images.iter().any(|i| (i.desc.format, i.desc.width, i.desc.height) != (first.format, first.width, first.height)) {
+            return Err(Error::Unsupported("imported images differ in format or size"));
+        }
+
+        Ok(SharedImageTarget::with_images(renderer, images, false))
+    }
+
+    fn with_images(renderer: &Renderer, images: Vec<SharedImage>, exported: bool) -> Self {
+        SharedImageTarget {
+            device: renderer.device().clone(),
+            frame_values: vec![0; images.len()],
+            images,
+            exported,
+            next: 0,
+            last_presented: None,
+            timeline: None,
+            timeline_value: 0
+        }
+    }
+
+    /// Signals render completion through an exportable timeline semaphore from now on; call before the first frame.
+    ///
+    /// Needs [`TimelineSemaphore::FD_DEVICE_EXTENSIONS`]. Returns the semaphore, e.g. to hand out with `export_fd`.
+    pub fn enable_timeline(&mut self) -> Result<&TimelineSemaphore> {
+        if self.timeline.is_none() {
+            self.timeline = Some(TimelineSemaphore::create(self.device.clone(), br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT)?);
+        }
+
+        Ok(self.timeline.as_ref().expect("no timeline"))
+    }
+    pub fn timeline(&self) -> Option<&TimelineSemaphore> { self.timeline.as_ref() }
+
+    pub fn images(&self) -> &[SharedImage] { &self.images }
+    /// Index of the image holding the most recently finished frame; with a timeline, the most recently submitted one.
+    pub fn last_presented(&self) -> Option<usize> { self.last_presented }
+    /// Timeline point at which the frame in [`SharedImageTarget::last_presented`] is finished.
+    pub fn last_presented_point(&self) -> Option<TimelinePoint> {
+        let timeline = self.timeline.as_ref()?;
+        self.last_presented.map(|n| timeline.point(self.frame_values[n]))
+    }
+}
+impl PresentTarget for SharedImageTarget {
+    fn format(&self) -> br::vk::VkFormat { self.images[0].desc.format }
+    fn extent(&self) -> br::vk::VkExtent2D {
+        br::vk::VkExtent2D { width: self.images[0].desc.width, height: self.images[0].desc.height }
+    }
+    fn backbuffer_images(&self) -> Vec<br::vk::VkImage> {
+        self.images.iter().map(|i| i.image).collect()
+    }
+
+    fn acquire_next_image(&mut self) -> Result<AcquiredImage> {
+        let index = self.next;
+        self.next = (index + 1) % self.images.len();
+        Ok(AcquiredImage { index, wait_semaphore: br::vk::VK_NULL_HANDLE as _, wait_value: 0 })
+    }
+    fn render_complete_timeline(&mut self, index: usize) -> Option<TimelinePoint> {
+        let timeline = self.timeline.as_ref()?;
+        self.timeline_value += 1;
+        self.frame_values[index] = self.timeline_value;
+
+        Some(timeline.point(self.timeline_value))
+    }
+    fn present(&mut self, index: usize) -> Result<()> {
+        self.last_presented = Some(index);
+
+        Ok(())
+    }
+    /// Reallocates exported images; previously exported fds keep referring to the old memory.
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        // the memory belongs to someone else, who has to share resized images instead
+        if !self.exported { return Err(Error::Unsupported("imported images cannot be resized")); }
+
+        self.device.wait_idle()?;
+        let (handle_type, format) = (self.images[0].desc.handle_type, self.images[0].desc.format);
+        let image_count = self.images.len();
+        self.images.clear();
+        self.next = 0;
+        self.last_presented = None;
+        for _ in 0..image_count {
+            self.images.push(SharedImage::exportable(self.device.clone(), handle_type, format, width, height)?);
+        }
+
+        Ok(())
+    }
+}
+impl Drop for SharedImageTarget {
+    fn drop(&mut self) {
+        let _ = self.device.wait_idle();
+    }
+}
@@ -0,0 +1,226 @@
+use bedrock as br;
+use crate::{vk_check, AcquiredImage, Allocation, Device, Error, MemoryUsage, PresentTarget, Renderer, Result};
+use crate::renderer::BACKBUFFER_FORMAT;
+use std::rc::Rc;
+
+struct OffscreenImage {
+    memory: Allocation,
+    image: br::vk::VkImage
+}
+
+/// Device-local backbuffers with no window system behind them.
+///
+/// Presenting copies the image into a host-visible buffer, so the frame can be read with [`HeadlessTarget::pixels`].
+pub struct HeadlessTarget {
+    device: Rc<Device>,
+    extent: br::vk::VkExtent2D,
+    image_count: usize,
+    images: Vec<OffscreenImage>,
+    next: usize,
+    readback_buffer: br::vk::VkBuffer,
+    readback_memory: Allocation,
+    fence: br::vk::VkFence,
+    pixels: Vec<u8>
+}
+impl HeadlessTarget {
+    /// Creates [`Renderer::buffer_count`] backbuffers at the renderer's extent.
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        let extent = renderer.extent();
+        let image_count = renderer.buffer_count() as usize;
+        let mut this = HeadlessTarget {
+            device: renderer.device().clone(),
+            extent: br::vk::VkExtent2D { width: extent.width, height: extent.height },
+            image_count,
+            images: Vec::with_capacity(image_count),
+            next: 0,
+            readback_buffer: br::vk::VK_NULL_HANDLE as _,
+            readback_memory: Allocation::default(),
+            fence: br::vk::VK_NULL_HANDLE as _,
+            pixels: Vec::new()
+        };
+        let fence_cinfo = br::vk::VkFenceCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_FENCE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0
+        };
+        let r = unsafe { br::vk::vkCreateFence(this.device.native_ptr(), &fence_cinfo, std::ptr::null(), &mut this.fence) };
+        vk_check(r, "vkCreateFence failed")?;
+        this.create_images()?;
+
+        Ok(this)
+    }
+
+    fn create_images(&mut self) -> Result<()> {
+        let vk_device = self.device.native_ptr();
+
+        for _ in 0..self.image_count {
+            // registered first so that failures below release what has been created so far
+            self.images.push(OffscreenImage {
+                memory: Allocation::default(),
+                image: br::vk::VK_NULL_HANDLE as _
+            });
+            let img = self.images.last_mut().expect("no images");
+
+            let image_cinfo = br::vk::VkImageCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+                pNext: std::ptr::null(),
+                imageType: br::vk::VK_IMAGE_TYPE_2D,
+                format: BACKBUFFER_FORMAT,
+                extent: br::vk::VkExtent3D { width: self.extent.width, height: self.extent.height, depth: 1 },
+                mipLevels: 1,
+                arrayLayers: 1,
+                samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+                tiling: br::vk::VK_IMAGE_TILING_OPTIMAL,
+                usage: br::vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT | br::vk::VK_IMAGE_USAGE_TRANSFER_SRC_BIT,
+                sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+                queueFamilyIndexCount: 0,
+                pQueueFamilyIndices: std::ptr::null(),
+                initialLayout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED,
+                flags: 0
+            };
+            let r = unsafe { br::vk::vkCreateImage(vk_device, &image_cinfo, std::ptr::null(), &mut img.image) };
+            vk_check(r, "vkCreateImage failed")?;
+            img.memory = unsafe { self.device.allocator().allocate_image(img.image, MemoryUsage::DEVICE_LOCAL, false)? };
+        }
+
+        let buffer_cinfo = br::vk::VkBufferCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            size: self.pixels_size() as _,
+            usage: br::vk::VK_BUFFER_USAGE_TRANSFER_DST_BIT,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkCreateBuffer(vk_device, &buffer_cinfo, std::ptr::null(), &mut self.readback_buffer) };
+        vk_check(r, "vkCreateBuffer for Readback failed")?;
+        self.readback_memory = unsafe { self.device.allocator().allocate_buffer(self.readback_buffer, MemoryUsage::READBACK)? };
+
+        Ok(())
+    }
+
+    fn pixels_size(&self) -> usize { self.extent.width as usize * self.extent.height as usize * 4 }
+
+    fn destroy_images(&mut self) {
+        let vk_device = self.device.native_ptr();
+
+        for img in self.images.drain(..) {
+            unsafe { br::vk::vkDestroyImage(vk_device, img.image, std::ptr::null()) };
+            self.device.allocator().free(img.memory);
+        }
+        unsafe { br::vk::vkDestroyBuffer(vk_device, self.readback_buffer, std::ptr::null()) };
+        self.device.allocator().free(std::mem::take(&mut self.readback_memory));
+        self.readback_buffer = br::vk::VK_NULL_HANDLE as _;
+    }
+
+    /// Copies the backbuffer into the readback buffer and from there into `pixels`.
+    fn read_back(&mut self, index: usize) -> Result<()> {
+        let size = self.pixels_size();
+
+        let in_image_barriers = &[br::vk::VkImageMemoryBarrier {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: std::ptr::null(),
+            srcAccessMask: br::vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+            dstAccessMask: br::vk::VK_ACCESS_TRANSFER_READ_BIT,
+            srcQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+            dstQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+            oldLayout: br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL,
+            newLayout: br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL,
+            image: self.images[index].image,
+            subresourceRange: br::vk::VkImageSubresourceRange {
+                aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+                baseMipLevel: 0,
+                levelCount: 1,
+                baseArrayLayer: 0,
+                layerCount: 1
+            }
+        }];
+        let out_buffer_barriers = &[br::vk::VkBufferMemoryBarrier {
+            sType: br::vk::VK_STRUCTURE_TYPE_BUFFER_MEMORY_BARRIER,
+            pNext: std::ptr::null(),
+            srcAccessMask: br::vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+            dstAccessMask: br::vk::VK_ACCESS_HOST_READ_BIT,
+            srcQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+            dstQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+            buffer: self.readback_buffer,
+            offset: 0,
+            size: size as _
+        }];
+        let copy_regions = &[br::vk::VkBufferImageCopy {
+            bufferOffset: 0,
+            bufferRowLength: 0,
+            bufferImageHeight: 0,
+            imageSubresource: br::vk::VkImageSubresourceLayers {
+                aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+                mipLevel: 0,
+                baseArrayLayer: 0,
+                layerCount: 1
+            },
+            imageOffset: br::vk::VkOffset3D { x: 0, y: 0, z: 0 },
+            imageExtent: br::vk::VkExtent3D { width: self.extent.width, height: self.extent.height, depth: 1 }
+        }];
+        let (image, buffer) = (self.images[index].image, self.readback_buffer);
+        self.device.immediate_submit(self.fence, |cmd| unsafe {
+            br::vk::vkCmdPipelineBarrier(
+                cmd, br::vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, 0,
+                0, std::ptr::null(), 0, std::ptr::null(), in_image_barriers.len() as _, in_image_barriers.as_ptr()
+            );
+            br::vk::vkCmdCopyImageToBuffer(
+                cmd, image, br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, buffer, copy_regions.len() as _, copy_regions.as_ptr()
+            );
+            br::vk::vkCmdPipelineBarrier(
+                cmd, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, br::vk::VK_PIPELINE_STAGE_HOST_BIT, 0,
+                0, std::ptr::null(), out_buffer_barriers.len() as _, out_buffer_barriers.as_ptr(), 0, std::ptr::null()
+            );
+        })?;
+
+        let p = self.readback_memory.mapped_ptr().ok_or(Error::Unsupported("readback memory not mapped?"))?;
+        self.device.allocator().invalidate(&self.readback_memory, 0, size as _)?;
+        self.pixels.clear();
+        self.pixels.extend_from_slice(unsafe { std::slice::from_raw_parts(p as *const u8, size) });
+
+        Ok(())
+    }
+
+    /// Pixels of the last presented frame: tightly packed premultiplied RGBA8 rows, top row first.
+    ///
+    /// Empty until the first frame is presented (see [`Renderer::flush`]).
+    pub fn pixels(&self) -> &[u8] { &self.pixels }
+}
+impl PresentTarget for HeadlessTarget {
+    fn format(&self) -> br::vk::VkFormat { BACKBUFFER_FORMAT }
+    fn extent(&self) -> br::vk::VkExtent2D {
+        br::vk::VkExtent2D { width: self.extent.width, height: self.extent.height }
+    }
+    fn backbuffer_images(&self) -> Vec<br::vk::VkImage> {
+        self.images.iter().map(|i| i.image).collect()
+    }
+
+    fn present_layout(&self) -> br::vk::VkImageLayout { br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL }
+
+    fn acquire_next_image(&mut self) -> Result<AcquiredImage> {
+        let index = self.next;
+        self.next = (index + 1) % self.images.len();
+        Ok(AcquiredImage { index, wait_semaphore: br::vk::VK_NULL_HANDLE as _, wait_value: 0 })
+    }
+    fn present(&mut self, index: usize) -> Result<()> {
+        self.read_back(index)?;
+
+        Ok(())
+    }
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.destroy_images();
+        self.extent = br::vk::VkExtent2D { width, height };
+        self.next = 0;
+        self.pixels.clear();
+        self.create_images()
+    }
+}
+impl Drop for HeadlessTarget {
+    fn drop(&mut self) {
+        let _ = self.device.wait_idle();
+        self.destroy_images();
+        unsafe { br::vk::vkDestroyFence(self.device.native_ptr(), self.fence, std::ptr::null()) };
+    }
+}
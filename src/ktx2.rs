@@ -0,0 +1,68 @@
+//! KTX2 texture containers with pre-built mip levels.
+
+use bedrock as br;
+use crate::{texture::level_size, Error, Result};
+
+const IDENTIFIER: [u8; 12] = [0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+/// `KHR_DF_FLAG_ALPHA_PREMULTIPLIED` in the flags of the basic data format descriptor block.
+const DF_FLAG_ALPHA_PREMULTIPLIED: u8 = 1;
+
+fn invalid(what: &'static str) -> Error {
+    Error::Os("Parsing KTX2 failed", std::io::Error::new(std::io::ErrorKind::InvalidData, what))
+}
+
+/// A 2D image in a KTX2 container, borrowing the level data from the file contents.
+///
+/// Only files without supercompression are supported, so their levels can be copied to the device as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ktx2Image<'a> {
+    pub format: br::vk::VkFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Level 0 (the largest) first, each tightly packed.
+    pub levels: Vec<&'a [u8]>,
+    /// The file asks for the levels below 0 to be generated on load (`levelCount` 0).
+    pub generate_mipmaps: bool,
+    /// Color channels are premultiplied by alpha, according to the data format descriptor.
+    pub premultiplied: bool
+}
+impl<'a> Ktx2Image<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[..12] != IDENTIFIER { return Err(invalid("not a KTX2 file")); }
+        let u32_at = |o: usize| u32::from_le_bytes([bytes[o], bytes[o + 1], bytes[o + 2], bytes[o + 3]]);
+        let u64_at = |o: usize| u32_at(o) as u64 | (u32_at(o + 4) as u64) << 32;
+
+        let format = u32_at(12) as br::vk::VkFormat;
+        let (width, height, depth) = (u32_at(20), u32_at(24), u32_at(28));
+        let (layer_count, face_count, level_count) = (u32_at(32), u32_at(36), u32_at(40));
+        if format == br::vk::VK_FORMAT_UNDEFINED { return Err(Error::Unsupported("KTX2 files without a Vulkan format (Basis Universal)")); }
+        if u32_at(44) != 0 { return Err(Error::Unsupported("supercompressed KTX2 files")); }
+        if width == 0 || height == 0 || depth != 0 || layer_count > 1 || face_count != 1 {
+            return Err(Error::Unsupported("KTX2 files other than single 2D images"));
+        }
+        let entries = level_count.max(1) as usize;
+        if entries > 32 || bytes.len() < HEADER_SIZE + entries * LEVEL_INDEX_ENTRY_SIZE {
+            return Err(invalid("truncated level index"));
+        }
+        let levels = (0..entries).map(|n| {
+            let entry = HEADER_SIZE + n * LEVEL_INDEX_ENTRY_SIZE;
+            let (offset, length) = (u64_at(entry), u64_at(entry + 8));
+            let expected = level_size(format, (width >> n).max(1), (height >> n).max(1))
+                .ok_or(Error::Unsupported("KTX2 files in formats without a known texel size"))?;
+            if length != expected as u64 { return Err(invalid("level size does not match the format and extent")); }
+            match offset.checked_add(length) {
+                Some(end) if end <= bytes.len() as u64 => Ok(&bytes[offset as usize..end as usize]),
+                _ => Err(invalid("level data out of range"))
+            }
+        }).collect::<Result<Vec<_>>>()?;
+
+        let (dfd_offset, dfd_length) = (u32_at(48) as usize, u32_at(52) as usize);
+        // total size, then the basic block: two header words before the model, primaries, transfer and flags bytes
+        let premultiplied = dfd_length >= 16 && dfd_offset + 16 <= bytes.len() &&
+            (bytes[dfd_offset + 4 + 11] & DF_FLAG_ALPHA_PREMULTIPLIED) != 0;
+
+        Ok(Ktx2Image { format, width, height, levels, generate_mipmaps: level_count == 0, premultiplied })
+    }
+}
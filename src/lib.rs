@@ -0,0 +1,173 @@
+//! Vulkan rendering into composition swapchains without a redirection bitmap.
+//!
+//! A [`Renderer`] owns the Vulkan device and everything needed to draw a frame; a [`PresentTarget`]
+//! owns the backbuffers it draws into and shows them. Targets are selected with cargo features:
+//!
+//! - `dxgi` (default, Windows only): DirectComposition swapchain imported into Vulkan (`DxgiPresenter`)
+//! - `headless`: device-local images without any window system (`HeadlessTarget`)
+//! - `x11`: ARGB window on an X server, presented through a Vulkan swapchain (`XcbWindow` + `SwapchainTarget`)
+//! - `wayland`: xdg_shell toplevel, presented through a Vulkan swapchain (`WaylandWindow` + `SwapchainTarget`)
+//! - `external-fd` (Unix only): images shared with other processes or APIs as opaque fds or dma-bufs
+//!   (`SharedImage` + `SharedImageTarget`)
+//! - `share` (Linux only): renders in one process and presents in another, passing the shared images over a
+//!   Unix domain socket (`SocketProducer` + `SocketConsumer`)
+//!
+//! The `models` feature adds glTF 2.0 and Wavefront OBJ loading into meshes of [`ModelVertex`] (`ModelData`), and
+//! the `png` feature (implied by `models`) PNG decoding into [`Texture`]s. The `text` feature lays out strings in
+//! TrueType/OpenType fonts (`Font`, with DejaVu Sans bundled) and draws them from a `GlyphAtlas`.
+//!
+//! The physical device is picked with a [`DeviceSelector`]; unless one is given, the `VK_NOREDIRECT_DEVICE`
+//! environment variable decides, falling back to the first discrete GPU.
+//!
+//! Targets that support it are handed render completion on the GPU, through a binary semaphore for swapchains or a
+//! [`TimelineSemaphore`] (a D3D12 fence for `dxgi`, an opaque fd for `external-fd`), so presenting never waits for
+//! the frame on the CPU.
+//!
+//! Buffer and image memory comes from the device's [`Allocator`], which sub-allocates from large blocks per memory
+//! type ([`select_memory_type`]) and gives imported or large resources dedicated allocations.
+//! Their contents are uploaded through a staging ring ([`Renderer::upload_buffer`], [`Renderer::upload_image`]) whose
+//! space is reused once the frame that copied from it has finished.
+//! Small parameters skip the staging ring: per-frame uniforms are written into host visible slices bound with
+//! dynamic offsets, and per-draw parameters ([`DrawParams`], or any [`ShaderData`]) are pushed as constants.
+//!
+//! Every frame is recorded anew from a [`DrawList`] the application fills (pipelines, descriptor sets, vertex and
+//! index buffers, parameters and draws) and hands to [`Renderer::render_list`]. Geometry lives in [`Mesh`]es:
+//! vertices of any [`ShaderData`] type with 16 or 32-bit indices. [`Texture`]s hold premultiplied RGBA8 pixels and
+//! are bound together with a [`Sampler`] as a [`TextureSet`] for [`Renderer::textured_pipeline`]. Their mip chains
+//! are blitted on load ([`Texture::with_mipmaps`]) or come pre-built from KTX2 files ([`Texture::from_ktx2`]).
+//! 2D rectangles, circles and Bézier [`Path`]s are filled and stroked into meshes by a [`ShapeBuilder`], drawn in
+//! pixels ([`DrawParams::pixel_space`]) with a [`WhiteTexture`].
+//! A depth or depth/stencil attachment ([`RendererOptions::depth_attachment`]) is created alongside each backbuffer
+//! and cleared every frame; [`Renderer::default_depth_pipeline`] and [`Renderer::textured_depth_pipeline`] test and
+//! write it.
+
+use bedrock as br;
+
+mod allocator;
+mod device;
+mod draw_list;
+mod ktx2;
+mod mesh;
+mod renderer;
+mod present;
+mod params;
+mod shapes;
+mod texture;
+mod timeline;
+mod upload;
+#[cfg(all(windows, feature = "dxgi"))]
+mod dxgi;
+#[cfg(feature = "headless")]
+mod headless;
+#[cfg(any(feature = "x11", feature = "wayland"))]
+mod swapchain;
+#[cfg(feature = "x11")]
+mod x11;
+#[cfg(feature = "wayland")]
+mod wayland;
+#[cfg(all(unix, feature = "external-fd"))]
+mod external_fd;
+#[cfg(all(target_os = "linux", feature = "share"))]
+mod share;
+#[cfg(feature = "models")]
+mod model;
+#[cfg(feature = "text")]
+mod text;
+
+pub use self::allocator::{select_memory_type, Allocation, Allocator, MemoryStats, MemoryUsage};
+#[doc(hidden)]
+pub use self::allocator::SubAllocator;
+pub use self::device::{Device, DeviceSelector, PhysicalDeviceInfo, DEVICE_ENV_VAR};
+pub use self::draw_list::{DrawList, GraphicsPipeline};
+pub use self::ktx2::Ktx2Image;
+pub use self::mesh::{Indices, Mesh};
+pub use self::renderer::{DepthAttachment, DrawRegion, Renderer, RendererOptions, ScissorRect, Viewport, BACKBUFFER_FORMAT};
+pub use self::params::{DrawParams, ShaderData, PUSH_CONSTANT_SIZE, PUSH_CONSTANT_STAGES};
+pub use self::present::{AcquiredImage, PresentTarget, WindowEvent};
+pub use self::shapes::{Paint, Path, Rect, ShapeBuilder, Stroke, WhiteTexture};
+pub use self::timeline::{TimelinePoint, TimelineSemaphore};
+pub use self::texture::{
+    downsample_rgba8, mip_level_count, premultiply_alpha, Sampler, SamplerOptions, Texture, TextureSet, TEXTURE_FORMAT
+};
+#[cfg(feature = "png")]
+pub use self::texture::decode_png;
+pub use self::upload::ImageUpload;
+#[doc(hidden)]
+pub use self::upload::{layout_transitions, LayoutTransition};
+#[cfg(all(windows, feature = "dxgi"))]
+pub use self::dxgi::{ComPtr, DxgiPresenter};
+#[cfg(feature = "headless")]
+pub use self::headless::HeadlessTarget;
+#[cfg(any(feature = "x11", feature = "wayland"))]
+pub use self::swapchain::SwapchainTarget;
+#[cfg(feature = "x11")]
+pub use self::x11::XcbWindow;
+#[cfg(feature = "wayland")]
+pub use self::wayland::WaylandWindow;
+#[cfg(all(unix, feature = "external-fd"))]
+pub use self::external_fd::{FdHandleType, SharedImage, SharedImageDesc, SharedImageTarget};
+#[cfg(all(target_os = "linux", feature = "share"))]
+pub use self::share::{SocketConsumer, SocketProducer};
+#[cfg(feature = "models")]
+pub use self::model::{ImageSource, Material, MeshData, MeshInstance, Model, ModelData, ModelMesh};
+#[cfg(feature = "text")]
+pub use self::text::{Font, GlyphAtlas, LaidOutGlyph, TextLayout, TextStyle};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A Vulkan call returned an error code.
+    Vulkan(&'static str, br::VkResultBox),
+    /// An operating system (or COM) call failed.
+    Os(&'static str, std::io::Error),
+    /// A required capability (queue, memory type, extension function...) is missing.
+    Unsupported(&'static str),
+    /// The target's backbuffers no longer match its surface. [`Renderer::render_list`] handles it by resizing the
+    /// target to its current extent.
+    OutOfDate
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Vulkan(ctx, r) => write!(f, "{}: {:?}", ctx, r),
+            Error::Os(ctx, e) => write!(f, "{}: {}", ctx, e),
+            Error::Unsupported(what) => f.write_str(what),
+            Error::OutOfDate => f.write_str("backbuffers are out of date")
+        }
+    }
+}
+impl std::error::Error for Error {}
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub(crate) fn vk_to_result(r: br::vk::VkResult) -> std::result::Result<(), br::VkResultBox> {
+    br::VkResultHandler::into_result(r)
+}
+pub(crate) fn vk_check(r: br::vk::VkResult, ctx: &'static str) -> Result<()> {
+    vk_to_result(r).map_err(|e| Error::Vulkan(ctx, e))
+}
+
+pub(crate) struct UniqueObject<T: Copy, D: Fn(T)>(pub T, pub D);
+impl<T: Copy, D: Fn(T)> Drop for UniqueObject<T, D> {
+    fn drop(&mut self) {
+        (self.1)(self.0);
+    }
+}
+impl<T, D: Fn(*mut T)> UniqueObject<*mut T, D> {
+    pub fn as_ptr(&self) -> *mut T { self.0 }
+}
+
+/// Vertex of the built-in pipeline: clip-space position and straight alpha color.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex { pub pos: [f32; 4], pub color: [f32; 4] }
+unsafe impl ShaderData for Vertex {}
+/// Vertex of loaded models; colors have straight alpha.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelVertex { pub position: [f32; 3], pub normal: [f32; 3], pub uv: [f32; 2], pub color: [f32; 4] }
+unsafe impl ShaderData for ModelVertex {}
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimerUniform { pub time: f32 }
+unsafe impl ShaderData for TimerUniform {}
+
+pub(crate) fn align2(x: usize, a: usize) -> usize { (x + (a - 1)) & !(a - 1) }
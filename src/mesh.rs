@@ -0,0 +1,117 @@
+//! Device-local vertex and index buffers drawn with `vkCmdDrawIndexed`.
+
+use bedrock as br;
+use crate::{vk_check, Allocation, Device, MemoryUsage, Renderer, Result, ShaderData};
+use std::rc::Rc;
+
+/// Index data of a mesh; 16-bit indices halve the size of meshes with up to 65536 vertices.
+#[derive(Debug, Clone, Copy)]
+pub enum Indices<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32])
+}
+impl Indices<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(i) => i.len(),
+            Indices::U32(i) => i.len()
+        }
+    }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn index_type(&self) -> br::vk::VkIndexType {
+        match self {
+            Indices::U16(_) => br::vk::VK_INDEX_TYPE_UINT16,
+            Indices::U32(_) => br::vk::VK_INDEX_TYPE_UINT32
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(i) => unsafe { std::slice::from_raw_parts(i.as_ptr() as *const u8, std::mem::size_of_val(*i)) },
+            Indices::U32(i) => unsafe { std::slice::from_raw_parts(i.as_ptr() as *const u8, std::mem::size_of_val(*i)) }
+        }
+    }
+}
+
+/// Vertices followed by indices in one device-local buffer, uploaded through the renderer's staging ring.
+///
+/// Draw with [`DrawList::draw_mesh`](crate::DrawList::draw_mesh) after binding a pipeline whose vertex input matches
+/// the vertex type. The buffer is destroyed on drop, so keep the mesh alive until the frames drawing it have finished
+/// ([`Renderer::flush`] or [`Renderer::wait_idle`]).
+pub struct Mesh {
+    device: Rc<Device>,
+    buffer: br::vk::VkBuffer,
+    memory: Allocation,
+    vertex_count: u32,
+    index_offset: u64,
+    index_count: u32,
+    index_type: br::vk::VkIndexType
+}
+impl Mesh {
+    /// Creates the buffer and stages the vertices and indices for upload ahead of the next submitted frame.
+    pub fn new<V: ShaderData>(renderer: &mut Renderer, vertices: &[V], indices: Indices) -> Result<Self> {
+        let vertex_bytes = unsafe {
+            std::slice::from_raw_parts(vertices.as_ptr() as *const u8, std::mem::size_of_val(vertices))
+        };
+        let mesh = Mesh::allocate(renderer.device().clone(), vertex_bytes.len() as _, vertices.len() as _, &indices)?;
+        renderer.upload_buffer(mesh.buffer, 0, vertex_bytes)?;
+        renderer.upload_buffer(mesh.buffer, mesh.index_offset, indices.as_bytes())?;
+
+        Ok(mesh)
+    }
+
+    /// [`Mesh::new`] from 32-bit indices, narrowed to 16 bits when there are no more than 65536 vertices.
+    pub fn from_u32_indices<V: ShaderData>(renderer: &mut Renderer, vertices: &[V], indices: &[u32]) -> Result<Self> {
+        if vertices.len() <= 1 << 16 {
+            let indices = indices.iter().map(|&i| i as u16).collect::<Vec<_>>();
+            Mesh::new(renderer, vertices, Indices::U16(&indices))
+        } else {
+            Mesh::new(renderer, vertices, Indices::U32(indices))
+        }
+    }
+
+    /// Creates the buffer without contents; `Renderer::new` uploads its built-in mesh by itself.
+    pub(crate) fn allocate(device: Rc<Device>, vertex_bytes: u64, vertex_count: u32, indices: &Indices) -> Result<Self> {
+        // index buffer offsets have to be a multiple of the index size
+        let index_offset = (vertex_bytes + 3) & !3;
+        let mut this = Mesh {
+            device,
+            buffer: br::vk::VK_NULL_HANDLE as _,
+            memory: Allocation::default(),
+            vertex_count,
+            index_offset,
+            index_count: indices.len() as _,
+            index_type: indices.index_type()
+        };
+        let buffer_cinfo = br::vk::VkBufferCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            size: (index_offset + indices.as_bytes().len() as u64).max(4),
+            usage: br::vk::VK_BUFFER_USAGE_VERTEX_BUFFER_BIT | br::vk::VK_BUFFER_USAGE_INDEX_BUFFER_BIT |
+                br::vk::VK_BUFFER_USAGE_TRANSFER_DST_BIT,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkCreateBuffer(this.device.native_ptr(), &buffer_cinfo, std::ptr::null(), &mut this.buffer) };
+        vk_check(r, "vkCreateBuffer for Mesh failed")?;
+        this.memory = unsafe { this.device.allocator().allocate_buffer(this.buffer, MemoryUsage::DEVICE_LOCAL)? };
+
+        Ok(this)
+    }
+
+    pub fn buffer(&self) -> br::vk::VkBuffer { self.buffer }
+    pub fn vertex_count(&self) -> u32 { self.vertex_count }
+    /// Byte offset of the indices in [`Mesh::buffer`]; the vertices start at 0.
+    pub fn index_offset(&self) -> u64 { self.index_offset }
+    pub fn index_count(&self) -> u32 { self.index_count }
+    pub fn index_type(&self) -> br::vk::VkIndexType { self.index_type }
+}
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe { br::vk::vkDestroyBuffer(self.device.native_ptr(), self.buffer, std::ptr::null()) };
+        self.device.allocator().free(std::mem::take(&mut self.memory));
+    }
+}
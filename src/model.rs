@@ -0,0 +1,294 @@
+//! glTF 2.0 and Wavefront OBJ loading into [`Mesh`]es of [`ModelVertex`].
+
+use crate::{premultiply_alpha, Error, Mesh, ModelVertex, Renderer, Result, Texture};
+use std::path::{Path, PathBuf};
+
+const IDENTITY: [[f32; 4]; 4] = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+/// Pixels of a material texture.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSource {
+    /// Decoded from the model file: tightly packed RGBA8 with straight alpha.
+    Decoded { width: u32, height: u32, pixels: Vec<u8> },
+    /// Image file referenced by the model, resolved against the model's directory.
+    File(PathBuf)
+}
+impl ImageSource {
+    /// Uploads the pixels as a [`Texture`] with mipmaps, premultiplying their alpha. Files are decoded as PNG.
+    pub fn create_texture(&self, renderer: &mut Renderer) -> Result<Texture> {
+        match self {
+            ImageSource::Decoded { width, height, pixels } => {
+                let mut pixels = pixels.clone();
+                premultiply_alpha(&mut pixels);
+                Texture::with_mipmaps(renderer, *width, *height, &pixels)
+            },
+            ImageSource::File(path) => Texture::load_png(renderer, path, true)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    /// Multiplied with the vertex colors and the base color texture; straight alpha.
+    pub base_color: [f32; 4],
+    /// Index into [`ModelData::images`].
+    pub base_color_texture: Option<usize>
+}
+impl Default for Material {
+    fn default() -> Self { Material { name: None, base_color: [1.0; 4], base_color_texture: None } }
+}
+
+/// Geometry of one primitive, as loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<ModelVertex>,
+    /// Triangle list.
+    pub indices: Vec<u32>,
+    /// Index into [`ModelData::materials`]; `None` for the default material.
+    pub material: Option<usize>
+}
+
+/// Placement of a mesh in the model; the same mesh may be placed several times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshInstance {
+    pub mesh: usize,
+    /// Model space transform, column-major.
+    pub transform: [[f32; 4]; 4]
+}
+
+/// A model loaded into host memory, ready to be uploaded with [`ModelData::upload`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub instances: Vec<MeshInstance>,
+    pub materials: Vec<Material>,
+    pub images: Vec<ImageSource>
+}
+impl ModelData {
+    /// Loads `.gltf`/`.glb` files with [`ModelData::load_gltf`] and `.obj` files with [`ModelData::load_obj`].
+    pub fn load(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("gltf") | Some("glb") => Self::load_gltf(path),
+            Some("obj") => Self::load_obj(path),
+            _ => Err(Error::Unsupported("unknown model file extension"))
+        }
+    }
+
+    /// Loads the default scene (or else the first one) of a glTF 2.0 file, with buffers and images either embedded
+    /// or in separate files.
+    ///
+    /// Only triangle list primitives are loaded. Missing normals are computed from the faces; missing UVs are 0 and
+    /// missing vertex colors white.
+    pub fn load_gltf(path: &Path) -> Result<Self> {
+        let (document, buffers, images) = gltf::import(path).map_err(|e| match e {
+            gltf::Error::Io(e) => Error::Os("Reading glTF failed", e),
+            e => Error::Os("Loading glTF failed", std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+        })?;
+        let mut this = ModelData {
+            images: images.iter().map(|i| ImageSource::Decoded { width: i.width, height: i.height, pixels: gltf_rgba8(i) }).collect(),
+            materials: document.materials().map(|m| {
+                let pbr = m.pbr_metallic_roughness();
+                Material {
+                    name: m.name().map(ToOwned::to_owned),
+                    base_color: pbr.base_color_factor(),
+                    base_color_texture: pbr.base_color_texture().map(|t| t.texture().source().index())
+                }
+            }).collect(),
+            .. ModelData::default()
+        };
+
+        // glTF meshes are split into one MeshData per primitive
+        let mut primitive_ranges = Vec::with_capacity(document.meshes().len());
+        for mesh in document.meshes() {
+            let first = this.meshes.len();
+            for primitive in mesh.primitives().filter(|p| p.mode() == gltf::mesh::Mode::Triangles) {
+                let reader = primitive.reader(|b| buffers.get(b.index()).map(|d| &d[..]));
+                let positions = match reader.read_positions() {
+                    Some(p) => p,
+                    None => continue
+                };
+                let mut vertices = positions.map(|position| ModelVertex {
+                    position, normal: [0.0; 3], uv: [0.0; 2], color: [1.0; 4]
+                }).collect::<Vec<_>>();
+                let indices = match reader.read_indices() {
+                    Some(i) => i.into_u32().collect(),
+                    None => (0..vertices.len() as u32).collect::<Vec<_>>()
+                };
+                match reader.read_normals() {
+                    Some(normals) => for (v, n) in vertices.iter_mut().zip(normals) { v.normal = n; },
+                    None => compute_normals(&mut vertices, &indices)
+                }
+                if let Some(uvs) = reader.read_tex_coords(0) {
+                    for (v, uv) in vertices.iter_mut().zip(uvs.into_f32()) { v.uv = uv; }
+                }
+                if let Some(colors) = reader.read_colors(0) {
+                    for (v, c) in vertices.iter_mut().zip(colors.into_rgba_f32()) { v.color = c; }
+                }
+                this.meshes.push(MeshData { vertices, indices, material: primitive.material().index() });
+            }
+            primitive_ranges.push(first..this.meshes.len());
+        }
+
+        let scene = document.default_scene().or_else(|| document.scenes().next());
+        let mut stack = scene.iter().flat_map(|s| s.nodes()).map(|n| (n, IDENTITY)).collect::<Vec<_>>();
+        while let Some((node, parent)) = stack.pop() {
+            let transform = mul(&parent, &node.transform().matrix());
+            if let Some(mesh) = node.mesh() {
+                this.instances.extend(primitive_ranges[mesh.index()].clone().map(|mesh| MeshInstance { mesh, transform }));
+            }
+            stack.extend(node.children().map(|c| (c, transform)));
+        }
+
+        Ok(this)
+    }
+
+    /// Loads a Wavefront OBJ file with its MTL materials, triangulating polygons. Each object or group becomes a
+    /// mesh placed once; diffuse colors, dissolve and diffuse maps are mapped to the base color.
+    ///
+    /// UVs are flipped vertically to put their origin at the top-left, like glTF's.
+    pub fn load_obj(path: &Path) -> Result<Self> {
+        let options = tobj::LoadOptions { single_index: true, triangulate: true, .. Default::default() };
+        let (models, materials) = tobj::load_obj(path, &options).map_err(|e| obj_error("Loading OBJ failed", e))?;
+        // a missing material library leaves the meshes with the default material
+        let materials = materials.unwrap_or_default();
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut this = ModelData::default();
+
+        for m in materials {
+            let base_color_texture = m.diffuse_texture.map(|t| {
+                let source = ImageSource::File(base.join(t.replace('\\', "/")));
+                match this.images.iter().position(|i| *i == source) {
+                    Some(n) => n,
+                    None => {
+                        this.images.push(source);
+                        this.images.len() - 1
+                    }
+                }
+            });
+            let [r, g, b] = m.diffuse.unwrap_or([1.0; 3]);
+            this.materials.push(Material {
+                name: Some(m.name),
+                base_color: [r, g, b, m.dissolve.unwrap_or(1.0)],
+                base_color_texture
+            });
+        }
+
+        for model in models {
+            let mesh = model.mesh;
+            let mut vertices = mesh.positions.chunks_exact(3).map(|p| ModelVertex {
+                position: [p[0], p[1], p[2]], normal: [0.0; 3], uv: [0.0; 2], color: [1.0; 4]
+            }).collect::<Vec<_>>();
+            if mesh.normals.len() == mesh.positions.len() {
+                for (v, n) in vertices.iter_mut().zip(mesh.normals.chunks_exact(3)) { v.normal = [n[0], n[1], n[2]]; }
+            } else {
+                compute_normals(&mut vertices, &mesh.indices);
+            }
+            if mesh.texcoords.len() / 2 == vertices.len() {
+                for (v, t) in vertices.iter_mut().zip(mesh.texcoords.chunks_exact(2)) { v.uv = [t[0], 1.0 - t[1]]; }
+            }
+            if mesh.vertex_color.len() == mesh.positions.len() {
+                for (v, c) in vertices.iter_mut().zip(mesh.vertex_color.chunks_exact(3)) { v.color = [c[0], c[1], c[2], 1.0]; }
+            }
+            let material = mesh.material_id.filter(|&n| n < this.materials.len());
+            this.instances.push(MeshInstance { mesh: this.meshes.len(), transform: IDENTITY });
+            this.meshes.push(MeshData { vertices, indices: mesh.indices, material });
+        }
+
+        Ok(this)
+    }
+
+    /// Uploads every mesh through the renderer's staging ring, with 16-bit indices where they suffice.
+    pub fn upload(self, renderer: &mut Renderer) -> Result<Model> {
+        let meshes = self.meshes.iter().map(|m| {
+            let mesh = Mesh::from_u32_indices(renderer, &m.vertices, &m.indices)?;
+
+            Ok(ModelMesh { mesh, material: m.material })
+        }).collect::<Result<_>>()?;
+
+        Ok(Model { meshes, instances: self.instances, materials: self.materials, images: self.images })
+    }
+}
+
+pub struct ModelMesh {
+    pub mesh: Mesh,
+    /// Index into [`Model::materials`].
+    pub material: Option<usize>
+}
+
+/// Meshes of a model on the device. Keep it alive until the frames drawing it have finished.
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub instances: Vec<MeshInstance>,
+    pub materials: Vec<Material>,
+    pub images: Vec<ImageSource>
+}
+
+fn obj_error(ctx: &'static str, e: tobj::LoadError) -> Error {
+    let kind = if e == tobj::LoadError::OpenFileFailed { std::io::ErrorKind::NotFound } else { std::io::ErrorKind::InvalidData };
+
+    Error::Os(ctx, std::io::Error::new(kind, e))
+}
+
+/// Converts decoded glTF image pixels to RGBA8; one and two channel images are treated as luminance (and alpha).
+fn gltf_rgba8(image: &gltf::image::Data) -> Vec<u8> {
+    use gltf::image::Format;
+
+    let (channels, size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => return image.pixels.clone(),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4)
+    };
+    // 16-bit channels are little endian; keep their high byte
+    let convert = |b: &[u8]| match size {
+        1 => b[0],
+        2 => b[1],
+        _ => (f32::from_le_bytes([b[0], b[1], b[2], b[3]]).clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    image.pixels.chunks_exact(channels * size).flat_map(|p| {
+        let c = p.chunks_exact(size).map(convert).collect::<Vec<_>>();
+        match channels {
+            1 => [c[0], c[0], c[0], 255],
+            2 => [c[0], c[0], c[0], c[1]],
+            3 => [c[0], c[1], c[2], 255],
+            _ => [c[0], c[1], c[2], c[3]]
+        }
+    }).collect()
+}
+
+/// Area-weighted vertex normals of a triangle list.
+fn compute_normals(vertices: &mut [ModelVertex], indices: &[u32]) {
+    let sub = |a: [f32; 3], b: [f32; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    for t in indices.chunks_exact(3) {
+        let (a, b, c) = (t[0] as usize, t[1] as usize, t[2] as usize);
+        if a.max(b).max(c) >= vertices.len() { continue; }
+        let e1 = sub(vertices[b].position, vertices[a].position);
+        let e2 = sub(vertices[c].position, vertices[a].position);
+        let n = [e1[1] * e2[2] - e1[2] * e2[1], e1[2] * e2[0] - e1[0] * e2[2], e1[0] * e2[1] - e1[1] * e2[0]];
+        for &v in &[a, b, c] {
+            for (d, s) in vertices[v].normal.iter_mut().zip(&n) { *d += s; }
+        }
+    }
+    for v in vertices {
+        let len = v.normal.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.normal = if len > 0.0 { v.normal.map(|x| x / len) } else { [0.0, 0.0, 1.0] };
+    }
+}
+
+fn mul(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut r = [[0.0; 4]; 4];
+    for (c, col) in r.iter_mut().enumerate() {
+        for (row, v) in col.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a[k][row] * b[c][k]).sum();
+        }
+    }
+
+    r
+}
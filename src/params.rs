@@ -0,0 +1,51 @@
+//! Small shader parameters: push constants per draw and dynamic uniform slices per frame.
+
+use bedrock as br;
+
+/// Bytes of push constants every pipeline layout reserves; the minimum `maxPushConstantsSize` devices guarantee.
+pub const PUSH_CONSTANT_SIZE: u32 = 128;
+/// Shader stages the push constant range is visible to.
+pub const PUSH_CONSTANT_STAGES: br::vk::VkShaderStageFlags = br::vk::VK_SHADER_STAGE_VERTEX_BIT | br::vk::VK_SHADER_STAGE_FRAGMENT_BIT;
+
+/// Plain data copied byte for byte into push constants and uniform buffers.
+///
+/// # Safety
+/// Implementors must be `#[repr(C)]` (or primitives and arrays of them), laid out as the shaders declare them
+/// (std140 for uniform blocks, std430 for push constants), and hold no pointers, references or uninitialized padding.
+pub unsafe trait ShaderData: Copy + 'static {
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const u8, std::mem::size_of::<Self>()) }
+    }
+}
+unsafe impl ShaderData for f32 {}
+unsafe impl ShaderData for u32 {}
+unsafe impl ShaderData for i32 {}
+unsafe impl<T: ShaderData, const N: usize> ShaderData for [T; N] {}
+
+/// Per-draw parameters of the built-in pipeline, pushed as constants right before each draw.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawParams {
+    /// Applied to the animated vertex positions, column-major.
+    pub transform: [[f32; 4]; 4],
+    /// Multiplied with the (straight alpha) vertex colors.
+    pub tint: [f32; 4]
+}
+unsafe impl ShaderData for DrawParams {}
+impl Default for DrawParams {
+    fn default() -> Self {
+        DrawParams {
+            transform: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+            tint: [1.0; 4]
+        }
+    }
+}
+impl DrawParams {
+    /// Maps pixels of a `width` × `height` region, from its top-left corner, to clip space.
+    pub fn pixel_space(width: f32, height: f32) -> Self {
+        DrawParams {
+            transform: [[2.0 / width, 0.0, 0.0, 0.0], [0.0, 2.0 / height, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [-1.0, -1.0, 0.0, 1.0]],
+            .. Default::default()
+        }
+    }
+}
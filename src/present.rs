@@ -0,0 +1,48 @@
+use bedrock as br;
+use crate::{Result, TimelinePoint};
+
+/// A backbuffer handed out by [`PresentTarget::acquire_next_image`].
+pub struct AcquiredImage {
+    pub index: usize,
+    /// Semaphore the rendering has to wait for before writing the image, or `VK_NULL_HANDLE`.
+    pub wait_semaphore: br::vk::VkSemaphore,
+    /// Counter value to wait for when `wait_semaphore` is a timeline semaphore; ignored for binary ones.
+    pub wait_value: u64
+}
+
+/// A set of backbuffers the renderer draws into, together with the means to show them.
+///
+/// Per frame, the renderer calls `acquire_next_image`, submits rendering into that image and, once it has observed
+/// the submission completing, calls `render_complete` followed by `present` for the same index.
+/// With several frames in flight, up to `frames_in_flight` images are acquired before the first of them is presented,
+/// so each acquire has to move on to the next backbuffer by itself. Presents arrive in acquisition order.
+pub trait PresentTarget {
+    fn format(&self) -> br::vk::VkFormat;
+    fn extent(&self) -> br::vk::VkExtent2D;
+    /// Backbuffer images, in index order. They are owned by the target.
+    fn backbuffer_images(&self) -> Vec<br::vk::VkImage>;
+    /// Layout the backbuffers have to be left in after rendering.
+    fn present_layout(&self) -> br::vk::VkImageLayout { br::vk::VK_IMAGE_LAYOUT_GENERAL }
+
+    /// Fails with [`Error::OutOfDate`](crate::Error::OutOfDate) when the backbuffers have to be recreated first.
+    fn acquire_next_image(&mut self) -> Result<AcquiredImage>;
+    /// Semaphore to be signaled when rendering into the backbuffer finishes, or `VK_NULL_HANDLE`.
+    fn render_complete_semaphore(&self, _index: usize) -> br::vk::VkSemaphore { br::vk::VK_NULL_HANDLE as _ }
+    /// Timeline point to be signaled when rendering into the backbuffer finishes, for targets that wait for it on the
+    /// GPU. Targets with either this or a `render_complete_semaphore` are presented right after submission instead of
+    /// once the renderer has observed completion, and `render_complete` is not called for them.
+    fn render_complete_timeline(&mut self, _index: usize) -> Option<TimelinePoint> { None }
+    /// Notifies that rendering into the backbuffer has finished executing on the device.
+    fn render_complete(&mut self, _index: usize) -> Result<()> { Ok(()) }
+    fn present(&mut self, index: usize) -> Result<()>;
+    /// Recreates the backbuffers at the new size. The device must not be using them anymore.
+    fn resize(&mut self, width: u32, height: u32) -> Result<()>;
+}
+
+/// Window system events the owner of a window-backed target has to react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowEvent {
+    /// The window has a new size; the target should be resized and reattached.
+    Resized(u32, u32),
+    CloseRequested
+}
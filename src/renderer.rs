@@ -0,0 +1,1364 @@
+use bedrock as br;
+use crate::{align2, vk_check, AcquiredImage, Allocation, Device, DeviceSelector, DrawList, DrawParams, Error, GraphicsPipeline, ImageUpload, Indices, MemoryUsage, Mesh, ModelVertex, PresentTarget, Result, ShaderData, TimelinePoint, TimerUniform, UniqueObject, Vertex, PUSH_CONSTANT_SIZE, PUSH_CONSTANT_STAGES};
+use crate::draw_list::DrawCommand;
+use crate::upload::UploadRing;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::rc::Rc;
+
+pub struct RendererOptions<'a> {
+    pub application_name: &'a str,
+    /// Enables VK_LAYER_KHRONOS_validation and routes its reports to stdout.
+    pub validation: bool,
+    pub instance_extensions: &'a [&'a str],
+    pub device_extensions: &'a [&'a str],
+    /// Physical device to run on. The `dxgi` target has to share its adapter with it.
+    pub device: DeviceSelector<'a>,
+    pub extent: br::vk::VkExtent2D,
+    /// Backbuffers window system targets are created with, 2 to 4. More buffers smooth out uneven frame times
+    /// at the cost of latency.
+    pub buffer_count: u32,
+    /// Frames that may be queued for presentation before the application is made to wait, 1 to `buffer_count`.
+    ///
+    /// Only the `dxgi` target applies it (as the swap chain's maximum frame latency). The other targets are bounded
+    /// by `frames_in_flight` and, for swapchains, by the images the presentation engine hands out.
+    pub max_frame_latency: u32,
+    /// Frames recorded ahead while earlier ones are still rendering, 1 to `buffer_count`. Each has its own fence,
+    /// staging space and uniform slice.
+    pub frames_in_flight: u32,
+    /// Bytes of the per-frame uniform slice bound to the vertex and fragment shaders, at least the built-in
+    /// [`TimerUniform`](crate::TimerUniform) it starts with. Written with [`Renderer::write_frame_uniforms`].
+    pub frame_uniform_size: usize,
+    /// Bytes of host visible memory uploads are staged in until their frame has finished. Everything uploaded
+    /// for one frame has to fit at once.
+    pub upload_ring_size: u64,
+    pub vertex_shader_path: &'a Path,
+    pub fragment_shader_path: &'a Path,
+    /// Shaders of the textured pipeline ([`Renderer::textured_pipeline`]).
+    pub textured_vertex_shader_path: &'a Path,
+    pub textured_fragment_shader_path: &'a Path,
+    /// Depth (and stencil) buffer attached next to each backbuffer and cleared every frame.
+    pub depth_attachment: DepthAttachment
+}
+impl Default for RendererOptions<'_> {
+    fn default() -> Self {
+        RendererOptions {
+            application_name: "vkNoRedirectRender",
+            validation: true,
+            instance_extensions: &[],
+            device_extensions: &[],
+            device: DeviceSelector::Default,
+            extent: br::vk::VkExtent2D { width: 640, height: 480 },
+            buffer_count: 2,
+            max_frame_latency: 1,
+            frames_in_flight: 2,
+            frame_uniform_size: std::mem::size_of::<TimerUniform>(),
+            upload_ring_size: 4 << 20,
+            vertex_shader_path: Path::new("./assets/vert.spv"),
+            fragment_shader_path: Path::new("./assets/frag.spv"),
+            textured_vertex_shader_path: Path::new("./assets/textured_vert.spv"),
+            textured_fragment_shader_path: Path::new("./assets/textured_frag.spv"),
+            depth_attachment: DepthAttachment::None
+        }
+    }
+}
+
+/// Color format of the backbuffers the renderer draws into, unless the attached target asks for another one.
+pub const BACKBUFFER_FORMAT: br::vk::VkFormat = br::vk::VK_FORMAT_R8G8B8A8_UNORM;
+
+/// Format of the depth attachment, resolved against the device's supported formats when the renderer is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthAttachment {
+    /// Color only.
+    None,
+    /// The most precise depth-only format the device supports (32-bit float, 24 or 16-bit normalized).
+    Depth,
+    /// The first combined depth and 8-bit stencil format the device supports.
+    DepthStencil,
+    /// A specific depth, stencil or depth/stencil format.
+    Format(br::vk::VkFormat)
+}
+impl DepthAttachment {
+    fn resolve(self, device: &Device) -> Result<Option<br::vk::VkFormat>> {
+        let candidates: &[br::vk::VkFormat] = match self {
+            DepthAttachment::None => return Ok(None),
+            DepthAttachment::Depth => &[
+                br::vk::VK_FORMAT_D32_SFLOAT, br::vk::VK_FORMAT_X8_D24_UNORM_PACK32, br::vk::VK_FORMAT_D16_UNORM
+            ],
+            DepthAttachment::DepthStencil => &[br::vk::VK_FORMAT_D24_UNORM_S8_UINT, br::vk::VK_FORMAT_D32_SFLOAT_S8_UINT],
+            DepthAttachment::Format(ref f) => std::slice::from_ref(f)
+        };
+
+        candidates.iter().copied().find(|&f| {
+            (device.format_properties(f).optimalTilingFeatures & br::vk::VK_FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT) != 0
+        }).map(Some).ok_or(Error::Unsupported("depth attachment format not supported"))
+    }
+}
+
+struct Backbuffer {
+    view: br::vk::VkImageView,
+    framebuffer: br::vk::VkFramebuffer,
+    _depth: Option<DepthBuffer>
+}
+
+/// Depth (and stencil) image of one backbuffer's framebuffer, at the backbuffer size.
+struct DepthBuffer {
+    device: Rc<Device>,
+    image: br::vk::VkImage,
+    view: br::vk::VkImageView,
+    memory: Allocation
+}
+impl DepthBuffer {
+    fn new(device: &Rc<Device>, format: br::vk::VkFormat, extent: &br::vk::VkExtent2D) -> Result<Self> {
+        let mut this = DepthBuffer {
+            device: device.clone(),
+            image: br::vk::VK_NULL_HANDLE as _,
+            view: br::vk::VK_NULL_HANDLE as _,
+            memory: Allocation::default()
+        };
+        let vk_device = device.native_ptr();
+        let image_cinfo = br::vk::VkImageCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            imageType: br::vk::VK_IMAGE_TYPE_2D,
+            format,
+            extent: br::vk::VkExtent3D { width: extent.width, height: extent.height, depth: 1 },
+            mipLevels: 1,
+            arrayLayers: 1,
+            samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+            tiling: br::vk::VK_IMAGE_TILING_OPTIMAL,
+            usage: br::vk::VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null(),
+            initialLayout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED
+        };
+        let r = unsafe { br::vk::vkCreateImage(vk_device, &image_cinfo, std::ptr::null(), &mut this.image) };
+        vk_check(r, "vkCreateImage for DepthBuffer failed")?;
+        this.memory = unsafe { device.allocator().allocate_image(this.image, MemoryUsage::DEVICE_LOCAL, false)? };
+
+        let iv_cinfo = br::vk::VkImageViewCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            image: this.image,
+            viewType: br::vk::VK_IMAGE_VIEW_TYPE_2D,
+            format,
+            components: br::vk::VkComponentMapping {
+                r: br::vk::VK_COMPONENT_SWIZZLE_IDENTITY,
+                g: br::vk::VK_COMPONENT_SWIZZLE_IDENTITY,
+                b: br::vk::VK_COMPONENT_SWIZZLE_IDENTITY,
+                a: br::vk::VK_COMPONENT_SWIZZLE_IDENTITY
+            },
+            subresourceRange: br::vk::VkImageSubresourceRange {
+                aspectMask: depth_stencil_aspects(format),
+                baseMipLevel: 0,
+                levelCount: 1,
+                baseArrayLayer: 0,
+                layerCount: 1
+            }
+        };
+        let r = unsafe { br::vk::vkCreateImageView(vk_device, &iv_cinfo, std::ptr::null(), &mut this.view) };
+        vk_check(r, "vkCreateImageView for DepthBuffer failed")?;
+
+        Ok(this)
+    }
+}
+impl Drop for DepthBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            br::vk::vkDestroyImageView(self.device.native_ptr(), self.view, std::ptr::null());
+            br::vk::vkDestroyImage(self.device.native_ptr(), self.image, std::ptr::null());
+        }
+        self.device.allocator().free(std::mem::take(&mut self.memory));
+    }
+}
+
+/// Part of the backbuffer a draw renders into, in pixels from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawRegion {
+    /// Area the scene is mapped onto; may extend past the backbuffer, but must not be empty.
+    pub viewport: Viewport,
+    /// Pixels outside of this rectangle are left untouched. Clamped to the backbuffer.
+    pub scissor: ScissorRect,
+    /// Pushed as constants for the draw.
+    pub params: DrawParams
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport { pub x: f32, pub y: f32, pub width: f32, pub height: f32 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScissorRect { pub x: u32, pub y: u32, pub width: u32, pub height: u32 }
+impl DrawRegion {
+    /// Viewport and scissor both covering the given rectangle.
+    pub fn rect(x: u32, y: u32, width: u32, height: u32) -> Self {
+        DrawRegion {
+            viewport: Viewport { x: x as _, y: y as _, width: width as _, height: height as _ },
+            scissor: ScissorRect { x, y, width, height },
+            params: DrawParams::default()
+        }
+    }
+
+    pub fn with_params(self, params: DrawParams) -> Self { DrawRegion { params, .. self } }
+
+    /// Whether the viewport has a positive width and height, as Vulkan requires.
+    pub fn has_valid_viewport(&self) -> bool { self.viewport.width > 0.0 && self.viewport.height > 0.0 }
+}
+
+/// Resources owned by one of the frames in flight.
+struct Frame {
+    fence: br::vk::VkFence,
+    /// Dynamic offset of the frame's slice of the uniform buffer.
+    uniform_offset: usize,
+    /// Reset once the frame's previous submission has finished; holds `commands`.
+    command_pool: br::vk::VkCommandPool,
+    /// Rerecorded for every submission: staged copies, then the frame's draw list.
+    commands: br::vk::VkCommandBuffer
+}
+
+/// A submitted frame whose fence has not been observed yet.
+struct InFlight {
+    frame: usize,
+    /// Backbuffer still to be presented once the frame has finished; `None` if the target was presented at submission.
+    image: Option<usize>
+}
+
+/// Owns the render pass, pipeline, buffers, per-backbuffer framebuffers and per-frame command buffers.
+///
+/// Backbuffer images are provided by a [`PresentTarget`] through [`Renderer::attach`]. Each frame is recorded
+/// from a [`DrawList`], either the application's ([`Renderer::render_list`]) or the built-in scene's
+/// ([`Renderer::render_frame`]).
+pub struct Renderer {
+    device: Rc<Device>,
+    format: br::vk::VkFormat,
+    extent: br::vk::VkExtent2D,
+    buffer_count: u32,
+    max_frame_latency: u32,
+    frame_uniform_size: usize,
+    depth_format: Option<br::vk::VkFormat>,
+    render_pass: br::vk::VkRenderPass,
+    /// Geometry of the built-in scene.
+    triangle: Mesh,
+    uniform_buffer: br::vk::VkBuffer,
+    uniform_mem: Allocation,
+    uploads: UploadRing,
+    dsl_ub1_v: br::vk::VkDescriptorSetLayout,
+    dsl_texture: br::vk::VkDescriptorSetLayout,
+    dspool: br::vk::VkDescriptorPool,
+    descriptor_set: br::vk::VkDescriptorSet,
+    vert_shader: br::vk::VkShaderModule,
+    frag_shader: br::vk::VkShaderModule,
+    ps_layout: br::vk::VkPipelineLayout,
+    pipeline: br::vk::VkPipeline,
+    depth_pipeline: br::vk::VkPipeline,
+    textured_vert_shader: br::vk::VkShaderModule,
+    textured_frag_shader: br::vk::VkShaderModule,
+    textured_layout: br::vk::VkPipelineLayout,
+    textured_pipeline: br::vk::VkPipeline,
+    textured_depth_pipeline: br::vk::VkPipeline,
+    frames: Vec<Frame>,
+    current_frame: usize,
+    backbuffers: Vec<Backbuffer>,
+    in_flight: VecDeque<InFlight>,
+    suspended: bool,
+    draw_regions: Vec<DrawRegion>,
+    /// Reused by `render_frame` for the built-in scene.
+    scene: DrawList
+}
+impl Renderer {
+    pub fn new(options: &RendererOptions) -> Result<Self> {
+        if !(2..=4).contains(&options.buffer_count) {
+            return Err(Error::Unsupported("buffer count must be between 2 and 4"));
+        }
+        if options.max_frame_latency < 1 || options.max_frame_latency > options.buffer_count {
+            return Err(Error::Unsupported("max frame latency must be between 1 and the buffer count"));
+        }
+        if options.frames_in_flight < 1 || options.frames_in_flight > options.buffer_count {
+            return Err(Error::Unsupported("frames in flight must be between 1 and the buffer count"));
+        }
+        if options.frame_uniform_size < std::mem::size_of::<TimerUniform>() {
+            return Err(Error::Unsupported("frame uniforms must have room for the timer"));
+        }
+
+        let device = Rc::new(Device::new(
+            options.application_name, options.validation, options.instance_extensions, options.device_extensions, options.device
+        )?);
+        let vk_device = device.native_ptr();
+        let depth_format = options.depth_attachment.resolve(&device)?;
+        let uploads = UploadRing::new(device.clone(), options.upload_ring_size)?;
+        let frame_count = options.frames_in_flight as usize;
+        // uniform slices are bound at dynamic offsets and flushed individually
+        let limits = &device.properties().limits;
+        let uniform_alignment = limits.minUniformBufferOffsetAlignment.max(limits.nonCoherentAtomSize).max(1) as usize;
+        let uniform_stride = align2(options.frame_uniform_size, uniform_alignment);
+        let vertices = [
+            Vertex { pos: [0.0, 0.5, 0.5, 1.0], color: [1.0, 1.0, 1.0, 0.6] },
+            Vertex { pos: [0.5, -0.5, 0.5, 1.0], color: [0.0, 1.0, 1.0, 1.0] },
+            Vertex { pos: [-0.5, -0.5, 0.5, 1.0], color: [1.0, 1.0, 0.0, 1.0] }
+        ];
+        let indices = Indices::U16(&[0, 1, 2]);
+        let triangle = Mesh::allocate(device.clone(), std::mem::size_of_val(&vertices) as _, vertices.len() as _, &indices)?;
+        // from here on, partially initialized objects are released by Drop
+        let mut this = Renderer {
+            device,
+            format: BACKBUFFER_FORMAT,
+            extent: br::vk::VkExtent2D { width: options.extent.width, height: options.extent.height },
+            buffer_count: options.buffer_count,
+            max_frame_latency: options.max_frame_latency,
+            frame_uniform_size: options.frame_uniform_size,
+            depth_format,
+            render_pass: br::vk::VK_NULL_HANDLE as _,
+            triangle,
+            uniform_buffer: br::vk::VK_NULL_HANDLE as _,
+            uniform_mem: Allocation::default(),
+            uploads,
+            dsl_ub1_v: br::vk::VK_NULL_HANDLE as _,
+            dsl_texture: br::vk::VK_NULL_HANDLE as _,
+            dspool: br::vk::VK_NULL_HANDLE as _,
+            descriptor_set: br::vk::VK_NULL_HANDLE as _,
+            vert_shader: br::vk::VK_NULL_HANDLE as _,
+            frag_shader: br::vk::VK_NULL_HANDLE as _,
+            ps_layout: br::vk::VK_NULL_HANDLE as _,
+            pipeline: br::vk::VK_NULL_HANDLE as _,
+            depth_pipeline: br::vk::VK_NULL_HANDLE as _,
+            textured_vert_shader: br::vk::VK_NULL_HANDLE as _,
+            textured_frag_shader: br::vk::VK_NULL_HANDLE as _,
+            textured_layout: br::vk::VK_NULL_HANDLE as _,
+            textured_pipeline: br::vk::VK_NULL_HANDLE as _,
+            textured_depth_pipeline: br::vk::VK_NULL_HANDLE as _,
+            frames: Vec::with_capacity(frame_count),
+            current_frame: 0,
+            backbuffers: Vec::new(),
+            in_flight: VecDeque::new(),
+            suspended: false,
+            draw_regions: Vec::new(),
+            scene: DrawList::new()
+        };
+        // Initialize Vulkan Rendering
+        this.render_pass = create_render_pass(vk_device, this.format, br::vk::VK_IMAGE_LAYOUT_GENERAL, depth_format)?;
+
+        let vertex_data = vertices.iter().flat_map(ShaderData::as_bytes).copied().collect::<Vec<_>>();
+        let triangle_buffer = this.triangle.buffer();
+        if !this.uploads.upload_buffer(triangle_buffer, 0, &vertex_data)? ||
+            !this.uploads.upload_buffer(triangle_buffer, this.triangle.index_offset(), indices.as_bytes())? {
+            return Err(Error::Unsupported("initial buffer contents exceed the upload ring size"));
+        }
+        // per-frame uniforms are written by the host directly, without staging
+        let buffer_cinfo = br::vk::VkBufferCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            size: (uniform_stride * frame_count) as _,
+            usage: br::vk::VK_BUFFER_USAGE_UNIFORM_BUFFER_BIT,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkCreateBuffer(vk_device, &buffer_cinfo, std::ptr::null(), &mut this.uniform_buffer) };
+        vk_check(r, "vkCreateBuffer for Uniforms failed")?;
+        this.uniform_mem = unsafe { this.device.allocator().allocate_buffer(this.uniform_buffer, MemoryUsage::UPLOAD)? };
+
+        let dsl_ub1_v_bindings = &[br::vk::VkDescriptorSetLayoutBinding {
+            binding: 0,
+            descriptorType: br::vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC,
+            descriptorCount: 1,
+            stageFlags: br::vk::VK_SHADER_STAGE_VERTEX_BIT | br::vk::VK_SHADER_STAGE_FRAGMENT_BIT,
+            pImmutableSamplers: std::ptr::null()
+        }];
+        let dsl_ub1_v_cinfo = br::vk::VkDescriptorSetLayoutCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            bindingCount: 1,
+            pBindings: dsl_ub1_v_bindings.as_ptr()
+        };
+        let r = unsafe { br::vk::vkCreateDescriptorSetLayout(vk_device, &dsl_ub1_v_cinfo, std::ptr::null(), &mut this.dsl_ub1_v) };
+        vk_check(r, "vkCreateDescriptorSetLayout failed")?;
+        let dsp_size = &[br::vk::VkDescriptorPoolSize { _type: br::vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC, descriptorCount: 1 }];
+        let dsp_cinfo = br::vk::VkDescriptorPoolCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            poolSizeCount: 1,
+            pPoolSizes: dsp_size.as_ptr(),
+            maxSets: 1
+        };
+        let r = unsafe { br::vk::vkCreateDescriptorPool(vk_device, &dsp_cinfo, std::ptr::null(), &mut this.dspool) };
+        vk_check(r, "vkCreateDescriptorPool failed")?;
+        let dsp_ainfo = br::vk::VkDescriptorSetAllocateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_ALLOCATE_INFO,
+            pNext: std::ptr::null(),
+            descriptorPool: this.dspool,
+            descriptorSetCount: 1,
+            pSetLayouts: &this.dsl_ub1_v
+        };
+        let r = unsafe { br::vk::vkAllocateDescriptorSets(vk_device, &dsp_ainfo, &mut this.descriptor_set) };
+        vk_check(r, "vkAllocateDescriptorSets failed")?;
+        // one descriptor for all frames; each binds its slice with a dynamic offset
+        let ubinfo_timer = br::vk::VkDescriptorBufferInfo {
+            buffer: this.uniform_buffer, offset: 0, range: options.frame_uniform_size as _
+        };
+        let descriptor_writes = &[br::vk::VkWriteDescriptorSet {
+            sType: br::vk::VK_STRUCTURE_TYPE_WRITE_DESCRIPTOR_SET,
+            pNext: std::ptr::null(),
+            dstSet: this.descriptor_set,
+            dstBinding: 0,
+            dstArrayElement: 0,
+            descriptorType: br::vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER_DYNAMIC,
+            descriptorCount: 1,
+            pBufferInfo: &ubinfo_timer,
+            .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+        }];
+        unsafe { br::vk::vkUpdateDescriptorSets(vk_device, descriptor_writes.len() as _, descriptor_writes.as_ptr(), 0, std::ptr::null()) };
+
+        // textures are bound as set 1 of the textured pipeline
+        let dsl_texture_bindings = &[br::vk::VkDescriptorSetLayoutBinding {
+            binding: 0,
+            descriptorType: br::vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+            descriptorCount: 1,
+            stageFlags: br::vk::VK_SHADER_STAGE_FRAGMENT_BIT,
+            pImmutableSamplers: std::ptr::null()
+        }];
+        let dsl_texture_cinfo = br::vk::VkDescriptorSetLayoutCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            bindingCount: 1,
+            pBindings: dsl_texture_bindings.as_ptr()
+        };
+        let r = unsafe { br::vk::vkCreateDescriptorSetLayout(vk_device, &dsl_texture_cinfo, std::ptr::null(), &mut this.dsl_texture) };
+        vk_check(r, "vkCreateDescriptorSetLayout for Textures failed")?;
+
+        this.vert_shader = create_shader_module(vk_device, options.vertex_shader_path, "Vertex Shader loading failed")?;
+        this.frag_shader = create_shader_module(vk_device, options.fragment_shader_path, "Fragment Shader loading failed")?;
+        this.textured_vert_shader = create_shader_module(
+            vk_device, options.textured_vertex_shader_path, "Textured Vertex Shader loading failed"
+        )?;
+        this.textured_frag_shader = create_shader_module(
+            vk_device, options.textured_fragment_shader_path, "Textured Fragment Shader loading failed"
+        )?;
+        let ps_layout_dsls = &[this.dsl_ub1_v];
+        let ps_layout_push_constants = &[br::vk::VkPushConstantRange {
+            stageFlags: PUSH_CONSTANT_STAGES,
+            offset: 0,
+            size: PUSH_CONSTANT_SIZE
+        }];
+        let ps_layout_cinfo = br::vk::VkPipelineLayoutCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_LAYOUT_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            setLayoutCount: ps_layout_dsls.len() as _,
+            pSetLayouts: ps_layout_dsls.as_ptr() as _,
+            pushConstantRangeCount: ps_layout_push_constants.len() as _,
+            pPushConstantRanges: ps_layout_push_constants.as_ptr()
+        };
+        let r = unsafe { br::vk::vkCreatePipelineLayout(vk_device, &ps_layout_cinfo, std::ptr::null(), &mut this.ps_layout) };
+        vk_check(r, "vkCreatePipelineLayout failed")?;
+        let textured_layout_dsls = &[this.dsl_ub1_v, this.dsl_texture];
+        let textured_layout_cinfo = br::vk::VkPipelineLayoutCreateInfo {
+            setLayoutCount: textured_layout_dsls.len() as _,
+            pSetLayouts: textured_layout_dsls.as_ptr() as _,
+            .. ps_layout_cinfo
+        };
+        let r = unsafe { br::vk::vkCreatePipelineLayout(vk_device, &textured_layout_cinfo, std::ptr::null(), &mut this.textured_layout) };
+        vk_check(r, "vkCreatePipelineLayout for Textured failed")?;
+        let [pipeline, depth_pipeline, textured_pipeline, textured_depth_pipeline] = this.create_pipelines()?;
+        this.pipeline = pipeline;
+        this.depth_pipeline = depth_pipeline;
+        this.textured_pipeline = textured_pipeline;
+        this.textured_depth_pipeline = textured_depth_pipeline;
+
+        for n in 0..frame_count {
+            // the first fence is signaled by the initial upload below, the rest as if their frames had been rendered once
+            let fence_cinfo = br::vk::VkFenceCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_FENCE_CREATE_INFO,
+                pNext: std::ptr::null(),
+                flags: if n == 0 { 0 } else { br::vk::VK_FENCE_CREATE_SIGNALED_BIT }
+            };
+            let mut fence = br::vk::VK_NULL_HANDLE as _;
+            let r = unsafe { br::vk::vkCreateFence(vk_device, &fence_cinfo, std::ptr::null(), &mut fence) };
+            vk_check(r, "vkCreateFence failed")?;
+            this.frames.push(Frame {
+                fence,
+                uniform_offset: uniform_stride * n,
+                command_pool: br::vk::VK_NULL_HANDLE as _,
+                commands: br::vk::VK_NULL_HANDLE as _
+            });
+            // not waiting: the first fence is only signaled by the initial upload
+            this.write_uniform_slice(n, &TimerUniform { time: 0.0 })?;
+
+            let cp_cinfo = br::vk::VkCommandPoolCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO,
+                pNext: std::ptr::null(),
+                flags: br::vk::VK_COMMAND_POOL_CREATE_TRANSIENT_BIT,
+                queueFamilyIndex: this.device.queue_family_index()
+            };
+            let frame = &mut this.frames[n];
+            let r = unsafe { br::vk::vkCreateCommandPool(vk_device, &cp_cinfo, std::ptr::null(), &mut frame.command_pool) };
+            vk_check(r, "vkCreateCommandPool failed")?;
+            let cmd_ainfo = br::vk::VkCommandBufferAllocateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO,
+                pNext: std::ptr::null(),
+                commandPool: frame.command_pool,
+                level: br::vk::VK_COMMAND_BUFFER_LEVEL_PRIMARY,
+                commandBufferCount: 1
+            };
+            let r = unsafe { br::vk::vkAllocateCommandBuffers(vk_device, &cmd_ainfo, &mut frame.commands) };
+            vk_check(r, "vkAllocateCommandBuffers failed")?;
+        }
+
+        let uploads = &mut this.uploads;
+        // the frame fence is left signaled afterwards, so the first frame can be submitted right away
+        this.device.immediate_submit(this.frames[0].fence, |cmd| uploads.record(cmd))?;
+        uploads.close_frame(0);
+        uploads.release_frame(0);
+
+        Ok(this)
+    }
+
+    pub fn device(&self) -> &Rc<Device> { &self.device }
+    pub fn extent(&self) -> &br::vk::VkExtent2D { &self.extent }
+    pub fn buffer_count(&self) -> u32 { self.buffer_count }
+    pub fn max_frame_latency(&self) -> u32 { self.max_frame_latency }
+    /// Frames that can be rendering at once; each has its own fence and uniform slice.
+    pub fn frames_in_flight(&self) -> usize { self.frames.len() }
+    /// Bytes of each frame slot's uniform slice ([`RendererOptions::frame_uniform_size`]).
+    pub fn frame_uniform_size(&self) -> usize { self.frame_uniform_size }
+    /// Frame slot the next [`Renderer::render_list`] renders with, cycling through `0..frames_in_flight()`.
+    pub fn frame_index(&self) -> usize { self.current_frame }
+    /// Render pass of the attached target; pipelines for draw lists are created against it.
+    pub fn render_pass(&self) -> br::vk::VkRenderPass { self.render_pass }
+    /// Format of the depth attachment in the render pass, if any. Pipelines leaving depth testing off ignore it;
+    /// the application's own pipelines set their depth state against it like the built-in `*_depth_pipeline`s.
+    pub fn depth_format(&self) -> Option<br::vk::VkFormat> { self.depth_format }
+    /// Layout of descriptor set 0 (the per-frame uniforms), which pipeline layouts for draw lists start with.
+    pub fn frame_set_layout(&self) -> br::vk::VkDescriptorSetLayout { self.dsl_ub1_v }
+    /// Layout of descriptor set 1 of the textured pipeline: a combined image sampler at binding 0
+    /// (see [`TextureSet`](crate::TextureSet)).
+    pub fn texture_set_layout(&self) -> br::vk::VkDescriptorSetLayout { self.dsl_texture }
+    /// The built-in pipeline, drawing [`Vertex`] triangle lists with [`DrawParams`] pushed at offset 0.
+    pub fn default_pipeline(&self) -> GraphicsPipeline {
+        GraphicsPipeline { pipeline: self.pipeline, layout: self.ps_layout }
+    }
+    /// [`Renderer::default_pipeline`] with depth testing (less or equal) and writes. Without a depth attachment
+    /// ([`RendererOptions::depth_attachment`]) it draws like the default pipeline.
+    pub fn default_depth_pipeline(&self) -> GraphicsPipeline {
+        GraphicsPipeline { pipeline: self.depth_pipeline, layout: self.ps_layout }
+    }
+    /// The built-in pipeline for [`ModelVertex`] triangle lists, multiplying the vertex colors with the
+    /// [`TextureSet`](crate::TextureSet) bound as set 1. [`DrawParams`] are pushed at offset 0 as for the default pipeline.
+    pub fn textured_pipeline(&self) -> GraphicsPipeline {
+        GraphicsPipeline { pipeline: self.textured_pipeline, layout: self.textured_layout }
+    }
+    /// [`Renderer::textured_pipeline`] with depth testing (less or equal) and writes, for 3D geometry. Without a
+    /// depth attachment ([`RendererOptions::depth_attachment`]) it draws like the textured pipeline.
+    pub fn textured_depth_pipeline(&self) -> GraphicsPipeline {
+        GraphicsPipeline { pipeline: self.textured_depth_pipeline, layout: self.textured_layout }
+    }
+
+    /// The default and textured pipelines for the current render pass, each without and with depth testing.
+    fn create_pipelines(&self) -> Result<[br::vk::VkPipeline; 4]> {
+        let vertex_attributes = &[
+            (0, br::vk::VK_FORMAT_R32G32B32A32_SFLOAT, 0),
+            (1, br::vk::VK_FORMAT_R32G32B32A32_SFLOAT, std::mem::size_of::<[f32; 4]>() as _)
+        ];
+        let textured_vertex_attributes = &[
+            (0, br::vk::VK_FORMAT_R32G32B32_SFLOAT, 0),
+            (1, br::vk::VK_FORMAT_R32G32B32_SFLOAT, std::mem::size_of::<[f32; 3]>() as _),
+            (2, br::vk::VK_FORMAT_R32G32_SFLOAT, std::mem::size_of::<[f32; 6]>() as _),
+            (3, br::vk::VK_FORMAT_R32G32B32A32_SFLOAT, std::mem::size_of::<[f32; 8]>() as _)
+        ];
+        let default = (self.ps_layout, self.vert_shader, self.frag_shader, std::mem::size_of::<Vertex>(), &vertex_attributes[..]);
+        let textured = (
+            self.textured_layout, self.textured_vert_shader, self.textured_frag_shader,
+            std::mem::size_of::<ModelVertex>(), &textured_vertex_attributes[..]
+        );
+
+        let mut pipelines = [br::vk::VK_NULL_HANDLE as br::vk::VkPipeline; 4];
+        for (n, &((layout, vert_shader, frag_shader, stride, attributes), depth_test)) in
+            [(default, false), (default, true), (textured, false), (textured, true)].iter().enumerate()
+        {
+            match self.create_pipeline(layout, vert_shader, frag_shader, stride, attributes, depth_test) {
+                Ok(p) => pipelines[n] = p,
+                Err(e) => {
+                    for &p in &pipelines[..n] {
+                        unsafe { br::vk::vkDestroyPipeline(self.device.native_ptr(), p, std::ptr::null()) };
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(pipelines)
+    }
+
+    /// Pipeline for the current render pass, reading one interleaved vertex buffer with the given
+    /// (location, format, offset) attributes, optionally testing and writing depth.
+    fn create_pipeline(
+        &self, layout: br::vk::VkPipelineLayout, vert_shader: br::vk::VkShaderModule, frag_shader: br::vk::VkShaderModule,
+        vertex_stride: usize, vertex_attributes: &[(u32, br::vk::VkFormat, u32)], depth_test: bool
+    ) -> Result<br::vk::VkPipeline> {
+        let shader_entry = std::ffi::CString::new("main").expect("ffi encoding failed");
+        let shader_stage_cinfos = &[
+            br::vk::VkPipelineShaderStageCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
+                pNext: std::ptr::null(),
+                flags: 0,
+                stage: br::vk::VK_SHADER_STAGE_VERTEX_BIT,
+                module: vert_shader,
+                pName: shader_entry.as_ptr(),
+                pSpecializationInfo: std::ptr::null()
+            },
+            br::vk::VkPipelineShaderStageCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
+                pNext: std::ptr::null(),
+                flags: 0,
+                stage: br::vk::VK_SHADER_STAGE_FRAGMENT_BIT,
+                module: frag_shader,
+                pName: shader_entry.as_ptr(),
+                pSpecializationInfo: std::ptr::null()
+            }
+        ];
+        let vertex_input_bindings = &[
+            br::vk::VkVertexInputBindingDescription {
+                binding: 0,
+                stride: vertex_stride as _,
+                inputRate: br::vk::VK_VERTEX_INPUT_RATE_VERTEX
+            }
+        ];
+        let vertex_input_attributes = vertex_attributes.iter().map(|&(location, format, offset)| {
+            br::vk::VkVertexInputAttributeDescription { binding: 0, location, offset, format }
+        }).collect::<Vec<_>>();
+        let vertex_input_state_cinfo = br::vk::VkPipelineVertexInputStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_STAGE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            vertexBindingDescriptionCount: vertex_input_bindings.len() as _,
+            pVertexBindingDescriptions: vertex_input_bindings.as_ptr(),
+            vertexAttributeDescriptionCount: vertex_input_attributes.len() as _,
+            pVertexAttributeDescriptions: vertex_input_attributes.as_ptr()
+        };
+        let input_assembly_state_cinfo = br::vk::VkPipelineInputAssemblyStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            topology: br::vk::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST,
+            primitiveRestartEnable: false as _
+        };
+        // the viewport and scissor are set while recording, from the attached target's extent and the draw regions
+        let viewport_state_cinfo = br::vk::VkPipelineViewportStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            viewportCount: 1,
+            pViewports: std::ptr::null(),
+            scissorCount: 1,
+            pScissors: std::ptr::null()
+        };
+        let dynamic_states = &[br::vk::VK_DYNAMIC_STATE_VIEWPORT, br::vk::VK_DYNAMIC_STATE_SCISSOR];
+        let dynamic_state_cinfo = br::vk::VkPipelineDynamicStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            dynamicStateCount: dynamic_states.len() as _,
+            pDynamicStates: dynamic_states.as_ptr()
+        };
+        let rasterization_state_cinfo = br::vk::VkPipelineRasterizationStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            depthClampEnable: false as _,
+            rasterizerDiscardEnable: false as _,
+            polygonMode: br::vk::VK_POLYGON_MODE_FILL,
+            cullMode: br::vk::VK_CULL_MODE_NONE,
+            frontFace: br::vk::VK_FRONT_FACE_COUNTER_CLOCKWISE,
+            depthBiasEnable: false as _,
+            depthBiasConstantFactor: 0.0,
+            depthBiasClamp: 0.0,
+            depthBiasSlopeFactor: 0.0,
+            lineWidth: 1.0
+        };
+        let multisample_state_cinfo = br::vk::VkPipelineMultisampleStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            rasterizationSamples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+            sampleShadingEnable: false as _,
+            minSampleShading: 1.0,
+            pSampleMask: std::ptr::null(),
+            alphaToCoverageEnable: false as _,
+            alphaToOneEnable: false as _
+        };
+        let color_blend_states = &[
+            br::vk::VkPipelineColorBlendAttachmentState {
+                blendEnable: true as _,
+                srcColorBlendFactor: br::vk::VK_BLEND_FACTOR_ONE,
+                dstColorBlendFactor: br::vk::VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
+                colorBlendOp: br::vk::VK_BLEND_OP_ADD,
+                srcAlphaBlendFactor: br::vk::VK_BLEND_FACTOR_ONE,
+                dstAlphaBlendFactor: br::vk::VK_BLEND_FACTOR_ONE_MINUS_SRC_ALPHA,
+                alphaBlendOp: br::vk::VK_BLEND_OP_ADD,
+                colorWriteMask: 0x0f
+            }
+        ];
+        // stencil testing is left to the application's own pipelines
+        let keep = || br::vk::VkStencilOpState {
+            failOp: br::vk::VK_STENCIL_OP_KEEP,
+            passOp: br::vk::VK_STENCIL_OP_KEEP,
+            depthFailOp: br::vk::VK_STENCIL_OP_KEEP,
+            compareOp: br::vk::VK_COMPARE_OP_ALWAYS,
+            compareMask: 0,
+            writeMask: 0,
+            reference: 0
+        };
+        let depth_stencil_state_cinfo = br::vk::VkPipelineDepthStencilStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            depthTestEnable: depth_test as _,
+            depthWriteEnable: depth_test as _,
+            depthCompareOp: br::vk::VK_COMPARE_OP_LESS_OR_EQUAL,
+            depthBoundsTestEnable: false as _,
+            stencilTestEnable: false as _,
+            front: keep(),
+            back: keep(),
+            minDepthBounds: 0.0,
+            maxDepthBounds: 1.0
+        };
+        let blend_state_cinfo = br::vk::VkPipelineColorBlendStateCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            logicOpEnable: false as _,
+            logicOp: br::vk::VK_LOGIC_OP_CLEAR,
+            attachmentCount: color_blend_states.len() as _,
+            pAttachments: color_blend_states.as_ptr(),
+            blendConstants: [0.0; 4]
+        };
+        let pipeline_cinfo = br::vk::VkGraphicsPipelineCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_GRAPHICS_PIPELINE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            layout,
+            renderPass: self.render_pass,
+            subpass: 0,
+            stageCount: shader_stage_cinfos.len() as _,
+            pStages: shader_stage_cinfos.as_ptr(),
+            pVertexInputState: &vertex_input_state_cinfo,
+            pInputAssemblyState: &input_assembly_state_cinfo,
+            pViewportState: &viewport_state_cinfo,
+            pRasterizationState: &rasterization_state_cinfo,
+            pMultisampleState: &multisample_state_cinfo,
+            pDepthStencilState: if self.depth_format.is_some() { &depth_stencil_state_cinfo } else { std::ptr::null() },
+            pColorBlendState: &blend_state_cinfo,
+            pDynamicState: &dynamic_state_cinfo,
+            .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+        };
+        let mut pipeline = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkCreateGraphicsPipelines(self.device.native_ptr(), br::vk::VK_NULL_HANDLE as _, 1, &pipeline_cinfo, std::ptr::null(), &mut pipeline) };
+        vk_check(r, "vkCreateGraphicsPipelines failed")?;
+
+        Ok(pipeline)
+    }
+
+    /// Creates framebuffers for the target's backbuffer images.
+    ///
+    /// Backbuffers of a previously attached target are released first.
+    pub fn attach(&mut self, target: &dyn PresentTarget) -> Result<()> {
+        self.release_backbuffers()?;
+        let vk_device = self.device.native_ptr();
+
+        let extent = target.extent();
+        // the pipeline stays compatible with the new render pass as long as the format matches
+        let rebuild_pipeline = target.format() != self.format;
+        let render_pass = create_render_pass(vk_device, target.format(), target.present_layout(), self.depth_format)?;
+        unsafe { br::vk::vkDestroyRenderPass(vk_device, self.render_pass, std::ptr::null()) };
+        self.render_pass = render_pass;
+        self.format = target.format();
+        self.extent = extent;
+        if rebuild_pipeline {
+            let [pipeline, depth_pipeline, textured_pipeline, textured_depth_pipeline] = self.create_pipelines()?;
+            unsafe {
+                br::vk::vkDestroyPipeline(vk_device, self.pipeline, std::ptr::null());
+                br::vk::vkDestroyPipeline(vk_device, self.depth_pipeline, std::ptr::null());
+                br::vk::vkDestroyPipeline(vk_device, self.textured_pipeline, std::ptr::null());
+                br::vk::vkDestroyPipeline(vk_device, self.textured_depth_pipeline, std::ptr::null());
+            }
+            self.pipeline = pipeline;
+            self.depth_pipeline = depth_pipeline;
+            self.textured_pipeline = textured_pipeline;
+            self.textured_depth_pipeline = textured_depth_pipeline;
+        }
+
+        for image in target.backbuffer_images() {
+            let iv_cinfo = br::vk::VkImageViewCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO,
+                pNext: std::ptr::null(),
+                image,
+                viewType: br::vk::VK_IMAGE_VIEW_TYPE_2D,
+                format: self.format,
+                components: br::vk::VkComponentMapping {
+                    r: br::vk::VK_COMPONENT_SWIZZLE_R,
+                    g: br::vk::VK_COMPONENT_SWIZZLE_G,
+                    b: br::vk::VK_COMPONENT_SWIZZLE_B,
+                    a: br::vk::VK_COMPONENT_SWIZZLE_A
+                },
+                subresourceRange: br::vk::VkImageSubresourceRange {
+                    aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+                    baseMipLevel: 0,
+                    levelCount: 1,
+                    baseArrayLayer: 0,
+                    layerCount: 1
+                },
+                flags: 0
+            };
+            let mut iv = br::vk::VK_NULL_HANDLE as _;
+            let r = unsafe { br::vk::vkCreateImageView(vk_device, &iv_cinfo, std::ptr::null(), &mut iv) };
+            vk_check(r, "vkCreateImageView failed")?;
+            let iv = UniqueObject(iv, |p| unsafe { br::vk::vkDestroyImageView(vk_device, p, std::ptr::null()); });
+
+            let depth = self.depth_format.map(|f| DepthBuffer::new(&self.device, f, &self.extent)).transpose()?;
+            let image_views = std::iter::once(iv.as_ptr()).chain(depth.as_ref().map(|d| d.view)).collect::<Vec<_>>();
+            let fb_cinfo = br::vk::VkFramebufferCreateInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_FRAMEBUFFER_CREATE_INFO,
+                pNext: std::ptr::null(),
+                flags: 0,
+                renderPass: self.render_pass,
+                attachmentCount: image_views.len() as _,
+                pAttachments: image_views.as_ptr(),
+                width: self.extent.width,
+                height: self.extent.height,
+                layers: 1
+            };
+            let mut fb = br::vk::VK_NULL_HANDLE as _;
+            let r = unsafe { br::vk::vkCreateFramebuffer(vk_device, &fb_cinfo, std::ptr::null(), &mut fb) };
+            vk_check(r, "vkCreateFramebuffer failed")?;
+
+            let view = iv.as_ptr();
+            std::mem::forget(iv);
+            self.backbuffers.push(Backbuffer { view, framebuffer: fb, _depth: depth });
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the device and destroys the framebuffers and views of the attached backbuffers.
+    fn release_backbuffers(&mut self) -> Result<()> {
+        let vk_device = self.device.native_ptr();
+        self.device.wait_idle()?;
+        self.in_flight.clear();
+
+        for bb in self.backbuffers.drain(..) {
+            unsafe {
+                br::vk::vkDestroyFramebuffer(vk_device, bb.framebuffer, std::ptr::null());
+                br::vk::vkDestroyImageView(vk_device, bb.view, std::ptr::null());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws the built-in scene once into each region in [`Renderer::render_frame`]; no regions means once over the
+    /// whole backbuffer. Fails, keeping the previous regions, when a viewport is empty.
+    pub fn set_draw_regions(&mut self, regions: &[DrawRegion]) -> Result<()> {
+        if !regions.iter().all(DrawRegion::has_valid_viewport) {
+            return Err(Error::Unsupported("draw region viewports must have a positive width and height"));
+        }
+        self.draw_regions = regions.to_vec();
+
+        Ok(())
+    }
+
+    /// Blocks until frame slot `index` has finished executing, so its per-frame data can be rewritten.
+    pub fn wait_frame(&self, index: usize) -> Result<()> {
+        let r = unsafe { br::vk::vkWaitForFences(self.device.native_ptr(), 1, &self.frames[index].fence, false as _, u64::MAX) };
+        vk_check(r, "vkWaitForFences failed")
+    }
+
+    /// Writes the animation time read by the vertex shader into the uniform slice of frame slot `index`.
+    ///
+    /// Waits for the slot's previous frame first; the value is picked up when the slot is next rendered.
+    pub fn set_frame_time(&self, index: usize, time: f32) -> Result<()> {
+        self.write_frame_uniforms(index, &TimerUniform { time })
+    }
+
+    /// Writes `data` to the start of frame slot `index`'s uniform slice, which shaders see in the renderer's
+    /// descriptor set (set 0, binding 0). Data for the built-in pipelines has to start with a [`TimerUniform`].
+    ///
+    /// Waits for the slot's previous frame first; the data is picked up when the slot is next rendered.
+    pub fn write_frame_uniforms<T: ShaderData>(&self, index: usize, data: &T) -> Result<()> {
+        if std::mem::size_of::<T>() > self.frame_uniform_size {
+            return Err(Error::Unsupported("frame uniform data exceeds the frame uniform size"));
+        }
+        self.wait_frame(index)?;
+        self.write_uniform_slice(index, data)
+    }
+
+    fn write_uniform_slice<T: ShaderData>(&self, index: usize, data: &T) -> Result<()> {
+        let offset = self.frames[index].uniform_offset;
+        let p = self.uniform_mem.mapped_ptr().ok_or(Error::Unsupported("uniform memory not mapped?"))?;
+        let bytes = data.as_bytes();
+        unsafe { std::slice::from_raw_parts_mut(p.add(offset), bytes.len()).copy_from_slice(bytes) };
+
+        self.device.allocator().flush(&self.uniform_mem, offset as _, bytes.len() as _)
+    }
+
+    /// Writes the animation time for the next submitted frame; `set_frame_time` on [`Renderer::frame_index`].
+    pub fn set_time(&self, time: f32) -> Result<()> { self.set_frame_time(self.current_frame, time) }
+
+    /// Copies `data` into `buffer` at `offset` through the staging ring, ahead of the next submitted frame.
+    ///
+    /// The buffer needs `VK_BUFFER_USAGE_TRANSFER_DST_BIT`; frames rendered from then on read the new contents in
+    /// the vertex input and shader stages. Blocks while earlier frames still hold the staging space needed.
+    pub fn upload_buffer(&mut self, buffer: br::vk::VkBuffer, offset: u64, data: &[u8]) -> Result<()> {
+        if data.is_empty() { return Ok(()); }
+        while !self.uploads.upload_buffer(buffer, offset, data)? {
+            self.reclaim_upload_space()?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies tightly packed texels into an image rectangle through the staging ring, ahead of the next submitted
+    /// frame. The image needs `VK_IMAGE_USAGE_TRANSFER_DST_BIT`; it is left in `dst.new_layout`.
+    pub fn upload_image(&mut self, dst: &ImageUpload, data: &[u8]) -> Result<()> {
+        if data.is_empty() { return Ok(()); }
+        while !self.uploads.upload_image(dst, data)? {
+            self.reclaim_upload_space()?;
+        }
+
+        Ok(())
+    }
+
+    /// Generates levels 1 to `levels - 1` of a color image with linear blits ahead of the next submitted frame, after
+    /// the uploads queued so far. Level 0 has to be uploaded into `VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL`; all levels
+    /// end up in `VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL`.
+    pub(crate) fn generate_mipmaps(&mut self, image: br::vk::VkImage, width: u32, height: u32, levels: u32) {
+        self.uploads.generate_mipmaps(image, width, height, levels, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    /// Waits for the frame holding the oldest staging space and releases it.
+    fn reclaim_upload_space(&mut self) -> Result<()> {
+        let frame = self.uploads.oldest_frame().ok_or(Error::Unsupported("uploads for one frame exceed the upload ring size"))?;
+        self.wait_frame(frame)?;
+        self.uploads.release_frame(frame);
+
+        Ok(())
+    }
+
+    /// Returns true when the next frame can be rendered without waiting for the GPU.
+    pub fn is_frame_ready(&self) -> Result<bool> { self.is_frame_signaled(self.current_frame) }
+
+    fn is_frame_signaled(&self, index: usize) -> Result<bool> {
+        let r = unsafe { br::vk::vkGetFenceStatus(self.device.native_ptr(), self.frames[index].fence) };
+        if r == br::vk::VK_NOT_READY { return Ok(false); }
+        vk_check(r, "vkGetFenceStatus failed").map(|_| true)
+    }
+
+    /// Renders the built-in scene (the triangle, once per draw region) with [`Renderer::render_list`].
+    pub fn render_frame(&mut self, target: &mut dyn PresentTarget) -> Result<()> {
+        let mut scene = std::mem::take(&mut self.scene);
+        scene.clear();
+        scene.bind_pipeline(self.default_pipeline());
+        if self.draw_regions.is_empty() {
+            scene.set_params(0, &DrawParams::default()).draw_mesh(&self.triangle);
+        }
+        for region in &self.draw_regions {
+            scene.set_region(region).set_params(0, &region.params).draw_mesh(&self.triangle);
+        }
+        let r = self.render_list(target, &scene);
+        self.scene = scene;
+
+        r
+    }
+
+    /// Records `list` into the next frame slot's command buffer and renders it into the next backbuffer of `target`.
+    ///
+    /// Targets that wait for the frame on the GPU are presented right away; others once the frame has finished, by a
+    /// later call or [`Renderer::flush`].
+    ///
+    /// Blocks while all `frames_in_flight` slots are still executing; poll `is_frame_ready` to avoid that.
+    /// Does nothing while suspended by a zero-sized [`Renderer::resize`]. When the target reports its backbuffers
+    /// out of date, it is resized to its current extent instead and the frame is skipped. Fails before acquiring a
+    /// backbuffer when the list sets an empty viewport.
+    pub fn render_list(&mut self, target: &mut dyn PresentTarget, list: &DrawList) -> Result<()> {
+        self.retire_frames(target, Some(self.current_frame))?;
+        if self.suspended { return Ok(()); }
+        list.validate()?;
+
+        let image = match target.acquire_next_image() {
+            Err(Error::OutOfDate) => {
+                let extent = target.extent();
+                return self.resize(target, extent.width, extent.height);
+            },
+            r => r?
+        };
+        self.record_frame(self.current_frame, image.index, list)?;
+        let timeline = target.render_complete_timeline(image.index);
+        let render_complete = target.render_complete_semaphore(image.index);
+        self.submit(self.current_frame, &image, render_complete, timeline)?;
+        let pending_image = if timeline.is_some() || render_complete != br::vk::VK_NULL_HANDLE as _ {
+            // the target waits for the timeline point or semaphore itself
+            target.present(image.index)?;
+            None
+        } else {
+            Some(image.index)
+        };
+        self.in_flight.push_back(InFlight { frame: self.current_frame, image: pending_image });
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
+
+        Ok(())
+    }
+
+    /// Waits for all rendered frames and presents them, without rendering a new one.
+    pub fn flush(&mut self, target: &mut dyn PresentTarget) -> Result<()> {
+        let last = self.in_flight.back().map(|f| f.frame);
+        self.retire_frames(target, last)
+    }
+
+    /// Retires in-flight frames in submission order, presenting them where still needed: finished ones without
+    /// blocking, and everything up to frame slot `wait_for` after waiting for it.
+    fn retire_frames(&mut self, target: &mut dyn PresentTarget, wait_for: Option<usize>) -> Result<()> {
+        let mut blocking = wait_for.is_some_and(|w| self.in_flight.iter().any(|f| f.frame == w));
+        while let Some(&InFlight { frame, image }) = self.in_flight.front() {
+            if blocking {
+                self.wait_frame(frame)?;
+            } else if !self.is_frame_signaled(frame)? {
+                break;
+            }
+            self.in_flight.pop_front();
+            self.uploads.release_frame(frame);
+            if let Some(image) = image {
+                target.render_complete(image)?;
+                target.present(image)?;
+            }
+            if Some(frame) == wait_for { blocking = false; }
+        }
+
+        Ok(())
+    }
+
+    /// Resizes the backbuffers of `target` and reattaches them, after presenting the last rendered frame.
+    ///
+    /// A zero width or height (as minimized windows report) suspends rendering until the next nonzero resize.
+    pub fn resize(&mut self, target: &mut dyn PresentTarget, width: u32, height: u32) -> Result<()> {
+        self.flush(target)?;
+        self.suspended = width == 0 || height == 0;
+        if self.suspended { return Ok(()); }
+
+        target.resize(width, height)?;
+        self.attach(target)
+    }
+
+    /// Resets frame slot `frame`'s command pool and records the staged copies and `list` into backbuffer `image`.
+    ///
+    /// The slot's previous submission must have finished.
+    fn record_frame(&mut self, frame: usize, image: usize, list: &DrawList) -> Result<()> {
+        let vk_device = self.device.native_ptr();
+        let Frame { command_pool, commands: cmd, uniform_offset, .. } = self.frames[frame];
+        let r = unsafe { br::vk::vkResetCommandPool(vk_device, command_pool, 0) };
+        vk_check(r, "vkResetCommandPool failed")?;
+        let cmd_begin_info = br::vk::VkCommandBufferBeginInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
+            pNext: std::ptr::null(),
+            flags: br::vk::VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT,
+            pInheritanceInfo: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkBeginCommandBuffer(cmd, &cmd_begin_info) };
+        vk_check(r, "vkBeginCommandBuffer failed")?;
+        if self.uploads.has_pending() { self.uploads.record(cmd); }
+        self.uploads.close_frame(frame);
+
+        let clear_values = &[
+            br::vk::VkClearValue { color: br::vk::VkClearColorValue { float32: [0.0; 4] } },
+            br::vk::VkClearValue { depthStencil: br::vk::VkClearDepthStencilValue { depth: 1.0, stencil: 0 } }
+        ];
+        let clear_values = if self.depth_format.is_some() { &clear_values[..] } else { &clear_values[..1] };
+        let rp_begin_info = br::vk::VkRenderPassBeginInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_RENDER_PASS_BEGIN_INFO,
+            pNext: std::ptr::null(),
+            renderPass: self.render_pass,
+            framebuffer: self.backbuffers[image].framebuffer,
+            renderArea: br::vk::VkRect2D {
+                offset: br::vk::VkOffset2D { x: 0, y: 0 },
+                extent: br::vk::VkExtent2D { width: self.extent.width, height: self.extent.height }
+            },
+            clearValueCount: clear_values.len() as _,
+            pClearValues: clear_values.as_ptr()
+        };
+        let full_region = DrawRegion::rect(0, 0, self.extent.width, self.extent.height);
+        // set 0 is rebound whenever a pipeline with another layout is bound
+        let mut layout = br::vk::VK_NULL_HANDLE as br::vk::VkPipelineLayout;
+        let uniform_offset = uniform_offset as u32;
+        unsafe {
+            br::vk::vkCmdBeginRenderPass(cmd, &rp_begin_info, br::vk::VK_SUBPASS_CONTENTS_INLINE);
+            self.set_region(cmd, &full_region);
+            for c in &list.commands {
+                match *c {
+                    DrawCommand::BindPipeline(p) => {
+                        br::vk::vkCmdBindPipeline(cmd, br::vk::VK_PIPELINE_BIND_POINT_GRAPHICS, p.pipeline);
+                        if p.layout != layout {
+                            layout = p.layout;
+                            br::vk::vkCmdBindDescriptorSets(
+                                cmd, br::vk::VK_PIPELINE_BIND_POINT_GRAPHICS, layout, 0, 1, &self.descriptor_set, 1, &uniform_offset
+                            );
+                        }
+                    },
+                    DrawCommand::BindDescriptorSet(index, set) => {
+                        br::vk::vkCmdBindDescriptorSets(
+                            cmd, br::vk::VK_PIPELINE_BIND_POINT_GRAPHICS, layout, index, 1, &set, 0, std::ptr::null()
+                        );
+                    },
+                    DrawCommand::BindVertexBuffer { binding, buffer, offset } => {
+                        br::vk::vkCmdBindVertexBuffers(cmd, binding, 1, &buffer, &offset);
+                    },
+                    DrawCommand::BindIndexBuffer { buffer, offset, index_type } => {
+                        br::vk::vkCmdBindIndexBuffer(cmd, buffer, offset, index_type);
+                    },
+                    DrawCommand::SetParams { offset, start, len } => {
+                        // every layout declares the same push constant range, so any of them will do
+                        let layout = if layout == br::vk::VK_NULL_HANDLE as _ { self.ps_layout } else { layout };
+                        let bytes = &list.params[start..start + len];
+                        br::vk::vkCmdPushConstants(cmd, layout, PUSH_CONSTANT_STAGES, offset, len as _, bytes.as_ptr() as _);
+                    },
+                    DrawCommand::SetRegion(ref region) => self.set_region(cmd, region),
+                    DrawCommand::Draw { vertex_count, instance_count, first_vertex, first_instance } => {
+                        br::vk::vkCmdDraw(cmd, vertex_count, instance_count, first_vertex, first_instance);
+                    },
+                    DrawCommand::DrawIndexed { index_count, instance_count, first_index, vertex_offset, first_instance } => {
+                        br::vk::vkCmdDrawIndexed(cmd, index_count, instance_count, first_index, vertex_offset, first_instance);
+                    }
+                }
+            }
+            br::vk::vkCmdEndRenderPass(cmd);
+        }
+        let r = unsafe { br::vk::vkEndCommandBuffer(cmd) };
+        vk_check(r, "Recording RenderCommands failed")
+    }
+
+    /// Records the viewport of `region` and its scissor, clamped to the backbuffer.
+    unsafe fn set_region(&self, cmd: br::vk::VkCommandBuffer, region: &DrawRegion) {
+        let viewport = br::vk::VkViewport {
+            x: region.viewport.x, y: region.viewport.y, width: region.viewport.width, height: region.viewport.height,
+            minDepth: 0.0, maxDepth: 1.0
+        };
+        let x = region.scissor.x.min(self.extent.width);
+        let y = region.scissor.y.min(self.extent.height);
+        let scissor = br::vk::VkRect2D {
+            offset: br::vk::VkOffset2D { x: x as _, y: y as _ },
+            extent: br::vk::VkExtent2D {
+                width: region.scissor.width.min(self.extent.width - x),
+                height: region.scissor.height.min(self.extent.height - y)
+            }
+        };
+        br::vk::vkCmdSetViewport(cmd, 0, 1, &viewport);
+        br::vk::vkCmdSetScissor(cmd, 0, 1, &scissor);
+    }
+
+    fn submit(
+        &mut self, frame: usize, image: &AcquiredImage, signal_semaphore: br::vk::VkSemaphore, signal_timeline: Option<TimelinePoint>
+    ) -> Result<()> {
+        let vk_device = self.device.native_ptr();
+        let fence = self.frames[frame].fence;
+        let r = unsafe { br::vk::vkResetFences(vk_device, 1, &fence) };
+        vk_check(r, "vkResetFences failed")?;
+        let command_buffers = &[self.frames[frame].commands];
+        let wait_stages = &[br::vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT];
+        let has_wait = image.wait_semaphore != br::vk::VK_NULL_HANDLE as _;
+        let mut signal_semaphores = Vec::with_capacity(2);
+        let mut signal_values = Vec::with_capacity(2);
+        if signal_semaphore != br::vk::VK_NULL_HANDLE as _ {
+            signal_semaphores.push(signal_semaphore);
+            signal_values.push(0);
+        }
+        if let Some(p) = signal_timeline {
+            signal_semaphores.push(p.semaphore);
+            signal_values.push(p.value);
+        }
+        // values for binary semaphores are ignored; the struct itself is only known with the extension enabled
+        let timeline_info = br::vk::VkTimelineSemaphoreSubmitInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_TIMELINE_SEMAPHORE_SUBMIT_INFO,
+            pNext: std::ptr::null(),
+            waitSemaphoreValueCount: has_wait as _,
+            pWaitSemaphoreValues: &image.wait_value,
+            signalSemaphoreValueCount: signal_values.len() as _,
+            pSignalSemaphoreValues: signal_values.as_ptr()
+        };
+        let submit_infos = &[
+            br::vk::VkSubmitInfo {
+                sType: br::vk::VK_STRUCTURE_TYPE_SUBMIT_INFO,
+                pNext: if self.device.timeline_semaphores() { &timeline_info as *const _ as _ } else { std::ptr::null() },
+                commandBufferCount: command_buffers.len() as _,
+                pCommandBuffers: command_buffers.as_ptr(),
+                waitSemaphoreCount: has_wait as _,
+                pWaitSemaphores: &image.wait_semaphore,
+                pWaitDstStageMask: wait_stages.as_ptr(),
+                signalSemaphoreCount: signal_semaphores.len() as _,
+                pSignalSemaphores: signal_semaphores.as_ptr()
+            }
+        ];
+        let r = unsafe { br::vk::vkQueueSubmit(self.device.queue(), submit_infos.len() as _, submit_infos.as_ptr(), fence) };
+        vk_check(r, "vkQueueSubmit loop failed")
+    }
+
+    pub fn wait_idle(&self) -> Result<()> { self.device.wait_idle() }
+}
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        let vk_device = self.device.native_ptr();
+        let _ = self.device.wait_idle();
+
+        unsafe {
+            for bb in self.backbuffers.drain(..) {
+                br::vk::vkDestroyFramebuffer(vk_device, bb.framebuffer, std::ptr::null());
+                br::vk::vkDestroyImageView(vk_device, bb.view, std::ptr::null());
+            }
+            for frame in &self.frames {
+                br::vk::vkDestroyCommandPool(vk_device, frame.command_pool, std::ptr::null());
+                br::vk::vkDestroyFence(vk_device, frame.fence, std::ptr::null());
+            }
+            br::vk::vkDestroyPipeline(vk_device, self.textured_depth_pipeline, std::ptr::null());
+            br::vk::vkDestroyPipeline(vk_device, self.textured_pipeline, std::ptr::null());
+            br::vk::vkDestroyPipeline(vk_device, self.depth_pipeline, std::ptr::null());
+            br::vk::vkDestroyPipeline(vk_device, self.pipeline, std::ptr::null());
+            br::vk::vkDestroyPipelineLayout(vk_device, self.textured_layout, std::ptr::null());
+            br::vk::vkDestroyPipelineLayout(vk_device, self.ps_layout, std::ptr::null());
+            br::vk::vkDestroyShaderModule(vk_device, self.textured_frag_shader, std::ptr::null());
+            br::vk::vkDestroyShaderModule(vk_device, self.textured_vert_shader, std::ptr::null());
+            br::vk::vkDestroyShaderModule(vk_device, self.frag_shader, std::ptr::null());
+            br::vk::vkDestroyShaderModule(vk_device, self.vert_shader, std::ptr::null());
+            br::vk::vkDestroyDescriptorPool(vk_device, self.dspool, std::ptr::null());
+            br::vk::vkDestroyDescriptorSetLayout(vk_device, self.dsl_texture, std::ptr::null());
+            br::vk::vkDestroyDescriptorSetLayout(vk_device, self.dsl_ub1_v, std::ptr::null());
+            br::vk::vkDestroyBuffer(vk_device, self.uniform_buffer, std::ptr::null());
+            br::vk::vkDestroyRenderPass(vk_device, self.render_pass, std::ptr::null());
+        }
+        self.device.allocator().free(std::mem::take(&mut self.uniform_mem));
+    }
+}
+
+/// Render pass drawing into a single backbuffer, cleared on load and left in `final_layout`.
+fn create_render_pass(
+    vk_device: br::vk::VkDevice, format: br::vk::VkFormat, final_layout: br::vk::VkImageLayout,
+    depth_format: Option<br::vk::VkFormat>
+) -> Result<br::vk::VkRenderPass> {
+    let mut rp_attachment_desc = vec![br::vk::VkAttachmentDescription {
+        format,
+        samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+        loadOp: br::vk::VK_ATTACHMENT_LOAD_OP_CLEAR,
+        storeOp: br::vk::VK_ATTACHMENT_STORE_OP_STORE,
+        stencilLoadOp: br::vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE,
+        stencilStoreOp: br::vk::VK_ATTACHMENT_STORE_OP_DONT_CARE,
+        initialLayout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED,
+        finalLayout: final_layout,
+        flags: 0
+    }];
+    if let Some(depth_format) = depth_format {
+        // only read within the pass, so nothing is stored
+        let stencil = (depth_stencil_aspects(depth_format) & br::vk::VK_IMAGE_ASPECT_STENCIL_BIT) != 0;
+        rp_attachment_desc.push(br::vk::VkAttachmentDescription {
+            format: depth_format,
+            samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+            loadOp: br::vk::VK_ATTACHMENT_LOAD_OP_CLEAR,
+            storeOp: br::vk::VK_ATTACHMENT_STORE_OP_DONT_CARE,
+            stencilLoadOp: if stencil { br::vk::VK_ATTACHMENT_LOAD_OP_CLEAR } else { br::vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE },
+            stencilStoreOp: br::vk::VK_ATTACHMENT_STORE_OP_DONT_CARE,
+            initialLayout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED,
+            finalLayout: br::vk::VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            flags: 0
+        });
+    }
+    let rp_attachment_color_out = &[br::vk::VkAttachmentReference { attachment: 0, layout: br::vk::VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL }];
+    let rp_attachment_depth = br::vk::VkAttachmentReference {
+        attachment: 1, layout: br::vk::VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+    };
+    let rp_subpass_color_desc = &[br::vk::VkSubpassDescription {
+        flags: 0,
+        pipelineBindPoint: br::vk::VK_PIPELINE_BIND_POINT_GRAPHICS,
+        inputAttachmentCount: 0,
+        pInputAttachments: std::ptr::null(),
+        colorAttachmentCount: 1,
+        pColorAttachments: rp_attachment_color_out.as_ptr(),
+        pResolveAttachments: std::ptr::null(),
+        pDepthStencilAttachment: if depth_format.is_some() { &rp_attachment_depth } else { std::ptr::null() },
+        preserveAttachmentCount: 0,
+        pPreserveAttachments: std::ptr::null()
+    }];
+    // the depth clear must also wait for the previous frame's depth tests on the same image
+    let (depth_stages, depth_access) = if depth_format.is_some() {
+        (
+            br::vk::VK_PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT | br::vk::VK_PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT,
+            br::vk::VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT
+        )
+    } else {
+        (0, 0)
+    };
+    let rp_subpass_deps = &[
+        br::vk::VkSubpassDependency {
+            srcSubpass: 0, dstSubpass: 0,
+            srcAccessMask: br::vk::VK_ACCESS_MEMORY_READ_BIT, dstAccessMask: br::vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT,
+            srcStageMask: br::vk::VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT, dstStageMask: br::vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT,
+            dependencyFlags: br::vk::VK_DEPENDENCY_BY_REGION_BIT
+        },
+        // orders the layout transition after the wait on the target's acquire semaphore
+        br::vk::VkSubpassDependency {
+            srcSubpass: br::vk::VK_SUBPASS_EXTERNAL, dstSubpass: 0,
+            srcAccessMask: depth_access, dstAccessMask: br::vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT | depth_access,
+            srcStageMask: br::vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT | depth_stages,
+            dstStageMask: br::vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT | depth_stages,
+            dependencyFlags: 0
+        }
+    ];
+    let rp_cinfo = br::vk::VkRenderPassCreateInfo {
+        sType: br::vk::VK_STRUCTURE_TYPE_RENDER_PASS_CREATE_INFO,
+        pNext: std::ptr::null(),
+        flags: 0,
+        attachmentCount: rp_attachment_desc.len() as _,
+        pAttachments: rp_attachment_desc.as_ptr(),
+        subpassCount: 1,
+        pSubpasses: rp_subpass_color_desc.as_ptr(),
+        dependencyCount: rp_subpass_deps.len() as _,
+        pDependencies: rp_subpass_deps.as_ptr()
+    };
+    let mut render_pass = br::vk::VK_NULL_HANDLE as _;
+    let r = unsafe { br::vk::vkCreateRenderPass(vk_device, &rp_cinfo, std::ptr::null(), &mut render_pass) };
+    vk_check(r, "vkCreateRenderPass failed")?;
+
+    Ok(render_pass)
+}
+
+/// Aspects of a depth, stencil or depth/stencil format.
+fn depth_stencil_aspects(format: br::vk::VkFormat) -> br::vk::VkImageAspectFlags {
+    match format {
+        br::vk::VK_FORMAT_S8_UINT => br::vk::VK_IMAGE_ASPECT_STENCIL_BIT,
+        br::vk::VK_FORMAT_D16_UNORM_S8_UINT | br::vk::VK_FORMAT_D24_UNORM_S8_UINT | br::vk::VK_FORMAT_D32_SFLOAT_S8_UINT =>
+            br::vk::VK_IMAGE_ASPECT_DEPTH_BIT | br::vk::VK_IMAGE_ASPECT_STENCIL_BIT,
+        _ => br::vk::VK_IMAGE_ASPECT_DEPTH_BIT
+    }
+}
+
+fn create_shader_module(vk_device: br::vk::VkDevice, path: &Path, load_ctx: &'static str) -> Result<br::vk::VkShaderModule> {
+    let binary = load_spirv(path).map_err(|e| Error::Os(load_ctx, e))?;
+    let shader_cinfo = br::vk::VkShaderModuleCreateInfo {
+        sType: br::vk::VK_STRUCTURE_TYPE_SHADER_MODULE_CREATE_INFO,
+        pNext: std::ptr::null(),
+        flags: 0,
+        codeSize: (binary.len() * 4) as _,
+        pCode: binary.as_ptr()
+    };
+    let mut shader = br::vk::VK_NULL_HANDLE as _;
+    let r = unsafe { br::vk::vkCreateShaderModule(vk_device, &shader_cinfo, std::ptr::null(), &mut shader) };
+    vk_check(r, "vkCreateShaderModule failed")?;
+
+    Ok(shader)
+}
+
+/// SPIR-V words of the file at `path`, in host byte order like the code vkCreateShaderModule takes.
+fn load_spirv(path: &Path) -> std::io::Result<Vec<u32>> {
+    let bytes = std::fs::read(path)?;
+    if bytes.len() % 4 != 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "SPIR-V size is not a multiple of 4 bytes"));
+    }
+
+    Ok(bytes.chunks_exact(4).map(|w| u32::from_ne_bytes([w[0], w[1], w[2], w[3]])).collect())
+}
@@ -0,0 +1,428 @@
+//! 2D shapes tessellated into [`ModelVertex`] triangles for [`Renderer::textured_pipeline`], in pixels.
+//!
+//! Shapes are drawn with a [`WhiteTexture`] bound, so their (straight alpha) vertex colors come out premultiplied by
+//! the pipeline. Edges are anti-aliased with a fringe fading out over [`ShapeBuilder::feather`] pixels.
+
+use crate::{Mesh, ModelVertex, Renderer, Result, Sampler, SamplerOptions, Texture, TextureSet};
+
+/// Miter joins longer than this many half widths are cut short.
+const MITER_LIMIT: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect { pub x: f32, pub y: f32, pub width: f32, pub height: f32 }
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self { Rect { x, y, width, height } }
+}
+
+/// Colors of filled or stroked shapes, straight alpha. Gradients are evaluated per vertex and interpolated between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paint {
+    Solid([f32; 4]),
+    /// Blends from `start_color` at `start` to `end_color` at `end`, clamped beyond them.
+    LinearGradient { start: [f32; 2], end: [f32; 2], start_color: [f32; 4], end_color: [f32; 4] },
+    /// Blends from `inner_color` at `center` to `outer_color` at `radius` and beyond.
+    RadialGradient { center: [f32; 2], radius: f32, inner_color: [f32; 4], outer_color: [f32; 4] }
+}
+impl Paint {
+    pub fn color_at(&self, p: [f32; 2]) -> [f32; 4] {
+        let (t, from, to) = match *self {
+            Paint::Solid(c) => return c,
+            Paint::LinearGradient { start, end, start_color, end_color } => {
+                let d = sub(end, start);
+                let len2 = dot(d, d);
+                (if len2 > 0.0 { dot(sub(p, start), d) / len2 } else { 0.0 }, start_color, end_color)
+            },
+            Paint::RadialGradient { center, radius, inner_color, outer_color } => {
+                (if radius > 0.0 { length(sub(p, center)) / radius } else { 1.0 }, inner_color, outer_color)
+            }
+        };
+        let t = t.clamp(0.0, 1.0);
+
+        [0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * t)
+    }
+}
+impl From<[f32; 4]> for Paint {
+    fn from(color: [f32; 4]) -> Self { Paint::Solid(color) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    /// In pixels, centered on the outline.
+    pub width: f32,
+    pub paint: Paint
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Segment {
+    MoveTo([f32; 2]),
+    LineTo([f32; 2]),
+    QuadTo([f32; 2], [f32; 2]),
+    CubicTo([f32; 2], [f32; 2], [f32; 2]),
+    Close
+}
+
+/// Outlines of lines and quadratic or cubic Bézier curves, in subpaths started by [`Path::move_to`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path { segments: Vec<Segment> }
+impl Path {
+    pub fn new() -> Self { Path::default() }
+
+    pub fn move_to(&mut self, p: [f32; 2]) -> &mut Self {
+        self.segments.push(Segment::MoveTo(p));
+        self
+    }
+    pub fn line_to(&mut self, p: [f32; 2]) -> &mut Self {
+        self.segments.push(Segment::LineTo(p));
+        self
+    }
+    pub fn quad_to(&mut self, control: [f32; 2], p: [f32; 2]) -> &mut Self {
+        self.segments.push(Segment::QuadTo(control, p));
+        self
+    }
+    pub fn cubic_to(&mut self, control1: [f32; 2], control2: [f32; 2], p: [f32; 2]) -> &mut Self {
+        self.segments.push(Segment::CubicTo(control1, control2, p));
+        self
+    }
+    /// Connects the current subpath back to its start.
+    pub fn close(&mut self) -> &mut Self {
+        self.segments.push(Segment::Close);
+        self
+    }
+
+    /// Approximates the subpaths by polylines deviating at most `tolerance` pixels from the curves, returning each
+    /// with whether it was closed. Lines without a preceding `move_to` start at the origin.
+    pub fn flatten(&self, tolerance: f32) -> Vec<(Vec<[f32; 2]>, bool)> {
+        let mut subpaths: Vec<(Vec<[f32; 2]>, bool)> = Vec::new();
+        let mut current = vec![[0.0, 0.0]];
+        // ends the current subpath, continuing from its start when closed and from its end otherwise
+        let mut finish = |points: &mut Vec<[f32; 2]>, closed| {
+            let restart = if closed { points[0] } else { *points.last().expect("subpaths are never empty") };
+            let done = std::mem::replace(points, vec![restart]);
+            if done.len() > 1 { subpaths.push((done, closed)); }
+        };
+        for s in &self.segments {
+            let last = *current.last().expect("subpaths are never empty");
+            match *s {
+                Segment::MoveTo(p) => {
+                    finish(&mut current, false);
+                    current = vec![p];
+                },
+                Segment::LineTo(p) => current.push(p),
+                Segment::QuadTo(c, p) => {
+                    let n = curve_steps(length(sub(add(last, p), scale(c, 2.0))) / 4.0, tolerance);
+                    current.extend((1..=n).map(|i| {
+                        let t = i as f32 / n as f32;
+                        let u = 1.0 - t;
+                        add(add(scale(last, u * u), scale(c, 2.0 * u * t)), scale(p, t * t))
+                    }));
+                },
+                Segment::CubicTo(c1, c2, p) => {
+                    let d1 = length(sub(add(last, c2), scale(c1, 2.0)));
+                    let d2 = length(sub(add(c1, p), scale(c2, 2.0)));
+                    let n = curve_steps(d1.max(d2) * 3.0 / 4.0, tolerance);
+                    current.extend((1..=n).map(|i| {
+                        let t = i as f32 / n as f32;
+                        let u = 1.0 - t;
+                        add(
+                            add(scale(last, u * u * u), scale(c1, 3.0 * u * u * t)),
+                            add(scale(c2, 3.0 * u * t * t), scale(p, t * t * t))
+                        )
+                    }));
+                },
+                Segment::Close => {
+                    if current.len() > 1 && current.first() == current.last() { current.pop(); }
+                    finish(&mut current, true);
+                }
+            }
+        }
+        finish(&mut current, false);
+
+        subpaths
+    }
+}
+
+/// Accumulates tessellated shapes; later shapes draw over earlier ones.
+#[derive(Debug, Clone)]
+pub struct ShapeBuilder {
+    /// Maximum distance in pixels between curves and the line segments approximating them.
+    pub tolerance: f32,
+    /// Width in pixels over which edges fade out, half inside and half outside the outline. 0 disables anti-aliasing.
+    pub feather: f32,
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u32>
+}
+impl Default for ShapeBuilder {
+    /// Quarter pixel tolerance, one pixel feather.
+    fn default() -> Self {
+        ShapeBuilder { tolerance: 0.25, feather: 1.0, vertices: Vec::new(), indices: Vec::new() }
+    }
+}
+impl ShapeBuilder {
+    pub fn new() -> Self { ShapeBuilder::default() }
+
+    pub fn vertices(&self) -> &[ModelVertex] { &self.vertices }
+    pub fn indices(&self) -> &[u32] { &self.indices }
+    pub fn is_empty(&self) -> bool { self.indices.is_empty() }
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, paint: &Paint) -> &mut Self {
+        self.fill_polygon(&rect_points(rect), paint)
+    }
+    pub fn stroke_rect(&mut self, rect: Rect, stroke: &Stroke) -> &mut Self {
+        self.stroke_polyline(&rect_points(rect), true, stroke)
+    }
+
+    /// Corner radii are clamped to half the shorter side.
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: f32, paint: &Paint) -> &mut Self {
+        let points = self.rounded_rect_points(rect, radius);
+        self.fill_polygon(&points, paint)
+    }
+    pub fn stroke_rounded_rect(&mut self, rect: Rect, radius: f32, stroke: &Stroke) -> &mut Self {
+        let points = self.rounded_rect_points(rect, radius);
+        self.stroke_polyline(&points, true, stroke)
+    }
+
+    pub fn fill_circle(&mut self, center: [f32; 2], radius: f32, paint: &Paint) -> &mut Self {
+        let points = self.arc_points(center, radius, 0.0, std::f32::consts::TAU, false);
+        self.fill_polygon(&points, paint)
+    }
+    pub fn stroke_circle(&mut self, center: [f32; 2], radius: f32, stroke: &Stroke) -> &mut Self {
+        let points = self.arc_points(center, radius, 0.0, std::f32::consts::TAU, false);
+        self.stroke_polyline(&points, true, stroke)
+    }
+
+    /// Fills each subpath as a simple polygon, closed implicitly. Holes and self-intersections are not supported.
+    pub fn fill_path(&mut self, path: &Path, paint: &Paint) -> &mut Self {
+        for (points, _) in path.flatten(self.tolerance) {
+            self.fill_polygon(&points, paint);
+        }
+        self
+    }
+    pub fn stroke_path(&mut self, path: &Path, stroke: &Stroke) -> &mut Self {
+        for (points, closed) in path.flatten(self.tolerance) {
+            self.stroke_polyline(&points, closed, stroke);
+        }
+        self
+    }
+
+    /// Fills a simple polygon, convex or not, in either winding order.
+    pub fn fill_polygon(&mut self, points: &[[f32; 2]], paint: &Paint) -> &mut Self {
+        let points = dedup(points, true);
+        if points.len() < 3 { return self; }
+        let area = signed_area(&points);
+        if area == 0.0 { return self; }
+
+        // offsets point outwards whichever way the polygon winds
+        let offsets = miter_offsets(&points, true).into_iter().map(|o| scale(o, area.signum())).collect::<Vec<_>>();
+        let half_feather = self.feather * 0.5;
+        let base = self.vertices.len() as u32;
+        for (&p, &o) in points.iter().zip(&offsets) {
+            let p = sub(p, scale(o, half_feather));
+            self.push_vertex(p, paint, 1.0);
+        }
+        let triangles = triangulate(&points, area > 0.0);
+        self.indices.extend(triangles.iter().map(|&i| base + i as u32));
+
+        if self.feather > 0.0 {
+            let outer = self.vertices.len() as u32;
+            for (&p, &o) in points.iter().zip(&offsets) {
+                self.push_vertex(add(p, scale(o, half_feather)), paint, 0.0);
+            }
+            let n = points.len() as u32;
+            for i in 0..n {
+                let j = (i + 1) % n;
+                self.push_quad(base + i, base + j, outer + j, outer + i);
+            }
+        }
+        self
+    }
+
+    /// Strokes the line segments between `points`, with miter joins and butt caps.
+    pub fn stroke_polyline(&mut self, points: &[[f32; 2]], closed: bool, stroke: &Stroke) -> &mut Self {
+        let points = dedup(points, closed);
+        if points.len() < 2 || stroke.width <= 0.0 { return self; }
+
+        // thin lines fade out instead of getting thinner than the feather
+        let (half_width, alpha) = if stroke.width < self.feather {
+            (self.feather * 0.5, stroke.width / self.feather)
+        } else {
+            (stroke.width * 0.5, 1.0)
+        };
+        let inner_half = half_width - self.feather * 0.5;
+        let outer_half = half_width + self.feather * 0.5;
+        // cross sections from one side of the outline to the other
+        let sections: &[(f32, f32)] = if self.feather > 0.0 {
+            &[(-outer_half, 0.0), (-inner_half, alpha), (inner_half, alpha), (outer_half, 0.0)]
+        } else {
+            &[(-half_width, alpha), (half_width, alpha)]
+        };
+
+        let offsets = miter_offsets(&points, closed);
+        let base = self.vertices.len() as u32;
+        for (&p, &o) in points.iter().zip(&offsets) {
+            for &(d, a) in sections {
+                self.push_vertex(add(p, scale(o, d)), &stroke.paint, a);
+            }
+        }
+        let (n, k) = (points.len() as u32, sections.len() as u32);
+        let segments = if closed { n } else { n - 1 };
+        for i in 0..segments {
+            let (a, b) = (base + i * k, base + (i + 1) % n * k);
+            for s in 0..k - 1 {
+                self.push_quad(a + s, b + s, b + s + 1, a + s + 1);
+            }
+        }
+        self
+    }
+
+    /// Uploads the shapes as a mesh; `None` when there are none.
+    pub fn build_mesh(&self, renderer: &mut Renderer) -> Result<Option<Mesh>> {
+        if self.is_empty() { return Ok(None); }
+
+        Mesh::from_u32_indices(renderer, &self.vertices, &self.indices).map(Some)
+    }
+
+    fn push_vertex(&mut self, p: [f32; 2], paint: &Paint, alpha: f32) {
+        let mut color = paint.color_at(p);
+        color[3] *= alpha;
+        self.vertices.push(ModelVertex { position: [p[0], p[1], 0.0], normal: [0.0, 0.0, -1.0], uv: [0.5, 0.5], color });
+    }
+    fn push_quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.indices.extend_from_slice(&[a, b, c, a, c, d]);
+    }
+
+    /// Points along an arc from angle `start` (radians, clockwise from +X on screen), excluding the end unless asked for.
+    fn arc_points(&self, center: [f32; 2], radius: f32, start: f32, sweep: f32, include_end: bool) -> Vec<[f32; 2]> {
+        let step = if radius > self.tolerance { 2.0 * (1.0 - self.tolerance / radius).acos() } else { std::f32::consts::FRAC_PI_2 };
+        let n = ((sweep.abs() / step).ceil() as usize).clamp(if include_end { 1 } else { 3 }, 1024);
+        let count = if include_end { n + 1 } else { n };
+
+        (0..count).map(|i| {
+            let a = start + sweep * i as f32 / n as f32;
+            [center[0] + radius * a.cos(), center[1] + radius * a.sin()]
+        }).collect()
+    }
+
+    fn rounded_rect_points(&self, rect: Rect, radius: f32) -> Vec<[f32; 2]> {
+        let r = radius.min(rect.width.abs() * 0.5).min(rect.height.abs() * 0.5);
+        if r <= 0.0 { return rect_points(rect).to_vec(); }
+        let (x0, y0, x1, y1) = (rect.x + r, rect.y + r, rect.x + rect.width - r, rect.y + rect.height - r);
+        let quarter = std::f32::consts::FRAC_PI_2;
+
+        [([x1, y0], -quarter), ([x1, y1], 0.0), ([x0, y1], quarter), ([x0, y0], 2.0 * quarter)].iter()
+            .flat_map(|&(c, start)| self.arc_points(c, r, start, quarter, true))
+            .collect()
+    }
+}
+
+/// 1×1 opaque white texture bound for [`Renderer::textured_pipeline`], so the vertex colors are drawn as they are
+/// (shapes, or models without a base color texture).
+pub struct WhiteTexture {
+    set: TextureSet,
+    _sampler: Sampler,
+    texture: Texture
+}
+impl WhiteTexture {
+    pub fn new(renderer: &mut Renderer) -> Result<Self> {
+        let texture = Texture::new(renderer, 1, 1, &[255; 4])?;
+        let sampler = Sampler::new(renderer, &SamplerOptions::default())?;
+        let set = TextureSet::new(renderer, &texture, &sampler)?;
+
+        Ok(WhiteTexture { set, _sampler: sampler, texture })
+    }
+
+    /// Binds the texture as set 1 of [`Renderer::textured_pipeline`].
+    pub fn texture_set(&self) -> &TextureSet { &self.set }
+    pub fn texture(&self) -> &Texture { &self.texture }
+}
+
+fn rect_points(r: Rect) -> [[f32; 2]; 4] {
+    [[r.x, r.y], [r.x + r.width, r.y], [r.x + r.width, r.y + r.height], [r.x, r.y + r.height]]
+}
+
+/// Drops repeated points, including a closing point equal to the first.
+fn dedup(points: &[[f32; 2]], closed: bool) -> Vec<[f32; 2]> {
+    let mut out = points.to_vec();
+    out.dedup();
+    if closed && out.len() > 1 && out.first() == out.last() { out.pop(); }
+    out
+}
+
+/// Positive when the points go clockwise on screen (y down).
+fn signed_area(points: &[[f32; 2]]) -> f32 {
+    let n = points.len();
+    (0..n).map(|i| cross(points[i], points[(i + 1) % n])).sum::<f32>() * 0.5
+}
+
+/// Per point, the direction and distance to move it for the outline to move out by one unit: left of the direction
+/// of travel on screen, which is outwards for clockwise polygons. Sharp joins are limited by [`MITER_LIMIT`].
+fn miter_offsets(points: &[[f32; 2]], closed: bool) -> Vec<[f32; 2]> {
+    let n = points.len();
+    let normal = |i: usize| {
+        let d = sub(points[(i + 1) % n], points[i]);
+        scale([d[1], -d[0]], 1.0 / length(d))
+    };
+
+    (0..n).map(|i| {
+        let (before, after) = match (closed, i) {
+            (false, 0) => (normal(0), normal(0)),
+            (false, i) if i == n - 1 => (normal(n - 2), normal(n - 2)),
+            (_, i) => (normal((i + n - 1) % n), normal(i))
+        };
+        let sum = add(before, after);
+        let len = length(sum);
+        if len < 1e-6 { return before; }
+        let miter = scale(sum, 1.0 / len);
+        // 1 / cos of half the angle between the normals
+        let extent = (1.0 / dot(miter, after)).min(MITER_LIMIT);
+
+        scale(miter, extent)
+    }).collect()
+}
+
+/// Triangulates a simple polygon by clipping ears, returning indices into `points`.
+fn triangulate(points: &[[f32; 2]], clockwise: bool) -> Vec<usize> {
+    let mut remaining = (0..points.len()).collect::<Vec<_>>();
+    let mut triangles = Vec::with_capacity((points.len() - 2) * 3);
+    // convex corners turn the same way as the polygon winds
+    let convex = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| {
+        let turn = cross(sub(b, a), sub(c, b));
+        if clockwise { turn > 0.0 } else { turn < 0.0 }
+    };
+    let inside = |p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]| {
+        let (d1, d2, d3) = (cross(sub(b, a), sub(p, a)), cross(sub(c, b), sub(p, b)), cross(sub(a, c), sub(p, c)));
+        !((d1 < 0.0 || d2 < 0.0 || d3 < 0.0) && (d1 > 0.0 || d2 > 0.0 || d3 > 0.0))
+    };
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            convex(points[a], points[b], points[c]) && remaining.iter()
+                .filter(|&&j| j != a && j != b && j != c)
+                .all(|&j| !inside(points[j], points[a], points[b], points[c]))
+        });
+        // degenerate (collinear or self-intersecting) leftovers are cut anywhere
+        let i = ear.unwrap_or(0);
+        triangles.extend_from_slice(&[remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+        remaining.remove(i);
+    }
+    triangles.extend_from_slice(&remaining);
+
+    triangles
+}
+
+/// Segments for a curve whose control polygon bulges `deviation` pixels, keeping within `tolerance`.
+fn curve_steps(deviation: f32, tolerance: f32) -> usize {
+    ((deviation / tolerance.max(1e-3)).sqrt().ceil() as usize).clamp(1, 256)
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] { [a[0] + b[0], a[1] + b[1]] }
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] { [a[0] - b[0], a[1] - b[1]] }
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] { [a[0] * s, a[1] * s] }
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 { a[0] * b[0] + a[1] * b[1] }
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 { a[0] * b[1] - a[1] * b[0] }
+fn length(a: [f32; 2]) -> f32 { dot(a, a).sqrt() }
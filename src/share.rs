@@ -0,0 +1,382 @@
+//! Producer/consumer split over a Unix domain socket: a rendering process draws into [`SharedImage`]s and
+//! passes their fds to the window-owning process, which imports them and is told which image holds each frame.
+//!
+//! Every message is 40 bytes. The producer opens with `Hello` followed by one `Image` per
+//! backbuffer and a `Timeline`, each carrying its fd as `SCM_RIGHTS` ancillary data, and the consumer answers `Ready`
+//! once everything is imported. From then on the producer sends `Frame` for each submitted frame, along with the
+//! timeline value that signals its completion, and the consumer sends `Release` when it no longer reads an image.
+//! A resize starts over with `Hello`.
+
+use bedrock as br;
+use crate::{
+    AcquiredImage, Device, Error, FdHandleType, PresentTarget, Renderer, Result,
+    SharedImage, SharedImageDesc, SharedImageTarget, TimelinePoint, TimelineSemaphore
+};
+use std::collections::VecDeque;
+use std::io::prelude::Write;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::rc::Rc;
+
+const PROTOCOL_VERSION: u32 = 2;
+const MESSAGE_SIZE: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Message {
+    Hello { version: u32, image_count: u32 },
+    /// Followed by the image fd as ancillary data.
+    Image { index: u32, desc: SharedImageDesc },
+    /// Followed by the render completion timeline semaphore fd as ancillary data.
+    Timeline,
+    Ready,
+    Frame { index: u32, value: u64 },
+    Release { index: u32 }
+}
+impl Message {
+    fn encode(&self) -> [u8; MESSAGE_SIZE] {
+        let mut bytes = [0; MESSAGE_SIZE];
+        let mut put = |offset: usize, v: &[u8]| bytes[offset..offset + v.len()].copy_from_slice(v);
+        match *self {
+            Message::Hello { version, image_count } => {
+                put(0, &1u32.to_le_bytes());
+                put(4, &version.to_le_bytes());
+                put(8, &image_count.to_le_bytes());
+            },
+            Message::Image { index, desc } => {
+                let handle_type: u32 = match desc.handle_type { FdHandleType::OpaqueFd => 0, FdHandleType::DmaBuf => 1 };
+                put(0, &2u32.to_le_bytes());
+                put(4, &index.to_le_bytes());
+                put(8, &handle_type.to_le_bytes());
+                put(12, &desc.format.to_le_bytes());
+                put(16, &desc.width.to_le_bytes());
+                put(20, &desc.height.to_le_bytes());
+                put(24, &(desc.dedicated as u32).to_le_bytes());
+                put(32, &desc.allocation_size.to_le_bytes());
+            },
+            Message::Ready => put(0, &3u32.to_le_bytes()),
+            Message::Frame { index, value } => {
+                put(0, &4u32.to_le_bytes());
+                put(4, &index.to_le_bytes());
+                put(8, &value.to_le_bytes());
+            },
+            Message::Release { index } => {
+                put(0, &5u32.to_le_bytes());
+                put(4, &index.to_le_bytes());
+            },
+            Message::Timeline => put(0, &6u32.to_le_bytes())
+        }
+
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let u32_at = |offset: usize| {
+            let mut v = [0; 4];
+            v.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(v)
+        };
+        let u64_at = |offset: usize| {
+            let mut v = [0; 8];
+            v.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(v)
+        };
+
+        match u32_at(0) {
+            1 => Ok(Message::Hello { version: u32_at(4), image_count: u32_at(8) }),
+            2 => {
+                let handle_type = match u32_at(8) {
+                    0 => FdHandleType::OpaqueFd,
+                    1 => FdHandleType::DmaBuf,
+                    _ => return Err(protocol_error("unknown handle type"))
+                };
+                Ok(Message::Image {
+                    index: u32_at(4),
+                    desc: SharedImageDesc {
+                        handle_type,
+                        format: u32_at(12) as _,
+                        width: u32_at(16),
+                        height: u32_at(20),
+                        dedicated: u32_at(24) != 0,
+                        allocation_size: u64_at(32)
+                    }
+                })
+            },
+            3 => Ok(Message::Ready),
+            4 => Ok(Message::Frame { index: u32_at(4), value: u64_at(8) }),
+            5 => Ok(Message::Release { index: u32_at(4) }),
+            6 => Ok(Message::Timeline),
+            _ => Err(protocol_error("unknown message"))
+        }
+    }
+}
+
+fn protocol_error(what: &'static str) -> Error {
+    Error::Os("Shared target protocol violation", std::io::Error::new(std::io::ErrorKind::InvalidData, what))
+}
+
+/// Socket end with the bytes and fds received so far.
+struct Channel {
+    stream: UnixStream,
+    received: Vec<u8>,
+    fds: VecDeque<OwnedFd>
+}
+impl Channel {
+    fn new(stream: UnixStream) -> Self {
+        Channel { stream, received: Vec::new(), fds: VecDeque::new() }
+    }
+
+    fn send(&mut self, message: &Message, fd: Option<RawFd>) -> Result<()> {
+        let bytes = message.encode();
+        let mut iov = libc::iovec { iov_base: bytes.as_ptr() as _, iov_len: bytes.len() };
+        // u64 storage keeps the control buffer aligned for cmsghdr
+        let mut control = [0u64; 4];
+        let mut header: libc::msghdr = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        if let Some(fd) = fd {
+            header.msg_control = control.as_mut_ptr() as _;
+            header.msg_controllen = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as _) } as _;
+            unsafe {
+                let cmsg = libc::CMSG_FIRSTHDR(&header);
+                (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as _) as _;
+                std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, fd);
+            }
+        }
+
+        self.stream.set_nonblocking(false).map_err(|e| Error::Os("Setting socket blocking mode failed", e))?;
+        let sent = unsafe { libc::sendmsg(self.stream.as_raw_fd(), &header, libc::MSG_NOSIGNAL) };
+        if sent < 0 { return Err(Error::Os("sendmsg failed", std::io::Error::last_os_error())); }
+        // the fd went along with the first byte; whatever did not fit goes out as plain data
+        self.stream.write_all(&bytes[sent as usize..]).map_err(|e| Error::Os("Writing to socket failed", e))
+    }
+
+    /// Reads the next message, and the fd attached to it if it is an `Image` or a `Timeline`.
+    ///
+    /// Returns `None` if `blocking` is off and no complete message has arrived yet.
+    fn recv(&mut self, blocking: bool) -> Result<Option<(Message, Option<OwnedFd>)>> {
+        while self.received.len() < MESSAGE_SIZE {
+            match self.fill(blocking) {
+                Ok(()) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(Error::Os("Reading from socket failed", e))
+            }
+        }
+        let message = Message::decode(&self.received[..MESSAGE_SIZE])?;
+        self.received.drain(..MESSAGE_SIZE);
+        let fd = match message {
+            Message::Image { .. } | Message::Timeline => Some(self.fds.pop_front().ok_or(protocol_error("message without fd"))?),
+            _ => None
+        };
+
+        Ok(Some((message, fd)))
+    }
+
+    fn recv_blocking(&mut self) -> Result<(Message, Option<OwnedFd>)> {
+        self.recv(true).map(|m| m.expect("blocking receive returned nothing"))
+    }
+
+    fn fill(&mut self, blocking: bool) -> std::io::Result<()> {
+        self.stream.set_nonblocking(!blocking)?;
+
+        let mut chunk = [0u8; MESSAGE_SIZE * 4];
+        let mut iov = libc::iovec { iov_base: chunk.as_mut_ptr() as _, iov_len: chunk.len() };
+        let mut control = [0u64; 16];
+        let mut header: libc::msghdr = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr() as _;
+        header.msg_controllen = std::mem::size_of_val(&control) as _;
+        let received = unsafe { libc::recvmsg(self.stream.as_raw_fd(), &mut header, libc::MSG_CMSG_CLOEXEC) };
+        if received < 0 { return Err(std::io::Error::last_os_error()); }
+        if received == 0 { return Err(std::io::ErrorKind::UnexpectedEof.into()); }
+
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let count = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / std::mem::size_of::<libc::c_int>();
+                    let data = libc::CMSG_DATA(cmsg) as *const libc::c_int;
+                    for n in 0..count {
+                        self.fds.push_back(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(n))));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+            }
+        }
+        if (header.msg_flags & libc::MSG_CTRUNC) != 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "ancillary data truncated"));
+        }
+        self.received.extend_from_slice(&chunk[..received as usize]);
+
+        Ok(())
+    }
+}
+
+/// Renders into exported images and hands each submitted frame to a [`SocketConsumer`], which waits for the frame to
+/// finish through the shared timeline semaphore.
+///
+/// Acquiring an image blocks until the consumer has released it.
+pub struct SocketProducer {
+    images: SharedImageTarget,
+    channel: Channel,
+    released: Vec<bool>
+}
+impl SocketProducer {
+    /// Connects to a consumer listening at `path` and shares `image_count` (at least 2) backbuffers in the renderer's extent.
+    ///
+    /// Needs the handle type's device extensions and [`TimelineSemaphore::FD_DEVICE_EXTENSIONS`] on both ends.
+    pub fn connect(renderer: &Renderer, path: impl AsRef<Path>, handle_type: FdHandleType, image_count: usize) -> Result<Self> {
+        if image_count < 2 { return Err(Error::Unsupported("sharing needs at least 2 images")); }
+        let stream = UnixStream::connect(path).map_err(|e| Error::Os("Connecting to consumer failed", e))?;
+        let mut images = SharedImageTarget::new(renderer, handle_type, image_count)?;
+        images.enable_timeline()?;
+        let mut this = SocketProducer { images, channel: Channel::new(stream), released: Vec::new() };
+        this.share_images()?;
+
+        Ok(this)
+    }
+
+    fn share_images(&mut self) -> Result<()> {
+        let images = self.images.images();
+        self.channel.send(&Message::Hello { version: PROTOCOL_VERSION, image_count: images.len() as _ }, None)?;
+        for (n, img) in images.iter().enumerate() {
+            let fd = img.export_fd()?;
+            self.channel.send(&Message::Image { index: n as _, desc: *img.desc() }, Some(fd.as_raw_fd()))?;
+        }
+        let timeline_fd = self.images.timeline().expect("no timeline").export_fd()?;
+        self.channel.send(&Message::Timeline, Some(timeline_fd.as_raw_fd()))?;
+        loop {
+            match self.channel.recv_blocking()?.0 {
+                Message::Ready => break,
+                // meant for the images replaced by now
+                Message::Release { .. } => (),
+                _ => return Err(protocol_error("expected Ready"))
+            }
+        }
+        self.released = vec![true; images.len()];
+
+        Ok(())
+    }
+}
+impl PresentTarget for SocketProducer {
+    fn format(&self) -> br::vk::VkFormat { self.images.format() }
+    fn extent(&self) -> br::vk::VkExtent2D { self.images.extent() }
+    fn backbuffer_images(&self) -> Vec<br::vk::VkImage> { self.images.backbuffer_images() }
+
+    fn acquire_next_image(&mut self) -> Result<AcquiredImage> {
+        let acquired = self.images.acquire_next_image()?;
+        while !self.released[acquired.index] {
+            match self.channel.recv_blocking()?.0 {
+                Message::Release { index } => {
+                    *self.released.get_mut(index as usize).ok_or(protocol_error("release of unknown image"))? = true;
+                },
+                _ => return Err(protocol_error("expected Release"))
+            }
+        }
+        self.released[acquired.index] = false;
+
+        Ok(acquired)
+    }
+    fn render_complete_timeline(&mut self, index: usize) -> Option<TimelinePoint> {
+        self.images.render_complete_timeline(index)
+    }
+    fn present(&mut self, index: usize) -> Result<()> {
+        self.images.present(index)?;
+        let value = self.images.last_presented_point().expect("no timeline").value;
+        self.channel.send(&Message::Frame { index: index as _, value }, None)
+    }
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.images.resize(width, height)?;
+        self.share_images()
+    }
+}
+
+/// Window-owning end: imports the producer's images and tracks which one holds the newest frame.
+pub struct SocketConsumer {
+    device: Rc<Device>,
+    channel: Channel,
+    images: Vec<SharedImage>,
+    timeline: Option<TimelineSemaphore>,
+    latest: Option<(usize, u64)>
+}
+impl SocketConsumer {
+    /// Waits for a producer to connect on `listener` and imports its images into `renderer`'s device.
+    pub fn accept(renderer: &Renderer, listener: &UnixListener) -> Result<Self> {
+        let (stream, _) = listener.accept().map_err(|e| Error::Os("Accepting producer failed", e))?;
+        let mut this = SocketConsumer {
+            device: renderer.device().clone(), channel: Channel::new(stream), images: Vec::new(), timeline: None, latest: None
+        };
+        match this.channel.recv_blocking()?.0 {
+            Message::Hello { version, image_count } => this.import_images(version, image_count)?,
+            _ => return Err(protocol_error("expected Hello"))
+        }
+
+        Ok(this)
+    }
+
+    fn import_images(&mut self, version: u32, image_count: u32) -> Result<()> {
+        if version != PROTOCOL_VERSION { return Err(protocol_error("protocol version mismatch")); }
+
+        self.device.wait_idle()?;
+        self.images.clear();
+        self.latest = None;
+        for n in 0..image_count {
+            match self.channel.recv_blocking()? {
+                (Message::Image { index, desc }, Some(fd)) if index == n => {
+                    self.images.push(SharedImage::imported(self.device.clone(), &desc, fd)?);
+                },
+                _ => return Err(protocol_error("expected Image"))
+            }
+        }
+        match self.channel.recv_blocking()? {
+            (Message::Timeline, Some(fd)) => self.timeline = Some(TimelineSemaphore::imported_fd(self.device.clone(), fd)?),
+            _ => return Err(protocol_error("expected Timeline"))
+        }
+        self.channel.send(&Message::Ready, None)
+    }
+
+    /// Handles everything the producer has sent so far, without blocking.
+    ///
+    /// Returns the index of the newest frame if one arrived; it may still be rendering until
+    /// [`SocketConsumer::latest_point`] is reached. The image holding the previous frame is handed back
+    /// to the producer, so it must no longer be in use. [`SocketConsumer::images`] may have been replaced on return.
+    pub fn poll(&mut self) -> Result<Option<usize>> {
+        let mut newest = None;
+        while let Some((message, _)) = self.channel.recv(false)? {
+            match message {
+                Message::Frame { index, value } => {
+                    let index = index as usize;
+                    if index >= self.images.len() { return Err(protocol_error("frame in unknown image")); }
+                    if let Some((previous, _)) = self.latest.filter(|&(p, _)| p != index) {
+                        self.channel.send(&Message::Release { index: previous as _ }, None)?;
+                    }
+                    self.latest = Some((index, value));
+                    newest = Some(index);
+                },
+                Message::Hello { version, image_count } => {
+                    self.import_images(version, image_count)?;
+                    newest = None;
+                },
+                _ => return Err(protocol_error("unexpected message from producer"))
+            }
+        }
+
+        Ok(newest)
+    }
+
+    pub fn images(&self) -> &[SharedImage] { &self.images }
+    /// Index of the image holding the newest frame received.
+    pub fn latest(&self) -> Option<usize> { self.latest.map(|(n, _)| n) }
+    /// Timeline point at which the newest frame has finished rendering. Wait for it before reading the image,
+    /// on the GPU or with [`TimelineSemaphore::wait`].
+    pub fn latest_point(&self) -> Option<TimelinePoint> {
+        let timeline = self.timeline.as_ref()?;
+        self.latest.map(|(_, value)| timeline.point(value))
+    }
+    /// Render completion timeline shared by the producer.
+    pub fn timeline(&self) -> Option<&TimelineSemaphore> { self.timeline.as_ref() }
+}
@@ -0,0 +1,227 @@
+use bedrock as br;
+use uninit::extension_traits::*;
+use crate::{vk_check, AcquiredImage, Device, Error, PresentTarget, Renderer, Result};
+use std::rc::Rc;
+
+/// Vulkan swapchain on a window system surface, composited with premultiplied alpha.
+pub struct SwapchainTarget {
+    device: Rc<Device>,
+    surface: br::vk::VkSurfaceKHR,
+    swapchain: br::vk::VkSwapchainKHR,
+    format: br::vk::VkFormat,
+    extent: br::vk::VkExtent2D,
+    buffer_count: u32,
+    frames_in_flight: u32,
+    images: Vec<br::vk::VkImage>,
+    /// Used in turn, so that none is reused before the frame that waited on it has finished.
+    acquire_semaphores: Vec<br::vk::VkSemaphore>,
+    next_acquire: usize,
+    render_complete_semaphores: Vec<br::vk::VkSemaphore>,
+    /// Set when presenting found the swapchain out of date; acquires fail until it has been recreated.
+    out_of_date: bool
+}
+impl SwapchainTarget {
+    /// Device extensions the renderer must be created with.
+    pub const DEVICE_EXTENSIONS: &'static [&'static str] = &["VK_KHR_swapchain"];
+
+    /// Takes ownership of `surface`, which is destroyed along with the target.
+    ///
+    /// `width`/`height` are only used when the surface leaves the swapchain size up to the application.
+    pub fn new(renderer: &Renderer, surface: br::vk::VkSurfaceKHR, width: u32, height: u32) -> Result<Self> {
+        let mut this = SwapchainTarget {
+            device: renderer.device().clone(),
+            surface,
+            swapchain: br::vk::VK_NULL_HANDLE as _,
+            format: br::vk::VK_FORMAT_UNDEFINED,
+            extent: br::vk::VkExtent2D { width, height },
+            buffer_count: renderer.buffer_count(),
+            frames_in_flight: renderer.frames_in_flight() as _,
+            images: Vec::new(),
+            acquire_semaphores: Vec::new(),
+            next_acquire: 0,
+            render_complete_semaphores: Vec::new(),
+            out_of_date: false
+        };
+        let adapter = this.device.adapter();
+
+        let mut supported = 0;
+        let r = unsafe {
+            br::vk::vkGetPhysicalDeviceSurfaceSupportKHR(adapter, this.device.queue_family_index(), this.surface, &mut supported)
+        };
+        vk_check(r, "vkGetPhysicalDeviceSurfaceSupportKHR failed")?;
+        if supported == 0 { return Err(Error::Unsupported("graphics queue cannot present to the surface")); }
+
+        let mut format_count = 0;
+        let r = unsafe { br::vk::vkGetPhysicalDeviceSurfaceFormatsKHR(adapter, this.surface, &mut format_count, std::ptr::null_mut()) };
+        vk_check(r, "vkGetPhysicalDeviceSurfaceFormatsKHR failed")?;
+        let mut formats = Vec::new();
+        let r = unsafe {
+            br::vk::vkGetPhysicalDeviceSurfaceFormatsKHR(
+                adapter, this.surface, &mut format_count, formats.reserve_uninit(format_count as _).as_mut_ptr() as _
+            )
+        };
+        vk_check(r, "vkGetPhysicalDeviceSurfaceFormatsKHR failed")?;
+        unsafe { formats.set_len(formats.len() + format_count as usize); }
+        // the renderer writes premultiplied values as they are, so no sRGB encoding on the way out
+        this.format = formats.iter()
+            .map(|f: &br::vk::VkSurfaceFormatKHR| f.format)
+            .find(|&f| f == br::vk::VK_FORMAT_B8G8R8A8_UNORM || f == br::vk::VK_FORMAT_R8G8B8A8_UNORM)
+            .or_else(|| formats.first().map(|f| f.format))
+            .ok_or(Error::Unsupported("no surface formats?"))?;
+        if this.format == br::vk::VK_FORMAT_UNDEFINED { this.format = crate::BACKBUFFER_FORMAT; }
+
+        this.create_swapchain(width, height)?;
+
+        Ok(this)
+    }
+
+    fn create_swapchain(&mut self, width: u32, height: u32) -> Result<()> {
+        let vk_device = self.device.native_ptr();
+
+        let mut caps = std::mem::MaybeUninit::uninit();
+        let r = unsafe { br::vk::vkGetPhysicalDeviceSurfaceCapabilitiesKHR(self.device.adapter(), self.surface, caps.as_mut_ptr()) };
+        vk_check(r, "vkGetPhysicalDeviceSurfaceCapabilitiesKHR failed")?;
+        let caps: br::vk::VkSurfaceCapabilitiesKHR = unsafe { caps.assume_init() };
+        self.extent = if caps.currentExtent.width == u32::MAX {
+            br::vk::VkExtent2D {
+                width: width.max(caps.minImageExtent.width).min(caps.maxImageExtent.width),
+                height: height.max(caps.minImageExtent.height).min(caps.maxImageExtent.height)
+            }
+        } else {
+            br::vk::VkExtent2D { width: caps.currentExtent.width, height: caps.currentExtent.height }
+        };
+        // the application holds one image per frame in flight on top of what the presentation engine keeps
+        let mut image_count = (caps.minImageCount + self.frames_in_flight - 1).max(self.buffer_count);
+        if caps.maxImageCount != 0 { image_count = image_count.min(caps.maxImageCount); }
+        // INHERIT leaves it to the window system, which blends ARGB visuals premultiplied as well
+        let composite_alpha = [br::vk::VK_COMPOSITE_ALPHA_PRE_MULTIPLIED_BIT_KHR, br::vk::VK_COMPOSITE_ALPHA_INHERIT_BIT_KHR]
+            .iter().copied()
+            .find(|&a| (caps.supportedCompositeAlpha & a) != 0)
+            .ok_or(Error::Unsupported("surface does not support premultiplied alpha compositing"))?;
+
+        let old_swapchain = self.swapchain;
+        let swapchain_cinfo = br::vk::VkSwapchainCreateInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_SWAPCHAIN_CREATE_INFO_KHR,
+            pNext: std::ptr::null(),
+            flags: 0,
+            surface: self.surface,
+            minImageCount: image_count,
+            imageFormat: self.format,
+            imageColorSpace: br::vk::VK_COLOR_SPACE_SRGB_NONLINEAR_KHR,
+            imageExtent: br::vk::VkExtent2D { width: self.extent.width, height: self.extent.height },
+            imageArrayLayers: 1,
+            imageUsage: br::vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT,
+            imageSharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null(),
+            preTransform: caps.currentTransform,
+            compositeAlpha: composite_alpha,
+            presentMode: br::vk::VK_PRESENT_MODE_FIFO_KHR,
+            clipped: true as _,
+            oldSwapchain: old_swapchain
+        };
+        let r = unsafe { br::vk::vkCreateSwapchainKHR(vk_device, &swapchain_cinfo, std::ptr::null(), &mut self.swapchain) };
+        unsafe { br::vk::vkDestroySwapchainKHR(vk_device, old_swapchain, std::ptr::null()) };
+        if let Err(e) = vk_check(r, "vkCreateSwapchainKHR failed") {
+            self.swapchain = br::vk::VK_NULL_HANDLE as _;
+            return Err(e);
+        }
+
+        let mut image_count = 0;
+        let r = unsafe { br::vk::vkGetSwapchainImagesKHR(vk_device, self.swapchain, &mut image_count, std::ptr::null_mut()) };
+        vk_check(r, "vkGetSwapchainImagesKHR failed")?;
+        self.images = vec![br::vk::VK_NULL_HANDLE as _; image_count as _];
+        let r = unsafe { br::vk::vkGetSwapchainImagesKHR(vk_device, self.swapchain, &mut image_count, self.images.as_mut_ptr()) };
+        vk_check(r, "vkGetSwapchainImagesKHR failed")?;
+
+        let semaphore_cinfo = br::vk::VkSemaphoreCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0
+        };
+        while self.render_complete_semaphores.len() < self.images.len() {
+            let mut s = br::vk::VK_NULL_HANDLE as _;
+            let r = unsafe { br::vk::vkCreateSemaphore(vk_device, &semaphore_cinfo, std::ptr::null(), &mut s) };
+            vk_check(r, "vkCreateSemaphore failed")?;
+            self.render_complete_semaphores.push(s);
+        }
+        while self.acquire_semaphores.len() < self.images.len() {
+            let mut s = br::vk::VK_NULL_HANDLE as _;
+            let r = unsafe { br::vk::vkCreateSemaphore(vk_device, &semaphore_cinfo, std::ptr::null(), &mut s) };
+            vk_check(r, "vkCreateSemaphore failed")?;
+            self.acquire_semaphores.push(s);
+        }
+        self.out_of_date = false;
+
+        Ok(())
+    }
+}
+impl PresentTarget for SwapchainTarget {
+    fn format(&self) -> br::vk::VkFormat { self.format }
+    fn extent(&self) -> br::vk::VkExtent2D {
+        br::vk::VkExtent2D { width: self.extent.width, height: self.extent.height }
+    }
+    fn backbuffer_images(&self) -> Vec<br::vk::VkImage> { self.images.clone() }
+    fn present_layout(&self) -> br::vk::VkImageLayout { br::vk::VK_IMAGE_LAYOUT_PRESENT_SRC_KHR }
+
+    fn acquire_next_image(&mut self) -> Result<AcquiredImage> {
+        if self.out_of_date { return Err(Error::OutOfDate); }
+        let semaphore = self.acquire_semaphores[self.next_acquire];
+        let mut index = 0;
+        let r = unsafe {
+            br::vk::vkAcquireNextImageKHR(
+                self.device.native_ptr(), self.swapchain, u64::MAX, semaphore, br::vk::VK_NULL_HANDLE as _, &mut index
+            )
+        };
+        // still presentable; the owner is expected to resize on the next configure event
+        if r == br::vk::VK_ERROR_OUT_OF_DATE_KHR { return Err(Error::OutOfDate); }
+        if r != br::vk::VK_SUBOPTIMAL_KHR { vk_check(r, "vkAcquireNextImageKHR failed")?; }
+        self.next_acquire = (self.next_acquire + 1) % self.acquire_semaphores.len();
+
+        Ok(AcquiredImage { index: index as _, wait_semaphore: semaphore, wait_value: 0 })
+    }
+    fn render_complete_semaphore(&self, index: usize) -> br::vk::VkSemaphore {
+        self.render_complete_semaphores[index]
+    }
+    fn present(&mut self, index: usize) -> Result<()> {
+        let index = index as u32;
+        let present_info = br::vk::VkPresentInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_PRESENT_INFO_KHR,
+            pNext: std::ptr::null(),
+            waitSemaphoreCount: 1,
+            pWaitSemaphores: &self.render_complete_semaphores[index as usize],
+            swapchainCount: 1,
+            pSwapchains: &self.swapchain,
+            pImageIndices: &index,
+            pResults: std::ptr::null_mut()
+        };
+        let r = unsafe { br::vk::vkQueuePresentKHR(self.device.queue(), &present_info) };
+        if r == br::vk::VK_SUBOPTIMAL_KHR { return Ok(()); }
+        // the frame is dropped; the next acquire has the renderer recreate the swapchain
+        if r == br::vk::VK_ERROR_OUT_OF_DATE_KHR {
+            self.out_of_date = true;
+            return Ok(());
+        }
+        vk_check(r, "vkQueuePresentKHR failed")
+    }
+    fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.create_swapchain(width, height)
+    }
+}
+impl Drop for SwapchainTarget {
+    fn drop(&mut self) {
+        let vk_device = self.device.native_ptr();
+        let _ = self.device.wait_idle();
+
+        unsafe {
+            for s in self.render_complete_semaphores.drain(..) {
+                br::vk::vkDestroySemaphore(vk_device, s, std::ptr::null());
+            }
+            for s in self.acquire_semaphores.drain(..) {
+                br::vk::vkDestroySemaphore(vk_device, s, std::ptr::null());
+            }
+            br::vk::vkDestroySwapchainKHR(vk_device, self.swapchain, std::ptr::null());
+            br::vk::vkDestroySurfaceKHR(self.device.instance(), self.surface, std::ptr::null());
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Text drawn from a glyph atlas: outlines of TrueType/OpenType fonts are rasterized on demand into a [`Texture`]
+//! and laid out as quads for [`Renderer::textured_pipeline`].
+
+use ab_glyph::{Font as _, ScaleFont as _};
+use bedrock as br;
+use crate::{Error, ImageUpload, Mesh, ModelVertex, Renderer, Result, Sampler, SamplerOptions, Texture, TextureSet};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// DejaVu Sans, see `assets/fonts/LICENSE`.
+static BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+static NEXT_FONT_ID: AtomicUsize = AtomicUsize::new(0);
+/// Empty texels right and below each glyph, so linear filtering never picks up a neighbour.
+const GLYPH_PADDING: u32 = 1;
+
+/// A TrueType or OpenType font. Clones share the outlines and the glyphs cached for them.
+#[derive(Clone)]
+pub struct Font {
+    id: usize,
+    font: ab_glyph::FontArc
+}
+impl Font {
+    /// The font compiled into the library (DejaVu Sans).
+    pub fn bundled() -> Self {
+        Font::new(ab_glyph::FontArc::try_from_slice(BUNDLED_FONT).expect("bundled font is invalid"))
+    }
+
+    /// Parses TrueType or OpenType font file contents; collections use their first font.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        ab_glyph::FontArc::try_from_vec(bytes).map(Font::new).map_err(|e| {
+            Error::Os("Parsing font failed", std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+        })
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        Font::from_bytes(std::fs::read(path).map_err(|e| Error::Os("Reading font failed", e))?)
+    }
+
+    fn new(font: ab_glyph::FontArc) -> Self {
+        Font { id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed), font }
+    }
+
+    /// Lays out `text` from the top-left corner: lines end at `\n`, and between words wherever they would get wider
+    /// than `style.max_width`. Words wider than a whole line are broken between characters.
+    pub fn layout(&self, text: &str, style: &TextStyle) -> TextLayout {
+        let font = self.font.as_scaled(style.size);
+        let line_height = (font.height() + font.line_gap()) * style.line_spacing;
+        let max_width = style.max_width.unwrap_or(f32::INFINITY);
+        let mut layout = TextLayout { size: style.size, color: style.color, glyphs: Vec::new(), width: 0.0, height: 0.0 };
+
+        let mut line = 0;
+        for paragraph in text.split('\n') {
+            let (mut x, mut prev) = (0.0, None);
+            // each segment is a word followed by (at most) one whitespace character
+            for segment in paragraph.split_inclusive(char::is_whitespace) {
+                let word_width = segment.chars().filter(|c| !c.is_whitespace() && !c.is_control()).fold((0.0, None), |(w, prev), c| {
+                    let id = font.glyph_id(c);
+                    (w + prev.map_or(0.0, |p| font.kern(p, id)) + font.h_advance(id), Some(id))
+                }).0;
+                if x > 0.0 && word_width > 0.0 && x + word_width > max_width {
+                    line += 1;
+                    x = 0.0;
+                    prev = None;
+                }
+
+                for c in segment.chars().filter(|c| !c.is_control()) {
+                    let id = font.glyph_id(c);
+                    let advance = font.h_advance(id);
+                    x += prev.map_or(0.0, |p| font.kern(p, id));
+                    if !c.is_whitespace() {
+                        if x > 0.0 && x + advance > max_width {
+                            line += 1;
+                            x = 0.0;
+                        }
+                        layout.glyphs.push(LaidOutGlyph { id: id.0, x, y: line as f32 * line_height + font.ascent(), line });
+                        layout.width = layout.width.max(x + advance);
+                    }
+                    x += advance;
+                    prev = Some(id);
+                }
+            }
+            line += 1;
+        }
+        layout.height = line as f32 * line_height;
+
+        layout
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Pixels from the highest ascender to the lowest descender.
+    pub size: f32,
+    /// Straight alpha.
+    pub color: [f32; 4],
+    /// Lines are broken before getting wider, in pixels.
+    pub max_width: Option<f32>,
+    /// Multiplied with the line height the font recommends.
+    pub line_spacing: f32
+}
+impl Default for TextStyle {
+    /// 16 pixel white text without line breaking.
+    fn default() -> Self {
+        TextStyle { size: 16.0, color: [1.0; 4], max_width: None, line_spacing: 1.0 }
+    }
+}
+
+/// A glyph placed on a baseline, in pixels from the top-left corner of the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaidOutGlyph {
+    /// Glyph index in the font.
+    pub id: u16,
+    pub x: f32,
+    /// The baseline.
+    pub y: f32,
+    pub line: usize
+}
+
+/// Glyphs of a string laid out with a [`Font`]; whitespace takes up space but has no glyphs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub size: f32,
+    pub color: [f32; 4],
+    pub glyphs: Vec<LaidOutGlyph>,
+    /// Of the widest line, without trailing whitespace.
+    pub width: f32,
+    /// All lines, including empty ones.
+    pub height: f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey { font: usize, id: u16, size_bits: u32 }
+/// Rasterized glyph: its texels in the atlas, and where they go relative to the glyph origin.
+#[derive(Debug, Clone, Copy)]
+struct AtlasGlyph { x: u32, y: u32, width: u32, height: u32, offset: [f32; 2] }
+/// Row of glyphs in the atlas, filled from the left.
+struct Shelf { y: u32, height: u32, x: u32 }
+
+/// Square texture the glyphs of any font and size are rasterized into as premultiplied white, with a set binding
+/// it for [`Renderer::textured_pipeline`]. Text color comes from the vertices.
+///
+/// Glyphs are added on first use by [`GlyphAtlas::append_quads`] and stay until [`GlyphAtlas::clear`]; new ones are
+/// uploaded by [`GlyphAtlas::flush`].
+pub struct GlyphAtlas {
+    // the set refers to the sampler and texture, so it goes first
+    set: TextureSet,
+    _sampler: Sampler,
+    texture: Texture,
+    size: u32,
+    pixels: Vec<u8>,
+    shelves: Vec<Shelf>,
+    /// Glyphs without any coverage (spaces) are kept as `None`.
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    /// Rows changed since the last flush.
+    dirty_rows: Option<(u32, u32)>
+}
+impl GlyphAtlas {
+    /// Creates a `size` × `size` atlas, uploading it blank through the staging ring (4 bytes per texel).
+    pub fn new(renderer: &mut Renderer, size: u32) -> Result<Self> {
+        let pixels = vec![0; size as usize * size as usize * 4];
+        let texture = Texture::new(renderer, size, size, &pixels)?;
+        let sampler = Sampler::new(renderer, &SamplerOptions::default())?;
+        let set = TextureSet::new(renderer, &texture, &sampler)?;
+
+        Ok(GlyphAtlas {
+            set,
+            _sampler: sampler,
+            texture,
+            size,
+            pixels,
+            shelves: Vec::new(),
+            glyphs: HashMap::new(),
+            dirty_rows: None
+        })
+    }
+
+    /// Binds the atlas as set 1 of [`Renderer::textured_pipeline`].
+    pub fn texture_set(&self) -> &TextureSet { &self.set }
+    pub fn texture(&self) -> &Texture { &self.texture }
+    pub fn size(&self) -> u32 { self.size }
+
+    /// Forgets all glyphs, making room for others. Quads appended before refer to stale texels.
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|p| *p = 0);
+        self.shelves.clear();
+        self.glyphs.clear();
+        self.dirty_rows = Some((0, self.size));
+    }
+
+    /// Appends two triangles per glyph of `layout` (made with `font`) to `vertices` and `indices`, with the top-left
+    /// corner of the text at `origin` pixels. Glyph origins snap to whole pixels so the texels map 1:1 when drawn with
+    /// [`DrawParams::pixel_space`](crate::DrawParams::pixel_space) over the whole target.
+    ///
+    /// Fails without appending anything once the atlas has no room left for a missing glyph.
+    pub fn append_quads(
+        &mut self, font: &Font, layout: &TextLayout, origin: [f32; 2], vertices: &mut Vec<ModelVertex>, indices: &mut Vec<u32>
+    ) -> Result<()> {
+        let mut quads = Vec::with_capacity(layout.glyphs.len());
+        for g in &layout.glyphs {
+            if let Some(a) = self.glyph(font, g.id, layout.size)? {
+                quads.push((a, [(origin[0] + g.x).round() + a.offset[0], (origin[1] + g.y).round() + a.offset[1]]));
+            }
+        }
+
+        let texel = 1.0 / self.size as f32;
+        for (a, [x, y]) in quads {
+            let (u0, v0) = (a.x as f32 * texel, a.y as f32 * texel);
+            let (u1, v1) = ((a.x + a.width) as f32 * texel, (a.y + a.height) as f32 * texel);
+            let (x1, y1) = (x + a.width as f32, y + a.height as f32);
+            let base = vertices.len() as u32;
+            vertices.extend([([x, y], [u0, v0]), ([x1, y], [u1, v0]), ([x1, y1], [u1, v1]), ([x, y1], [u0, v1])].iter().map(|&(p, uv)| {
+                ModelVertex { position: [p[0], p[1], 0.0], normal: [0.0, 0.0, -1.0], uv, color: layout.color }
+            }));
+            indices.extend([0, 1, 2, 0, 2, 3].iter().map(|i| base + i));
+        }
+
+        Ok(())
+    }
+
+    /// Builds a mesh of the quads of `layout`, uploading new glyphs along with it; `None` when nothing is visible.
+    pub fn build_mesh(&mut self, renderer: &mut Renderer, font: &Font, layout: &TextLayout, origin: [f32; 2]) -> Result<Option<Mesh>> {
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        self.append_quads(font, layout, origin, &mut vertices, &mut indices)?;
+        self.flush(renderer)?;
+        if indices.is_empty() { return Ok(None); }
+
+        Mesh::from_u32_indices(renderer, &vertices, &indices).map(Some)
+    }
+
+    /// Uploads the glyphs added since the last flush ahead of the next submitted frame.
+    pub fn flush(&mut self, renderer: &mut Renderer) -> Result<()> {
+        let (top, bottom) = match self.dirty_rows.take() {
+            Some(rows) => rows,
+            None => return Ok(())
+        };
+        let row_bytes = self.size as usize * 4;
+
+        renderer.upload_image(&ImageUpload {
+            image: self.texture.image(),
+            aspect_mask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+            mip_level: 0,
+            array_layer: 0,
+            x: 0,
+            y: top as _,
+            width: self.size,
+            height: bottom - top,
+            // keeps the glyphs outside the rows
+            old_layout: br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+            new_layout: br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL
+        }, &self.pixels[top as usize * row_bytes..bottom as usize * row_bytes])
+    }
+
+    /// Looks up a glyph, rasterizing it into the atlas when missing.
+    fn glyph(&mut self, font: &Font, id: u16, size: f32) -> Result<Option<AtlasGlyph>> {
+        let key = GlyphKey { font: font.id, id, size_bits: size.to_bits() };
+        if let Some(&g) = self.glyphs.get(&key) { return Ok(g); }
+
+        let outline = match font.font.outline_glyph(ab_glyph::GlyphId(id).with_scale(size)) {
+            Some(o) => o,
+            None => {
+                self.glyphs.insert(key, None);
+                return Ok(None);
+            }
+        };
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let (x, y) = self.allocate(width + GLYPH_PADDING, height + GLYPH_PADDING).ok_or(Error::Unsupported("glyph atlas is full"))?;
+        let size = self.size as usize;
+        let pixels = &mut self.pixels;
+        outline.draw(|gx, gy, coverage| {
+            let o = ((y + gy) as usize * size + (x + gx) as usize) * 4;
+            pixels[o..o + 4].iter_mut().for_each(|p| *p = (coverage.min(1.0) * 255.0).round() as u8);
+        });
+        self.dirty_rows = Some(match self.dirty_rows {
+            Some((top, bottom)) => (top.min(y), bottom.max(y + height)),
+            None => (y, y + height)
+        });
+
+        let g = AtlasGlyph { x, y, width, height, offset: [bounds.min.x, bounds.min.y] };
+        self.glyphs.insert(key, Some(g));
+
+        Ok(Some(g))
+    }
+
+    /// Finds room for `width` × `height` texels: on the first shelf tall enough with space left, or a new one.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let size = self.size;
+        if width > size { return None; }
+        if let Some(s) = self.shelves.iter_mut().find(|s| s.height >= height && s.x + width <= size) {
+            s.x += width;
+            return Some((s.x - width, s.y));
+        }
+        let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+        if y + height > size { return None; }
+        self.shelves.push(Shelf { y, height, x: width });
+
+        Some((0, y))
+    }
+}
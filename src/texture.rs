@@ -0,0 +1,506 @@
+//! Sampled images, samplers and the combined-image-sampler sets binding them.
+
+use bedrock as br;
+use crate::{vk_check, Allocation, Device, Error, ImageUpload, Ktx2Image, MemoryUsage, Renderer, Result};
+use std::rc::Rc;
+
+/// Format textures are created with: 8-bit RGBA, premultiplied alpha.
+pub const TEXTURE_FORMAT: br::vk::VkFormat = br::vk::VK_FORMAT_R8G8B8A8_UNORM;
+
+/// Multiplies the color channels of tightly packed RGBA8 pixels by their alpha, in place.
+pub fn premultiply_alpha(pixels: &mut [u8]) {
+    for p in pixels.chunks_exact_mut(4) {
+        let a = p[3] as u32;
+        for c in &mut p[..3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Decodes a PNG into tightly packed RGBA8 with premultiplied alpha, returning its width and height with the pixels.
+///
+/// Palette, grayscale and 16-bit images are converted; transparency chunks become alpha.
+#[cfg(feature = "png")]
+pub fn decode_png(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    let png_error = |e| match e {
+        png::DecodingError::IoError(e) => Error::Os("Reading PNG failed", e),
+        e => Error::Os("Decoding PNG failed", std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    };
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(png_error)?;
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).map_err(png_error)?;
+
+    let mut pixels = match info.color_type {
+        png::ColorType::RGBA => buf,
+        png::ColorType::RGB => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        png::ColorType::Indexed => return Err(Error::Unsupported("PNG palette was not expanded"))
+    };
+    premultiply_alpha(&mut pixels);
+
+    Ok((info.width, info.height, pixels))
+}
+
+/// Number of levels in a full mip chain for a `width` × `height` image, down to 1×1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 { 32 - (width.max(height) | 1).leading_zeros() }
+
+/// Bytes of a tightly packed `width` × `height` level in `format`, in whole blocks for compressed formats.
+/// `None` for formats without a single-aspect texel layout known here (combined depth/stencil, multi-planar...).
+pub(crate) fn level_size(format: br::vk::VkFormat, width: u32, height: u32) -> Option<usize> {
+    // (block width, block height, bytes per block); the ranges follow the VkFormat numbering
+    let (bw, bh, bytes) = match format {
+        br::vk::VK_FORMAT_R4G4_UNORM_PACK8 | br::vk::VK_FORMAT_S8_UINT
+            | br::vk::VK_FORMAT_R8_UNORM..=br::vk::VK_FORMAT_R8_SRGB => (1, 1, 1),
+        br::vk::VK_FORMAT_R4G4B4A4_UNORM_PACK16..=br::vk::VK_FORMAT_A1R5G5B5_UNORM_PACK16
+            | br::vk::VK_FORMAT_R8G8_UNORM..=br::vk::VK_FORMAT_R8G8_SRGB
+            | br::vk::VK_FORMAT_R16_UNORM..=br::vk::VK_FORMAT_R16_SFLOAT
+            | br::vk::VK_FORMAT_D16_UNORM => (1, 1, 2),
+        br::vk::VK_FORMAT_R8G8B8_UNORM..=br::vk::VK_FORMAT_B8G8R8_SRGB => (1, 1, 3),
+        br::vk::VK_FORMAT_R8G8B8A8_UNORM..=br::vk::VK_FORMAT_A2B10G10R10_SINT_PACK32
+            | br::vk::VK_FORMAT_R16G16_UNORM..=br::vk::VK_FORMAT_R16G16_SFLOAT
+            | br::vk::VK_FORMAT_R32_UINT..=br::vk::VK_FORMAT_R32_SFLOAT
+            | br::vk::VK_FORMAT_B10G11R11_UFLOAT_PACK32 | br::vk::VK_FORMAT_E5B9G9R9_UFLOAT_PACK32
+            | br::vk::VK_FORMAT_X8_D24_UNORM_PACK32 | br::vk::VK_FORMAT_D32_SFLOAT => (1, 1, 4),
+        br::vk::VK_FORMAT_R16G16B16_UNORM..=br::vk::VK_FORMAT_R16G16B16_SFLOAT => (1, 1, 6),
+        br::vk::VK_FORMAT_R16G16B16A16_UNORM..=br::vk::VK_FORMAT_R16G16B16A16_SFLOAT
+            | br::vk::VK_FORMAT_R32G32_UINT..=br::vk::VK_FORMAT_R32G32_SFLOAT
+            | br::vk::VK_FORMAT_R64_UINT..=br::vk::VK_FORMAT_R64_SFLOAT => (1, 1, 8),
+        br::vk::VK_FORMAT_R32G32B32_UINT..=br::vk::VK_FORMAT_R32G32B32_SFLOAT => (1, 1, 12),
+        br::vk::VK_FORMAT_R32G32B32A32_UINT..=br::vk::VK_FORMAT_R32G32B32A32_SFLOAT
+            | br::vk::VK_FORMAT_R64G64_UINT..=br::vk::VK_FORMAT_R64G64_SFLOAT => (1, 1, 16),
+        br::vk::VK_FORMAT_R64G64B64_UINT..=br::vk::VK_FORMAT_R64G64B64_SFLOAT => (1, 1, 24),
+        br::vk::VK_FORMAT_R64G64B64A64_UINT..=br::vk::VK_FORMAT_R64G64B64A64_SFLOAT => (1, 1, 32),
+        br::vk::VK_FORMAT_BC1_RGB_UNORM_BLOCK..=br::vk::VK_FORMAT_BC1_RGBA_SRGB_BLOCK
+            | br::vk::VK_FORMAT_BC4_UNORM_BLOCK | br::vk::VK_FORMAT_BC4_SNORM_BLOCK
+            | br::vk::VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK..=br::vk::VK_FORMAT_ETC2_R8G8B8A1_SRGB_BLOCK
+            | br::vk::VK_FORMAT_EAC_R11_UNORM_BLOCK | br::vk::VK_FORMAT_EAC_R11_SNORM_BLOCK => (4, 4, 8),
+        br::vk::VK_FORMAT_BC2_UNORM_BLOCK..=br::vk::VK_FORMAT_BC3_SRGB_BLOCK
+            | br::vk::VK_FORMAT_BC5_UNORM_BLOCK..=br::vk::VK_FORMAT_BC7_SRGB_BLOCK
+            | br::vk::VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK | br::vk::VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK
+            | br::vk::VK_FORMAT_EAC_R11G11_UNORM_BLOCK | br::vk::VK_FORMAT_EAC_R11G11_SNORM_BLOCK => (4, 4, 16),
+        br::vk::VK_FORMAT_ASTC_4x4_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_4x4_SRGB_BLOCK => (4, 4, 16),
+        br::vk::VK_FORMAT_ASTC_5x4_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_5x4_SRGB_BLOCK => (5, 4, 16),
+        br::vk::VK_FORMAT_ASTC_5x5_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_5x5_SRGB_BLOCK => (5, 5, 16),
+        br::vk::VK_FORMAT_ASTC_6x5_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_6x5_SRGB_BLOCK => (6, 5, 16),
+        br::vk::VK_FORMAT_ASTC_6x6_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_6x6_SRGB_BLOCK => (6, 6, 16),
+        br::vk::VK_FORMAT_ASTC_8x5_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_8x5_SRGB_BLOCK => (8, 5, 16),
+        br::vk::VK_FORMAT_ASTC_8x6_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_8x6_SRGB_BLOCK => (8, 6, 16),
+        br::vk::VK_FORMAT_ASTC_8x8_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_8x8_SRGB_BLOCK => (8, 8, 16),
+        br::vk::VK_FORMAT_ASTC_10x5_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_10x5_SRGB_BLOCK => (10, 5, 16),
+        br::vk::VK_FORMAT_ASTC_10x6_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_10x6_SRGB_BLOCK => (10, 6, 16),
+        br::vk::VK_FORMAT_ASTC_10x8_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_10x8_SRGB_BLOCK => (10, 8, 16),
+        br::vk::VK_FORMAT_ASTC_10x10_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_10x10_SRGB_BLOCK => (10, 10, 16),
+        br::vk::VK_FORMAT_ASTC_12x10_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_12x10_SRGB_BLOCK => (12, 10, 16),
+        br::vk::VK_FORMAT_ASTC_12x12_UNORM_BLOCK | br::vk::VK_FORMAT_ASTC_12x12_SRGB_BLOCK => (12, 12, 16),
+        _ => return None
+    };
+
+    Some(width.div_ceil(bw) as usize * height.div_ceil(bh) as usize * bytes)
+}
+
+/// Halves tightly packed RGBA8 pixels with a box filter, returning the next mip level with its width and height.
+///
+/// Odd extents round down, dropping the last row or column; extents of 1 stay 1.
+pub fn downsample_rgba8(width: u32, height: u32, pixels: &[u8]) -> (u32, u32, Vec<u8>) {
+    let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+    let texel = |x: u32, y: u32| {
+        let o = (y.min(height - 1) as usize * width as usize + x.min(width - 1) as usize) * 4;
+        &pixels[o..o + 4]
+    };
+    let mut out = Vec::with_capacity(w as usize * h as usize * 4);
+    for y in 0..h {
+        for x in 0..w {
+            let quad = [texel(2 * x, 2 * y), texel(2 * x + 1, 2 * y), texel(2 * x, 2 * y + 1), texel(2 * x + 1, 2 * y + 1)];
+            for c in 0..4 {
+                out.push(((quad.iter().map(|p| p[c] as u32).sum::<u32>() + 2) / 4) as u8);
+            }
+        }
+    }
+
+    (w, h, out)
+}
+
+/// A 2D image sampled in fragment shaders, in `VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL`. Textures made from pixels are
+/// in [`TEXTURE_FORMAT`]; KTX2 files bring their own format.
+///
+/// Pixels are uploaded through the renderer's staging ring ahead of the next submitted frame, so they have to fit
+/// into `RendererOptions::upload_ring_size` at once. Keep the texture alive until the frames sampling it have finished.
+pub struct Texture {
+    device: Rc<Device>,
+    image: br::vk::VkImage,
+    view: br::vk::VkImageView,
+    memory: Allocation,
+    format: br::vk::VkFormat,
+    width: u32,
+    height: u32,
+    mip_levels: u32
+}
+impl Texture {
+    /// Creates a texture from tightly packed RGBA8 pixels with premultiplied alpha.
+    pub fn new(renderer: &mut Renderer, width: u32, height: u32, pixels: &[u8]) -> Result<Self> {
+        check_rgba8_extent(width, height, pixels)?;
+        let this = Texture::create(renderer, TEXTURE_FORMAT, width, height, 1)?;
+        this.upload_level(renderer, 0, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL, pixels)?;
+
+        Ok(this)
+    }
+
+    /// Creates a texture with a full mip chain from tightly packed RGBA8 pixels with premultiplied alpha.
+    ///
+    /// The levels are blitted on the device, or downsampled on the host where the format does not support linear
+    /// blits; all of them then have to fit into the upload ring at once.
+    pub fn with_mipmaps(renderer: &mut Renderer, width: u32, height: u32, pixels: &[u8]) -> Result<Self> {
+        check_rgba8_extent(width, height, pixels)?;
+
+        Texture::generate_levels(renderer, TEXTURE_FORMAT, width, height, pixels)
+    }
+
+    /// Creates a texture from pre-built mip levels in `format`, level 0 first, each tightly packed
+    /// (in whole blocks for compressed formats).
+    pub fn with_levels(renderer: &mut Renderer, format: br::vk::VkFormat, width: u32, height: u32, levels: &[&[u8]]) -> Result<Self> {
+        if levels.is_empty() || levels.len() as u32 > mip_level_count(width, height) {
+            return Err(Error::Unsupported("texture mip levels do not match its extent"));
+        }
+        for (level, data) in levels.iter().enumerate() {
+            check_level_size(format, (width >> level).max(1), (height >> level).max(1), data)?;
+        }
+        let this = Texture::create(renderer, format, width, height, levels.len() as _)?;
+        for (level, data) in levels.iter().enumerate() {
+            this.upload_level(renderer, level as _, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL, data)?;
+        }
+
+        Ok(this)
+    }
+
+    /// Creates a texture from PNG file contents, premultiplying their alpha and optionally generating mipmaps.
+    #[cfg(feature = "png")]
+    pub fn from_png(renderer: &mut Renderer, bytes: &[u8], mipmaps: bool) -> Result<Self> {
+        let (width, height, pixels) = decode_png(bytes)?;
+
+        if mipmaps { Texture::with_mipmaps(renderer, width, height, &pixels) } else { Texture::new(renderer, width, height, &pixels) }
+    }
+
+    /// Creates a texture from a PNG file, premultiplying its alpha and optionally generating mipmaps.
+    #[cfg(feature = "png")]
+    pub fn load_png(renderer: &mut Renderer, path: &std::path::Path, mipmaps: bool) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::Os("Reading PNG failed", e))?;
+
+        Texture::from_png(renderer, &bytes, mipmaps)
+    }
+
+    /// Creates a texture from KTX2 file contents with the levels they carry, or a generated mip chain when the file
+    /// asks for one. 8-bit RGBA levels without premultiplied alpha are premultiplied; other formats are used as stored.
+    pub fn from_ktx2(renderer: &mut Renderer, bytes: &[u8]) -> Result<Self> {
+        let premultiplied_levels: Vec<Vec<u8>>;
+        let mut ktx = Ktx2Image::parse(bytes)?;
+        if !ktx.premultiplied && is_rgba8(ktx.format) {
+            premultiplied_levels = ktx.levels.iter().map(|l| {
+                let mut l = l.to_vec();
+                premultiply_alpha(&mut l);
+                l
+            }).collect();
+            ktx.levels = premultiplied_levels.iter().map(|l| &l[..]).collect();
+        }
+
+        if ktx.generate_mipmaps {
+            Texture::generate_levels(renderer, ktx.format, ktx.width, ktx.height, ktx.levels[0])
+        } else {
+            Texture::with_levels(renderer, ktx.format, ktx.width, ktx.height, &ktx.levels)
+        }
+    }
+
+    /// Creates a texture from a KTX2 file, as [`Texture::from_ktx2`] does.
+    pub fn load_ktx2(renderer: &mut Renderer, path: &std::path::Path) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::Os("Reading KTX2 failed", e))?;
+
+        Texture::from_ktx2(renderer, &bytes)
+    }
+
+    fn create(renderer: &Renderer, format: br::vk::VkFormat, width: u32, height: u32, mip_levels: u32) -> Result<Self> {
+        let max_extent = renderer.device().properties().limits.maxImageDimension2D;
+        if width == 0 || height == 0 || width > max_extent || height > max_extent {
+            return Err(Error::Unsupported("texture extent out of range"));
+        }
+        let features = renderer.device().format_properties(format).optimalTilingFeatures;
+        if (features & br::vk::VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT) == 0 {
+            return Err(Error::Unsupported("texture format cannot be sampled"));
+        }
+
+        let mut this = Texture {
+            device: renderer.device().clone(),
+            image: br::vk::VK_NULL_HANDLE as _,
+            view: br::vk::VK_NULL_HANDLE as _,
+            memory: Allocation::default(),
+            format,
+            width,
+            height,
+            mip_levels
+        };
+        let vk_device = this.device.native_ptr();
+        let image_cinfo = br::vk::VkImageCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            imageType: br::vk::VK_IMAGE_TYPE_2D,
+            format,
+            extent: br::vk::VkExtent3D { width, height, depth: 1 },
+            mipLevels: mip_levels,
+            arrayLayers: 1,
+            samples: br::vk::VK_SAMPLE_COUNT_1_BIT,
+            tiling: br::vk::VK_IMAGE_TILING_OPTIMAL,
+            // generated levels are blitted from the one above
+            usage: br::vk::VK_IMAGE_USAGE_SAMPLED_BIT | br::vk::VK_IMAGE_USAGE_TRANSFER_DST_BIT | br::vk::VK_IMAGE_USAGE_TRANSFER_SRC_BIT,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null(),
+            initialLayout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED
+        };
+        let r = unsafe { br::vk::vkCreateImage(vk_device, &image_cinfo, std::ptr::null(), &mut this.image) };
+        vk_check(r, "vkCreateImage for Texture failed")?;
+        this.memory = unsafe { this.device.allocator().allocate_image(this.image, MemoryUsage::DEVICE_LOCAL, false)? };
+
+        let iv_cinfo = br::vk::VkImageViewCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            image: this.image,
+            viewType: br::vk::VK_IMAGE_VIEW_TYPE_2D,
+            format,
+            components: br::vk::VkComponentMapping {
+                r: br::vk::VK_COMPONENT_SWIZZLE_R,
+                g: br::vk::VK_COMPONENT_SWIZZLE_G,
+                b: br::vk::VK_COMPONENT_SWIZZLE_B,
+                a: br::vk::VK_COMPONENT_SWIZZLE_A
+            },
+            subresourceRange: br::vk::VkImageSubresourceRange {
+                aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+                baseMipLevel: 0,
+                levelCount: mip_levels,
+                baseArrayLayer: 0,
+                layerCount: 1
+            }
+        };
+        let r = unsafe { br::vk::vkCreateImageView(vk_device, &iv_cinfo, std::ptr::null(), &mut this.view) };
+        vk_check(r, "vkCreateImageView for Texture failed")?;
+
+        Ok(this)
+    }
+
+    /// Creates a texture with a full mip chain, uploading level 0 from `pixels` and generating the rest.
+    fn generate_levels(renderer: &mut Renderer, format: br::vk::VkFormat, width: u32, height: u32, pixels: &[u8]) -> Result<Self> {
+        let levels = mip_level_count(width, height);
+        let blit_features = br::vk::VK_FORMAT_FEATURE_BLIT_SRC_BIT | br::vk::VK_FORMAT_FEATURE_BLIT_DST_BIT |
+            br::vk::VK_FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT;
+        let blittable = (renderer.device().format_properties(format).optimalTilingFeatures & blit_features) == blit_features;
+        if levels > 1 && !blittable && !is_rgba8(format) {
+            return Err(Error::Unsupported("mipmaps cannot be generated for the texture format"));
+        }
+        check_level_size(format, width, height, pixels)?;
+        let this = Texture::create(renderer, format, width, height, levels)?;
+
+        if levels == 1 {
+            this.upload_level(renderer, 0, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL, pixels)?;
+        } else if blittable {
+            this.upload_level(renderer, 0, br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, pixels)?;
+            renderer.generate_mipmaps(this.image, width, height, levels);
+        } else {
+            this.upload_level(renderer, 0, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL, pixels)?;
+            let (mut w, mut h, mut level_pixels) = downsample_rgba8(width, height, pixels);
+            for level in 1..levels {
+                this.upload_level(renderer, level, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL, &level_pixels)?;
+                if level + 1 < levels { (w, h, level_pixels) = downsample_rgba8(w, h, &level_pixels); }
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn upload_level(&self, renderer: &mut Renderer, level: u32, new_layout: br::vk::VkImageLayout, data: &[u8]) -> Result<()> {
+        renderer.upload_image(&ImageUpload {
+            image: self.image,
+            aspect_mask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+            mip_level: level,
+            array_layer: 0,
+            x: 0,
+            y: 0,
+            width: (self.width >> level).max(1),
+            height: (self.height >> level).max(1),
+            old_layout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED,
+            new_layout
+        }, data)
+    }
+
+    pub fn image(&self) -> br::vk::VkImage { self.image }
+    pub fn view(&self) -> br::vk::VkImageView { self.view }
+    pub fn format(&self) -> br::vk::VkFormat { self.format }
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn mip_levels(&self) -> u32 { self.mip_levels }
+}
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            br::vk::vkDestroyImageView(self.device.native_ptr(), self.view, std::ptr::null());
+            br::vk::vkDestroyImage(self.device.native_ptr(), self.image, std::ptr::null());
+        }
+        self.device.allocator().free(std::mem::take(&mut self.memory));
+    }
+}
+
+fn check_level_size(format: br::vk::VkFormat, width: u32, height: u32, data: &[u8]) -> Result<()> {
+    match level_size(format, width, height) {
+        Some(size) if size == data.len() => Ok(()),
+        Some(_) => Err(Error::Unsupported("texture level data does not match its extent")),
+        None => Err(Error::Unsupported("texture format has no known texel size"))
+    }
+}
+
+fn check_rgba8_extent(width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    if pixels.len() != width as usize * height as usize * 4 {
+        return Err(Error::Unsupported("texture pixels do not match its extent"));
+    }
+
+    Ok(())
+}
+fn is_rgba8(format: br::vk::VkFormat) -> bool {
+    format == br::vk::VK_FORMAT_R8G8B8A8_UNORM || format == br::vk::VK_FORMAT_R8G8B8A8_SRGB
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerOptions {
+    pub mag_filter: br::vk::VkFilter,
+    pub min_filter: br::vk::VkFilter,
+    pub mipmap_mode: br::vk::VkSamplerMipmapMode,
+    /// Applied to both U and V.
+    pub address_mode: br::vk::VkSamplerAddressMode
+}
+impl Default for SamplerOptions {
+    /// Linear filtering, clamped to the edges.
+    fn default() -> Self {
+        SamplerOptions {
+            mag_filter: br::vk::VK_FILTER_LINEAR,
+            min_filter: br::vk::VK_FILTER_LINEAR,
+            mipmap_mode: br::vk::VK_SAMPLER_MIPMAP_MODE_LINEAR,
+            address_mode: br::vk::VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE
+        }
+    }
+}
+
+pub struct Sampler {
+    device: Rc<Device>,
+    handle: br::vk::VkSampler
+}
+impl Sampler {
+    pub fn new(renderer: &Renderer, options: &SamplerOptions) -> Result<Self> {
+        let mut this = Sampler { device: renderer.device().clone(), handle: br::vk::VK_NULL_HANDLE as _ };
+        let sampler_cinfo = br::vk::VkSamplerCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_SAMPLER_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            magFilter: options.mag_filter,
+            minFilter: options.min_filter,
+            mipmapMode: options.mipmap_mode,
+            addressModeU: options.address_mode,
+            addressModeV: options.address_mode,
+            addressModeW: options.address_mode,
+            mipLodBias: 0.0,
+            anisotropyEnable: false as _,
+            maxAnisotropy: 1.0,
+            compareEnable: false as _,
+            compareOp: br::vk::VK_COMPARE_OP_ALWAYS,
+            minLod: 0.0,
+            maxLod: br::vk::VK_LOD_CLAMP_NONE,
+            borderColor: br::vk::VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK,
+            unnormalizedCoordinates: false as _
+        };
+        let r = unsafe { br::vk::vkCreateSampler(this.device.native_ptr(), &sampler_cinfo, std::ptr::null(), &mut this.handle) };
+        vk_check(r, "vkCreateSampler failed")?;
+
+        Ok(this)
+    }
+
+    pub fn native_ptr(&self) -> br::vk::VkSampler { self.handle }
+}
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { br::vk::vkDestroySampler(self.device.native_ptr(), self.handle, std::ptr::null()) };
+    }
+}
+
+/// Descriptor set binding a texture with a sampler, for set 1 of [`Renderer::textured_pipeline`]
+/// (or any layout using [`Renderer::texture_set_layout`]).
+///
+/// The texture and sampler are referenced, not owned: keep them alive as long as the set is drawn with.
+pub struct TextureSet {
+    device: Rc<Device>,
+    pool: br::vk::VkDescriptorPool,
+    set: br::vk::VkDescriptorSet
+}
+impl TextureSet {
+    pub fn new(renderer: &Renderer, texture: &Texture, sampler: &Sampler) -> Result<Self> {
+        TextureSet::for_view(renderer, texture.view(), br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL, sampler)
+    }
+
+    /// Binds an image view not owned by a [`Texture`], such as a shared image, sampled in `layout`.
+    pub fn for_view(
+        renderer: &Renderer, view: br::vk::VkImageView, layout: br::vk::VkImageLayout, sampler: &Sampler
+    ) -> Result<Self> {
+        let mut this = TextureSet {
+            device: renderer.device().clone(),
+            pool: br::vk::VK_NULL_HANDLE as _,
+            set: br::vk::VK_NULL_HANDLE as _
+        };
+        let vk_device = this.device.native_ptr();
+        let dsp_size = &[br::vk::VkDescriptorPoolSize { _type: br::vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER, descriptorCount: 1 }];
+        let dsp_cinfo = br::vk::VkDescriptorPoolCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            poolSizeCount: 1,
+            pPoolSizes: dsp_size.as_ptr(),
+            maxSets: 1
+        };
+        let r = unsafe { br::vk::vkCreateDescriptorPool(vk_device, &dsp_cinfo, std::ptr::null(), &mut this.pool) };
+        vk_check(r, "vkCreateDescriptorPool for Textures failed")?;
+        let set_layout = renderer.texture_set_layout();
+        let dsp_ainfo = br::vk::VkDescriptorSetAllocateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_ALLOCATE_INFO,
+            pNext: std::ptr::null(),
+            descriptorPool: this.pool,
+            descriptorSetCount: 1,
+            pSetLayouts: &set_layout
+        };
+        let r = unsafe { br::vk::vkAllocateDescriptorSets(vk_device, &dsp_ainfo, &mut this.set) };
+        vk_check(r, "vkAllocateDescriptorSets for Textures failed")?;
+        let image_info = br::vk::VkDescriptorImageInfo {
+            sampler: sampler.native_ptr(),
+            imageView: view,
+            imageLayout: layout
+        };
+        let descriptor_writes = &[br::vk::VkWriteDescriptorSet {
+            sType: br::vk::VK_STRUCTURE_TYPE_WRITE_DESCRIPTOR_SET,
+            pNext: std::ptr::null(),
+            dstSet: this.set,
+            dstBinding: 0,
+            dstArrayElement: 0,
+            descriptorType: br::vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+            descriptorCount: 1,
+            pImageInfo: &image_info,
+            .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+        }];
+        unsafe { br::vk::vkUpdateDescriptorSets(vk_device, descriptor_writes.len() as _, descriptor_writes.as_ptr(), 0, std::ptr::null()) };
+
+        Ok(this)
+    }
+
+    pub fn native_ptr(&self) -> br::vk::VkDescriptorSet { self.set }
+}
+impl Drop for TextureSet {
+    fn drop(&mut self) {
+        // destroying the pool frees the set
+        unsafe { br::vk::vkDestroyDescriptorPool(self.device.native_ptr(), self.pool, std::ptr::null()) };
+    }
+}
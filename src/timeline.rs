@@ -0,0 +1,265 @@
+use bedrock as br;
+use crate::{vk_check, Device, Error, Renderer, Result};
+use std::rc::Rc;
+
+/// Point on a timeline semaphore, reached once the semaphore's counter is at least `value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelinePoint {
+    pub semaphore: br::vk::VkSemaphore,
+    pub value: u64
+}
+
+/// Timeline semaphore, optionally shared with another API or process.
+pub struct TimelineSemaphore {
+    device: Rc<Device>,
+    handle: br::vk::VkSemaphore
+}
+impl TimelineSemaphore {
+    /// Device extensions the renderer must be created with to use timeline semaphores at all.
+    pub const DEVICE_EXTENSIONS: &'static [&'static str] = &["VK_KHR_timeline_semaphore"];
+
+    /// Creates a semaphore used only within the renderer's device.
+    pub fn new(renderer: &Renderer) -> Result<Self> {
+        TimelineSemaphore::create(renderer.device().clone(), 0)
+    }
+
+    /// Creates a semaphore at value 0 that can be exported through `export_handle_types` (0 for none).
+    ///
+    /// Fails unless the device was created with [`TimelineSemaphore::DEVICE_EXTENSIONS`].
+    pub(crate) fn create(device: Rc<Device>, export_handle_types: br::vk::VkExternalSemaphoreHandleTypeFlags) -> Result<Self> {
+        if !device.timeline_semaphores() { return Err(Error::Unsupported("timeline semaphores not enabled")); }
+        if export_handle_types != 0 {
+            check_external_support(&device, export_handle_types, br::vk::VK_EXTERNAL_SEMAPHORE_FEATURE_EXPORTABLE_BIT)?;
+        }
+
+        let export_info = br::vk::VkExportSemaphoreCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_EXPORT_SEMAPHORE_CREATE_INFO,
+            pNext: std::ptr::null(),
+            handleTypes: export_handle_types
+        };
+        let type_info = br::vk::VkSemaphoreTypeCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_TYPE_CREATE_INFO,
+            pNext: if export_handle_types != 0 { &export_info as *const _ as _ } else { std::ptr::null() },
+            semaphoreType: br::vk::VK_SEMAPHORE_TYPE_TIMELINE,
+            initialValue: 0
+        };
+        let semaphore_cinfo = br::vk::VkSemaphoreCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_CREATE_INFO,
+            pNext: &type_info as *const _ as _,
+            flags: 0
+        };
+        let mut handle = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkCreateSemaphore(device.native_ptr(), &semaphore_cinfo, std::ptr::null(), &mut handle) };
+        vk_check(r, "vkCreateSemaphore failed")?;
+
+        Ok(TimelineSemaphore { device, handle })
+    }
+
+    pub fn native_ptr(&self) -> br::vk::VkSemaphore { self.handle }
+    pub fn point(&self, value: u64) -> TimelinePoint { TimelinePoint { semaphore: self.handle, value } }
+
+    /// Current counter value.
+    pub fn value(&self) -> Result<u64> {
+        let vk_device = self.device.native_ptr();
+        let vk_get_semaphore_counter_value_khr: br::vk::PFN_vkGetSemaphoreCounterValue = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetDeviceProcAddr(vk_device, b"vkGetSemaphoreCounterValueKHR\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkGetSemaphoreCounterValueKHR not found?"))?
+            )
+        };
+        let mut value = 0;
+        let r = (vk_get_semaphore_counter_value_khr)(vk_device, self.handle, &mut value);
+        vk_check(r, "vkGetSemaphoreCounterValue failed")?;
+
+        Ok(value)
+    }
+
+    /// Blocks until the counter reaches `value` or `timeout` nanoseconds have passed; returns whether it was reached.
+    pub fn wait(&self, value: u64, timeout: u64) -> Result<bool> {
+        let vk_device = self.device.native_ptr();
+        let vk_wait_semaphores_khr: br::vk::PFN_vkWaitSemaphores = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetDeviceProcAddr(vk_device, b"vkWaitSemaphoresKHR\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkWaitSemaphoresKHR not found?"))?
+            )
+        };
+        let wait_info = br::vk::VkSemaphoreWaitInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_WAIT_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            semaphoreCount: 1,
+            pSemaphores: &self.handle,
+            pValues: &value
+        };
+        let r = (vk_wait_semaphores_khr)(vk_device, &wait_info, timeout);
+        if r == br::vk::VK_TIMEOUT { return Ok(false); }
+        vk_check(r, "vkWaitSemaphores failed").map(|_| true)
+    }
+
+    /// Creates a semaphore and imports `import_payload`'s handle into it with `f`.
+    #[cfg(any(all(unix, feature = "external-fd"), all(windows, feature = "dxgi")))]
+    fn imported(
+        device: Rc<Device>, handle_type: br::vk::VkExternalSemaphoreHandleTypeFlags,
+        import_payload: impl FnOnce(br::vk::VkDevice, br::vk::VkSemaphore) -> Result<()>
+    ) -> Result<Self> {
+        check_external_support(&device, handle_type, br::vk::VK_EXTERNAL_SEMAPHORE_FEATURE_IMPORTABLE_BIT)?;
+        let this = TimelineSemaphore::create(device, 0)?;
+        import_payload(this.device.native_ptr(), this.handle)?;
+
+        Ok(this)
+    }
+}
+#[cfg(all(unix, feature = "external-fd"))]
+impl TimelineSemaphore {
+    /// Device extensions the renderer must be created with to share timeline semaphores as opaque fds.
+    pub const FD_DEVICE_EXTENSIONS: &'static [&'static str] = &["VK_KHR_timeline_semaphore", "VK_KHR_external_semaphore_fd"];
+
+    /// Creates a semaphore that can be handed out with [`TimelineSemaphore::export_fd`].
+    ///
+    /// Only opaque fds are used: sync fds carry a single signal operation, so they cannot stand for a timeline.
+    pub fn new_exportable(renderer: &Renderer) -> Result<Self> {
+        TimelineSemaphore::create(renderer.device().clone(), br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT)
+    }
+
+    /// Imports a semaphore exported by [`TimelineSemaphore::export_fd`], in this or another process.
+    pub fn import_fd(renderer: &Renderer, fd: std::os::unix::io::OwnedFd) -> Result<Self> {
+        TimelineSemaphore::imported_fd(renderer.device().clone(), fd)
+    }
+
+    pub(crate) fn imported_fd(device: Rc<Device>, fd: std::os::unix::io::OwnedFd) -> Result<Self> {
+        use std::os::unix::io::{AsRawFd, IntoRawFd};
+
+        TimelineSemaphore::imported(device, br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT, |vk_device, semaphore| {
+            let vk_import_semaphore_fd_khr: br::vk::PFN_vkImportSemaphoreFdKHR = unsafe {
+                std::mem::transmute(
+                    br::vk::vkGetDeviceProcAddr(vk_device, b"vkImportSemaphoreFdKHR\0".as_ptr() as _)
+                        .ok_or(Error::Unsupported("vkImportSemaphoreFdKHR not found?"))?
+                )
+            };
+            let import_info = br::vk::VkImportSemaphoreFdInfoKHR {
+                sType: br::vk::VK_STRUCTURE_TYPE_IMPORT_SEMAPHORE_FD_INFO_KHR,
+                pNext: std::ptr::null(),
+                semaphore,
+                flags: 0,
+                handleType: br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT,
+                fd: fd.as_raw_fd()
+            };
+            let r = (vk_import_semaphore_fd_khr)(vk_device, &import_info);
+            vk_check(r, "vkImportSemaphoreFdKHR failed")?;
+            // a successful import takes ownership of the fd
+            let _ = fd.into_raw_fd();
+
+            Ok(())
+        })
+    }
+
+    /// Returns a new fd referring to the semaphore.
+    pub fn export_fd(&self) -> Result<std::os::unix::io::OwnedFd> {
+        use std::os::unix::io::FromRawFd;
+
+        let vk_device = self.device.native_ptr();
+        let vk_get_semaphore_fd_khr: br::vk::PFN_vkGetSemaphoreFdKHR = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetDeviceProcAddr(vk_device, b"vkGetSemaphoreFdKHR\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkGetSemaphoreFdKHR not found?"))?
+            )
+        };
+        let get_info = br::vk::VkSemaphoreGetFdInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_GET_FD_INFO_KHR,
+            pNext: std::ptr::null(),
+            semaphore: self.handle,
+            handleType: br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT
+        };
+        let mut fd = -1;
+        let r = (vk_get_semaphore_fd_khr)(vk_device, &get_info, &mut fd);
+        vk_check(r, "vkGetSemaphoreFdKHR failed")?;
+
+        Ok(unsafe { std::os::unix::io::OwnedFd::from_raw_fd(fd) })
+    }
+}
+#[cfg(all(windows, feature = "dxgi"))]
+impl TimelineSemaphore {
+    /// Imports a shared `ID3D12Fence` handle. The handle stays owned by the caller.
+    pub(crate) fn import_d3d12_fence(device: Rc<Device>, handle: winapi::shared::ntdef::HANDLE) -> Result<Self> {
+        TimelineSemaphore::imported(device, br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_D3D12_FENCE_BIT, |vk_device, semaphore| {
+            let vk_import_semaphore_win32_handle_khr: br::vk::PFN_vkImportSemaphoreWin32HandleKHR = unsafe {
+                std::mem::transmute(
+                    br::vk::vkGetDeviceProcAddr(vk_device, b"vkImportSemaphoreWin32HandleKHR\0".as_ptr() as _)
+                        .ok_or(Error::Unsupported("vkImportSemaphoreWin32HandleKHR not found?"))?
+                )
+            };
+            let import_info = br::vk::VkImportSemaphoreWin32HandleInfoKHR {
+                sType: br::vk::VK_STRUCTURE_TYPE_IMPORT_SEMAPHORE_WIN32_HANDLE_INFO_KHR,
+                pNext: std::ptr::null(),
+                semaphore,
+                flags: 0,
+                handleType: br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_D3D12_FENCE_BIT,
+                handle: handle as _,
+                name: std::ptr::null()
+            };
+            let r = (vk_import_semaphore_win32_handle_khr)(vk_device, &import_info);
+            vk_check(r, "vkImportSemaphoreWin32HandleKHR failed")
+        })
+    }
+
+    /// Returns a new NT handle that D3D12 opens as an `ID3D12Fence`. The caller has to close it.
+    pub(crate) fn export_d3d12_fence(&self) -> Result<winapi::shared::ntdef::HANDLE> {
+        let vk_device = self.device.native_ptr();
+        let vk_get_semaphore_win32_handle_khr: br::vk::PFN_vkGetSemaphoreWin32HandleKHR = unsafe {
+            std::mem::transmute(
+                br::vk::vkGetDeviceProcAddr(vk_device, b"vkGetSemaphoreWin32HandleKHR\0".as_ptr() as _)
+                    .ok_or(Error::Unsupported("vkGetSemaphoreWin32HandleKHR not found?"))?
+            )
+        };
+        let get_info = br::vk::VkSemaphoreGetWin32HandleInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_GET_WIN32_HANDLE_INFO_KHR,
+            pNext: std::ptr::null(),
+            semaphore: self.handle,
+            handleType: br::vk::VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_D3D12_FENCE_BIT
+        };
+        let mut handle = std::ptr::null_mut();
+        let r = (vk_get_semaphore_win32_handle_khr)(vk_device, &get_info, &mut handle);
+        vk_check(r, "vkGetSemaphoreWin32HandleKHR failed")?;
+
+        Ok(handle as _)
+    }
+}
+impl Drop for TimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe { br::vk::vkDestroySemaphore(self.device.native_ptr(), self.handle, std::ptr::null()) };
+    }
+}
+
+/// Fails unless timeline semaphores can be exported/imported (`feature`) through `handle_type`.
+fn check_external_support(
+    device: &Device, handle_type: br::vk::VkExternalSemaphoreHandleTypeFlags, feature: br::vk::VkExternalSemaphoreFeatureFlags
+) -> Result<()> {
+    let vk_get_physical_device_external_semaphore_properties: br::vk::PFN_vkGetPhysicalDeviceExternalSemaphoreProperties = unsafe {
+        std::mem::transmute(
+            br::vk::vkGetInstanceProcAddr(device.instance(), b"vkGetPhysicalDeviceExternalSemaphoreProperties\0".as_ptr() as _)
+                .ok_or(Error::Unsupported("vkGetPhysicalDeviceExternalSemaphoreProperties not found?"))?
+        )
+    };
+    let type_info = br::vk::VkSemaphoreTypeCreateInfo {
+        sType: br::vk::VK_STRUCTURE_TYPE_SEMAPHORE_TYPE_CREATE_INFO,
+        pNext: std::ptr::null(),
+        semaphoreType: br::vk::VK_SEMAPHORE_TYPE_TIMELINE,
+        initialValue: 0
+    };
+    let external_info = br::vk::VkPhysicalDeviceExternalSemaphoreInfo {
+        sType: br::vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_EXTERNAL_SEMAPHORE_INFO,
+        pNext: &type_info as *const _ as _,
+        handleType: handle_type
+    };
+    let mut props = br::vk::VkExternalSemaphoreProperties {
+        sType: br::vk::VK_STRUCTURE_TYPE_EXTERNAL_SEMAPHORE_PROPERTIES,
+        pNext: std::ptr::null_mut(),
+        .. unsafe { std::mem::MaybeUninit::zeroed().assume_init() }
+    };
+    (vk_get_physical_device_external_semaphore_properties)(device.adapter(), &external_info, &mut props);
+    if (props.externalSemaphoreFeatures & feature) == 0 {
+        return Err(Error::Unsupported("timeline semaphores cannot be exported/imported through the handle type"));
+    }
+
+    Ok(())
+}
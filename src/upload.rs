@@ -0,0 +1,411 @@
+//! Staging ring for uploads into device-local buffers and images.
+
+use bedrock as br;
+use crate::{vk_check, Allocation, Device, Error, MemoryUsage, Result};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Staging offsets are aligned to this, which suits every texel size.
+const COPY_ALIGNMENT: u64 = 16;
+
+/// Destination of an image upload: a rectangle of one mip level and array layer, with tightly packed rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageUpload {
+    pub image: br::vk::VkImage,
+    pub aspect_mask: br::vk::VkImageAspectFlags,
+    pub mip_level: u32,
+    pub array_layer: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Layout before the upload; `VK_IMAGE_LAYOUT_UNDEFINED` discards the contents of the whole subresource.
+    pub old_layout: br::vk::VkImageLayout,
+    /// Layout the image is left in, for reading in vertex and fragment shaders.
+    pub new_layout: br::vk::VkImageLayout
+}
+
+/// Layout change of one image subresource around a batch of uploads into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutTransition {
+    pub image: br::vk::VkImage,
+    pub aspect_mask: br::vk::VkImageAspectFlags,
+    pub mip_level: u32,
+    pub array_layer: u32,
+    pub old_layout: br::vk::VkImageLayout,
+    pub new_layout: br::vk::VkImageLayout
+}
+
+/// One transition per subresource written by `uploads`, in order of first appearance: out of the first upload's
+/// `old_layout` before all the copies, and into the last one's `new_layout` after them.
+///
+/// How the ring batches its barriers; reachable from outside only for tests, not part of the public API.
+pub fn layout_transitions(uploads: &[ImageUpload]) -> Vec<LayoutTransition> {
+    let mut transitions = Vec::<LayoutTransition>::new();
+    for u in uploads {
+        let same = transitions.iter_mut().find(|t| {
+            t.image == u.image && t.aspect_mask == u.aspect_mask && t.mip_level == u.mip_level && t.array_layer == u.array_layer
+        });
+        match same {
+            Some(t) => t.new_layout = u.new_layout,
+            None => transitions.push(LayoutTransition {
+                image: u.image,
+                aspect_mask: u.aspect_mask,
+                mip_level: u.mip_level,
+                array_layer: u.array_layer,
+                old_layout: u.old_layout,
+                new_layout: u.new_layout
+            })
+        }
+    }
+
+    transitions
+}
+
+enum Copy {
+    Buffer { dst: br::vk::VkBuffer, src_offset: u64, dst_offset: u64, size: u64 },
+    Image { dst: ImageUpload, src_offset: u64 }
+}
+impl Copy {
+    /// Whether both copies write some of the same bytes.
+    fn overlaps(&self, other: &Copy) -> bool {
+        match (self, other) {
+            (Copy::Buffer { dst: a, dst_offset: ao, size: asz, .. }, Copy::Buffer { dst: b, dst_offset: bo, size: bsz, .. }) =>
+                a == b && *ao < bo + bsz && *bo < ao + asz,
+            (Copy::Image { dst: a, .. }, Copy::Image { dst: b, .. }) =>
+                a.image == b.image && (a.aspect_mask & b.aspect_mask) != 0 && a.mip_level == b.mip_level &&
+                a.array_layer == b.array_layer &&
+                a.x < b.x + b.width as i32 && b.x < a.x + a.width as i32 &&
+                a.y < b.y + b.height as i32 && b.y < a.y + a.height as i32,
+            _ => false
+        }
+    }
+}
+
+/// Color image whose levels from 1 on are generated from level 0 with linear blits, after the copies.
+struct MipChain {
+    image: br::vk::VkImage,
+    width: u32,
+    height: u32,
+    levels: u32,
+    new_layout: br::vk::VkImageLayout
+}
+
+/// Staging space handed out by an [`UploadRing`] and released with the frame slot it was submitted with.
+struct Region {
+    frame: usize,
+    end: u64
+}
+
+/// Host visible buffer used as a ring: each frame's uploads are written behind the previous frame's, and their
+/// space is reused once the frame has finished.
+pub(crate) struct UploadRing {
+    device: Rc<Device>,
+    buffer: br::vk::VkBuffer,
+    memory: Allocation,
+    capacity: u64,
+    head: u64,
+    tail: u64,
+    /// Bytes handed out since the last `close_frame`, including padding.
+    open_bytes: u64,
+    regions: VecDeque<Region>,
+    copies: Vec<Copy>,
+    mip_chains: Vec<MipChain>
+}
+impl UploadRing {
+    pub fn new(device: Rc<Device>, capacity: u64) -> Result<Self> {
+        let mut this = UploadRing {
+            device,
+            buffer: br::vk::VK_NULL_HANDLE as _,
+            memory: Allocation::default(),
+            capacity,
+            head: 0,
+            tail: 0,
+            open_bytes: 0,
+            regions: VecDeque::new(),
+            copies: Vec::new(),
+            mip_chains: Vec::new()
+        };
+        let buffer_cinfo = br::vk::VkBufferCreateInfo {
+            sType: br::vk::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO,
+            pNext: std::ptr::null(),
+            flags: 0,
+            size: capacity,
+            usage: br::vk::VK_BUFFER_USAGE_TRANSFER_SRC_BIT,
+            sharingMode: br::vk::VK_SHARING_MODE_EXCLUSIVE,
+            queueFamilyIndexCount: 0,
+            pQueueFamilyIndices: std::ptr::null()
+        };
+        let r = unsafe { br::vk::vkCreateBuffer(this.device.native_ptr(), &buffer_cinfo, std::ptr::null(), &mut this.buffer) };
+        vk_check(r, "vkCreateBuffer for Staging failed")?;
+        this.memory = unsafe { this.device.allocator().allocate_buffer(this.buffer, MemoryUsage::UPLOAD)? };
+
+        Ok(this)
+    }
+
+    /// Whether copies or mip chains have been queued since the last `record`.
+    pub fn has_pending(&self) -> bool { !self.copies.is_empty() || !self.mip_chains.is_empty() }
+
+    /// Hands out `size` bytes of staging space, or `None` while the ring is too full.
+    fn alloc(&mut self, size: u64) -> Option<u64> {
+        if size > self.capacity { return None; }
+        let live = self.open_bytes > 0 || !self.regions.is_empty();
+        if !live {
+            self.head = 0;
+            self.tail = 0;
+        }
+        let start = align_up(self.head, COPY_ALIGNMENT);
+        let offset = if self.head < self.tail || (self.head == self.tail && live) {
+            // wrapped around: the free space ends at the tail
+            if start + size > self.tail { return None; }
+            start
+        } else if start + size <= self.capacity {
+            start
+        } else if size <= self.tail {
+            0
+        } else {
+            return None;
+        };
+        self.open_bytes += if offset == 0 && self.head > 0 { self.capacity - self.head + size } else { offset + size - self.head };
+        self.head = offset + size;
+
+        Some(offset)
+    }
+
+    /// Writes `size` bytes with `f` into staging space, or returns `None` while the ring is too full.
+    fn stage(&mut self, size: u64, f: impl FnOnce(&mut [u8])) -> Result<Option<u64>> {
+        let offset = match self.alloc(size) {
+            Some(o) => o,
+            None => return Ok(None)
+        };
+        let p = self.memory.mapped_ptr().ok_or(Error::Unsupported("staging memory not mapped?"))?;
+        f(unsafe { std::slice::from_raw_parts_mut(p.add(offset as _), size as _) });
+        self.device.allocator().flush(&self.memory, offset, size)?;
+
+        Ok(Some(offset))
+    }
+
+    /// Stages `data` for a copy into `dst` at `dst_offset`. Returns false while the ring is too full.
+    pub fn upload_buffer(&mut self, dst: br::vk::VkBuffer, dst_offset: u64, data: &[u8]) -> Result<bool> {
+        let size = data.len() as u64;
+        match self.stage(size, |p| p.copy_from_slice(data))? {
+            Some(src_offset) => {
+                self.copies.push(Copy::Buffer { dst, src_offset, dst_offset, size });
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    }
+
+    /// Stages `data` for a copy into the image rectangle `dst`. Returns false while the ring is too full.
+    pub fn upload_image(&mut self, dst: &ImageUpload, data: &[u8]) -> Result<bool> {
+        match self.stage(data.len() as _, |p| p.copy_from_slice(data))? {
+            Some(src_offset) => {
+                self.copies.push(Copy::Image { dst: *dst, src_offset });
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    }
+
+    /// Fills levels 1 to `levels - 1` of a color image by blitting each from the one above, once the queued copies have
+    /// been recorded. Level 0 has to be uploaded in `VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL` first; all levels are left in
+    /// `new_layout`. The format must support linear blits.
+    pub fn generate_mipmaps(
+        &mut self, image: br::vk::VkImage, width: u32, height: u32, levels: u32, new_layout: br::vk::VkImageLayout
+    ) {
+        debug_assert!(levels > 1, "no levels to generate");
+        self.mip_chains.push(MipChain { image, width, height, levels, new_layout });
+    }
+
+    /// Records the queued copies into `cmd`, with barriers against earlier reads of the destinations and making
+    /// the new contents visible to the vertex input, shader and transfer stages. Mip chains are generated afterwards.
+    /// Several uploads into one subresource share its layout transitions ([`layout_transitions`]); copies writing over
+    /// earlier ones of the batch wait for them.
+    pub fn record(&mut self, cmd: br::vk::VkCommandBuffer) {
+        let subresource_range = |d: &LayoutTransition| br::vk::VkImageSubresourceRange {
+            aspectMask: d.aspect_mask,
+            baseMipLevel: d.mip_level,
+            levelCount: 1,
+            baseArrayLayer: d.array_layer,
+            layerCount: 1
+        };
+        let image_barrier = |d: &LayoutTransition, src_access, dst_access, old_layout, new_layout| br::vk::VkImageMemoryBarrier {
+            sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: std::ptr::null(),
+            srcAccessMask: src_access,
+            dstAccessMask: dst_access,
+            oldLayout: old_layout,
+            newLayout: new_layout,
+            srcQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+            dstQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+            image: d.image,
+            subresourceRange: subresource_range(d)
+        };
+        let images = self.copies.iter().filter_map(|c| match c { Copy::Image { dst, .. } => Some(*dst), _ => None });
+        let transitions = layout_transitions(&images.collect::<Vec<_>>());
+        let in_image_barriers = transitions.iter().map(|d| image_barrier(
+            d, 0, br::vk::VK_ACCESS_TRANSFER_WRITE_BIT, d.old_layout, br::vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL
+        )).collect::<Vec<_>>();
+        let out_image_barriers = transitions.iter().map(|d| image_barrier(
+            d, br::vk::VK_ACCESS_TRANSFER_WRITE_BIT, br::vk::VK_ACCESS_SHADER_READ_BIT | br::vk::VK_ACCESS_TRANSFER_READ_BIT,
+            br::vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, d.new_layout
+        )).collect::<Vec<_>>();
+        let in_memory_barriers = &[br::vk::VkMemoryBarrier {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_BARRIER,
+            pNext: std::ptr::null(),
+            srcAccessMask: br::vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+            dstAccessMask: br::vk::VK_ACCESS_TRANSFER_WRITE_BIT
+        }];
+        let out_memory_barriers = &[br::vk::VkMemoryBarrier {
+            sType: br::vk::VK_STRUCTURE_TYPE_MEMORY_BARRIER,
+            pNext: std::ptr::null(),
+            srcAccessMask: br::vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+            dstAccessMask: br::vk::VK_ACCESS_VERTEX_ATTRIBUTE_READ_BIT | br::vk::VK_ACCESS_INDEX_READ_BIT |
+                br::vk::VK_ACCESS_UNIFORM_READ_BIT | br::vk::VK_ACCESS_SHADER_READ_BIT | br::vk::VK_ACCESS_TRANSFER_READ_BIT
+        }];
+        let read_stages = br::vk::VK_PIPELINE_STAGE_VERTEX_INPUT_BIT | br::vk::VK_PIPELINE_STAGE_VERTEX_SHADER_BIT |
+            br::vk::VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT;
+
+        unsafe {
+            // earlier frames may still be reading (or copying into) the destinations
+            br::vk::vkCmdPipelineBarrier(
+                cmd, read_stages | br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, 0,
+                in_memory_barriers.len() as _, in_memory_barriers.as_ptr(), 0, std::ptr::null(),
+                in_image_barriers.len() as _, in_image_barriers.as_ptr()
+            );
+            let mut unordered_from = 0;
+            for (n, c) in self.copies.iter().enumerate() {
+                if self.copies[unordered_from..n].iter().any(|e| e.overlaps(c)) {
+                    br::vk::vkCmdPipelineBarrier(
+                        cmd, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, 0,
+                        in_memory_barriers.len() as _, in_memory_barriers.as_ptr(), 0, std::ptr::null(), 0, std::ptr::null()
+                    );
+                    unordered_from = n;
+                }
+                match *c {
+                    Copy::Buffer { dst, src_offset, dst_offset, size } => {
+                        let region = br::vk::VkBufferCopy { srcOffset: src_offset, dstOffset: dst_offset, size };
+                        br::vk::vkCmdCopyBuffer(cmd, self.buffer, dst, 1, &region);
+                    },
+                    Copy::Image { dst, src_offset } => {
+                        let region = br::vk::VkBufferImageCopy {
+                            bufferOffset: src_offset,
+                            bufferRowLength: 0,
+                            bufferImageHeight: 0,
+                            imageSubresource: br::vk::VkImageSubresourceLayers {
+                                aspectMask: dst.aspect_mask,
+                                mipLevel: dst.mip_level,
+                                baseArrayLayer: dst.array_layer,
+                                layerCount: 1
+                            },
+                            imageOffset: br::vk::VkOffset3D { x: dst.x, y: dst.y, z: 0 },
+                            imageExtent: br::vk::VkExtent3D { width: dst.width, height: dst.height, depth: 1 }
+                        };
+                        br::vk::vkCmdCopyBufferToImage(
+                            cmd, self.buffer, dst.image, br::vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, 1, &region
+                        );
+                    }
+                }
+            }
+            self.copies.clear();
+            br::vk::vkCmdPipelineBarrier(
+                cmd, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, read_stages | br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, 0,
+                out_memory_barriers.len() as _, out_memory_barriers.as_ptr(), 0, std::ptr::null(),
+                out_image_barriers.len() as _, out_image_barriers.as_ptr()
+            );
+        }
+        for m in self.mip_chains.drain(..) {
+            unsafe { record_mip_chain(cmd, &m, read_stages) };
+        }
+    }
+
+    /// Assigns the space handed out since the last call to frame slot `frame`, which is about to be submitted.
+    pub fn close_frame(&mut self, frame: usize) {
+        if self.open_bytes == 0 { return; }
+        self.regions.push_back(Region { frame, end: self.head });
+        self.open_bytes = 0;
+    }
+
+    /// Releases the space of frame slot `frame` (and of the frames submitted before it) after it has finished.
+    pub fn release_frame(&mut self, frame: usize) {
+        if let Some(n) = self.regions.iter().position(|r| r.frame == frame) {
+            self.tail = self.regions[n].end;
+            self.regions.drain(..=n);
+        }
+    }
+
+    /// Frame slot holding the oldest staging space still in use, if any.
+    pub fn oldest_frame(&self) -> Option<usize> { self.regions.front().map(|r| r.frame) }
+}
+impl Drop for UploadRing {
+    fn drop(&mut self) {
+        unsafe { br::vk::vkDestroyBuffer(self.device.native_ptr(), self.buffer, std::ptr::null()) };
+        self.device.allocator().free(std::mem::take(&mut self.memory));
+    }
+}
+
+/// Blits each level of `m` from the one above. Level 0 is in `TRANSFER_SRC_OPTIMAL` and readable by transfers.
+unsafe fn record_mip_chain(cmd: br::vk::VkCommandBuffer, m: &MipChain, read_stages: br::vk::VkPipelineStageFlags) {
+    let barrier = |base_level, level_count, src_access, dst_access, old_layout, new_layout| br::vk::VkImageMemoryBarrier {
+        sType: br::vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+        pNext: std::ptr::null(),
+        srcAccessMask: src_access,
+        dstAccessMask: dst_access,
+        oldLayout: old_layout,
+        newLayout: new_layout,
+        srcQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+        dstQueueFamilyIndex: br::vk::VK_QUEUE_FAMILY_IGNORED,
+        image: m.image,
+        subresourceRange: br::vk::VkImageSubresourceRange {
+            aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+            baseMipLevel: base_level,
+            levelCount: level_count,
+            baseArrayLayer: 0,
+            layerCount: 1
+        }
+    };
+    let layers = |level| br::vk::VkImageSubresourceLayers {
+        aspectMask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT, mipLevel: level, baseArrayLayer: 0, layerCount: 1
+    };
+    let extent = |level: u32| br::vk::VkOffset3D { x: (m.width >> level).max(1) as _, y: (m.height >> level).max(1) as _, z: 1 };
+
+    let b = barrier(
+        1, m.levels - 1, 0, br::vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+        br::vk::VK_IMAGE_LAYOUT_UNDEFINED, br::vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL
+    );
+    br::vk::vkCmdPipelineBarrier(
+        cmd, br::vk::VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, 0,
+        0, std::ptr::null(), 0, std::ptr::null(), 1, &b
+    );
+    for level in 1..m.levels {
+        let region = br::vk::VkImageBlit {
+            srcSubresource: layers(level - 1),
+            srcOffsets: [br::vk::VkOffset3D { x: 0, y: 0, z: 0 }, extent(level - 1)],
+            dstSubresource: layers(level),
+            dstOffsets: [br::vk::VkOffset3D { x: 0, y: 0, z: 0 }, extent(level)]
+        };
+        br::vk::vkCmdBlitImage(
+            cmd, m.image, br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, m.image, br::vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+            1, &region, br::vk::VK_FILTER_LINEAR
+        );
+        // the level is the source of the next blit
+        let b = barrier(
+            level, 1, br::vk::VK_ACCESS_TRANSFER_WRITE_BIT, br::vk::VK_ACCESS_TRANSFER_READ_BIT,
+            br::vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL
+        );
+        br::vk::vkCmdPipelineBarrier(
+            cmd, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, 0,
+            0, std::ptr::null(), 0, std::ptr::null(), 1, &b
+        );
+    }
+    let b = barrier(
+        0, m.levels, br::vk::VK_ACCESS_TRANSFER_WRITE_BIT, br::vk::VK_ACCESS_SHADER_READ_BIT,
+        br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, m.new_layout
+    );
+    br::vk::vkCmdPipelineBarrier(
+        cmd, br::vk::VK_PIPELINE_STAGE_TRANSFER_BIT, read_stages, 0, 0, std::ptr::null(), 0, std::ptr::null(), 1, &b
+    );
+}
+
+fn align_up(x: u64, a: u64) -> u64 { x.div_ceil(a) * a }
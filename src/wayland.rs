@@ -0,0 +1,128 @@
+use bedrock as br;
+use wayland_client::{Display, EventQueue, GlobalManager, Main};
+use wayland_client::protocol::{wl_compositor, wl_surface};
+use wayland_protocols::xdg_shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base};
+use crate::{vk_check, Error, Renderer, Result, WindowEvent};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct ToplevelState {
+    configured_size: Option<(u32, u32)>,
+    close_requested: bool
+}
+
+/// xdg_shell toplevel on a Wayland compositor.
+///
+/// Compositors blend surface contents as premultiplied alpha, which is what the renderer outputs;
+/// no opaque region is set, so the whole surface takes part in blending.
+pub struct WaylandWindow {
+    display: Display,
+    event_queue: EventQueue,
+    _wm_base: Main<xdg_wm_base::XdgWmBase>,
+    surface: Main<wl_surface::WlSurface>,
+    xdg_surface: Main<xdg_surface::XdgSurface>,
+    toplevel: Main<xdg_toplevel::XdgToplevel>,
+    state: Rc<RefCell<ToplevelState>>,
+    width: u32,
+    height: u32
+}
+impl WaylandWindow {
+    /// Instance extensions the renderer must be created with to create a surface for the window.
+    pub const INSTANCE_EXTENSIONS: &'static [&'static str] = &["VK_KHR_surface", "VK_KHR_wayland_surface"];
+
+    /// Connects to `$WAYLAND_DISPLAY` and creates a toplevel, sized by the compositor or by `width`/`height`.
+    pub fn new(title: &str, width: u32, height: u32) -> Result<Self> {
+        let display = Display::connect_to_env()
+            .map_err(|e| Error::Os("Wayland connection failed", std::io::Error::other(format!("{:?}", e))))?;
+        let mut event_queue = display.create_event_queue();
+        let attached_display = display.attach(event_queue.token());
+        let globals = GlobalManager::new(&attached_display);
+        event_queue.sync_roundtrip(&mut (), |_, _, _| ()).map_err(|e| Error::Os("Wayland roundtrip failed", e))?;
+
+        let compositor = globals.instantiate_exact::<wl_compositor::WlCompositor>(1)
+            .map_err(|_| Error::Unsupported("wl_compositor not available"))?;
+        let wm_base = globals.instantiate_exact::<xdg_wm_base::XdgWmBase>(1)
+            .map_err(|_| Error::Unsupported("xdg_wm_base not available"))?;
+        wm_base.quick_assign(|wm_base, event, _| {
+            if let xdg_wm_base::Event::Ping { serial } = event { wm_base.pong(serial); }
+        });
+
+        let state = Rc::new(RefCell::new(ToplevelState::default()));
+        let surface = compositor.create_surface();
+        let xdg_surface = wm_base.get_xdg_surface(&surface);
+        xdg_surface.quick_assign(|xdg_surface, event, _| {
+            if let xdg_surface::Event::Configure { serial } = event { xdg_surface.ack_configure(serial); }
+        });
+        let toplevel = xdg_surface.get_toplevel();
+        toplevel.set_title(title.to_owned());
+        let toplevel_state = state.clone();
+        toplevel.quick_assign(move |_, event, _| match event {
+            // zero means the size is up to the client
+            xdg_toplevel::Event::Configure { width, height, .. } if width > 0 && height > 0 => {
+                toplevel_state.borrow_mut().configured_size = Some((width as _, height as _));
+            },
+            xdg_toplevel::Event::Close => { toplevel_state.borrow_mut().close_requested = true; },
+            _ => ()
+        });
+        // the initial configure has to be acknowledged before the swapchain attaches any buffer
+        surface.commit();
+        event_queue.sync_roundtrip(&mut (), |_, _, _| ()).map_err(|e| Error::Os("Wayland roundtrip failed", e))?;
+        let (width, height) = state.borrow_mut().configured_size.take().unwrap_or((width, height));
+
+        Ok(WaylandWindow {
+            display, event_queue, _wm_base: wm_base, surface, xdg_surface, toplevel, state, width, height
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) { (self.width, self.height) }
+
+    /// Creates a surface for the window; pass it on to [`SwapchainTarget::new`](crate::SwapchainTarget::new).
+    pub fn create_surface(&self, renderer: &Renderer) -> Result<br::vk::VkSurfaceKHR> {
+        let surface_cinfo = br::vk::VkWaylandSurfaceCreateInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR,
+            pNext: std::ptr::null(),
+            flags: 0,
+            display: self.display.get_display_ptr() as _,
+            surface: self.surface.as_ref().c_ptr() as _
+        };
+        let mut surface = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkCreateWaylandSurfaceKHR(renderer.device().instance(), &surface_cinfo, std::ptr::null(), &mut surface) };
+        vk_check(r, "vkCreateWaylandSurfaceKHR failed")?;
+
+        Ok(surface)
+    }
+
+    /// Reads and dispatches pending events without blocking. A lost connection is reported as a close request.
+    pub fn poll_events(&mut self) -> Vec<WindowEvent> {
+        let mut events = Vec::new();
+        let mut alive = self.display.flush().is_ok();
+        if let Some(guard) = self.event_queue.prepare_read() {
+            // the socket is read without blocking; WouldBlock only means nothing has arrived yet
+            if let Err(e) = guard.read_events() {
+                alive &= e.kind() == std::io::ErrorKind::WouldBlock;
+            }
+        }
+        alive &= self.event_queue.dispatch_pending(&mut (), |_, _, _| ()).is_ok();
+
+        let mut state = self.state.borrow_mut();
+        if let Some((width, height)) = state.configured_size.take() {
+            if (width, height) != (self.width, self.height) {
+                self.width = width;
+                self.height = height;
+                events.push(WindowEvent::Resized(width, height));
+            }
+        }
+        if state.close_requested || !alive { events.push(WindowEvent::CloseRequested); }
+
+        events
+    }
+}
+impl Drop for WaylandWindow {
+    fn drop(&mut self) {
+        self.toplevel.destroy();
+        self.xdg_surface.destroy();
+        self.surface.destroy();
+        let _ = self.display.flush();
+    }
+}
@@ -0,0 +1,110 @@
+use bedrock as br;
+use crate::{vk_check, Error, Renderer, Result, WindowEvent};
+
+/// Top-level X11 window with a 32-bit ARGB visual, so that a compositing manager blends it with what is behind.
+pub struct XcbWindow {
+    connection: xcb::Connection,
+    window: xcb::Window,
+    colormap: xcb::Colormap,
+    wm_protocols: xcb::Atom,
+    wm_delete_window: xcb::Atom,
+    width: u32,
+    height: u32
+}
+impl XcbWindow {
+    /// Instance extensions the renderer must be created with to create a surface for the window.
+    pub const INSTANCE_EXTENSIONS: &'static [&'static str] = &["VK_KHR_surface", "VK_KHR_xcb_surface"];
+
+    /// Connects to `$DISPLAY` and maps a window of the given size.
+    pub fn new(title: &str, width: u32, height: u32) -> Result<Self> {
+        let (connection, screen_num) = xcb::Connection::connect(None)
+            .map_err(|e| Error::Os("xcb connection failed", std::io::Error::other(format!("{:?}", e))))?;
+        let setup = connection.get_setup();
+        let screen = setup.roots().nth(screen_num as _).ok_or(Error::Unsupported("X screen not found?"))?;
+        let visual = screen.allowed_depths()
+            .filter(|d| d.depth() == 32)
+            .flat_map(|d| d.visuals())
+            .find(|v| v.class() == xcb::VISUAL_CLASS_TRUE_COLOR as u8)
+            .map(|v| v.visual_id())
+            .ok_or(Error::Unsupported("no 32-bit TrueColor visual"))?;
+        let root = screen.root();
+
+        // a window with a depth differing from its parent needs its own colormap and border pixel
+        let colormap = connection.generate_id();
+        xcb::create_colormap(&connection, xcb::COLORMAP_ALLOC_NONE as _, colormap, root, visual);
+        let window = connection.generate_id();
+        xcb::create_window(
+            &connection, 32, window, root, 0, 0, width as _, height as _, 0,
+            xcb::WINDOW_CLASS_INPUT_OUTPUT as _, visual,
+            &[
+                (xcb::CW_BACK_PIXEL, 0),
+                (xcb::CW_BORDER_PIXEL, 0),
+                (xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY),
+                (xcb::CW_COLORMAP, colormap)
+            ]
+        );
+        let wm_protocols = xcb::intern_atom(&connection, false, "WM_PROTOCOLS").get_reply()
+            .map_err(|_| Error::Unsupported("interning WM_PROTOCOLS failed"))?.atom();
+        let wm_delete_window = xcb::intern_atom(&connection, false, "WM_DELETE_WINDOW").get_reply()
+            .map_err(|_| Error::Unsupported("interning WM_DELETE_WINDOW failed"))?.atom();
+        xcb::change_property(&connection, xcb::PROP_MODE_REPLACE as _, window, wm_protocols, xcb::ATOM_ATOM, 32, &[wm_delete_window]);
+        xcb::change_property(&connection, xcb::PROP_MODE_REPLACE as _, window, xcb::ATOM_WM_NAME, xcb::ATOM_STRING, 8, title.as_bytes());
+        xcb::map_window(&connection, window);
+        connection.flush();
+
+        Ok(XcbWindow { connection, window, colormap, wm_protocols, wm_delete_window, width, height })
+    }
+
+    pub fn size(&self) -> (u32, u32) { (self.width, self.height) }
+
+    /// Creates a surface for the window; pass it on to [`SwapchainTarget::new`](crate::SwapchainTarget::new).
+    pub fn create_surface(&self, renderer: &Renderer) -> Result<br::vk::VkSurfaceKHR> {
+        let surface_cinfo = br::vk::VkXcbSurfaceCreateInfoKHR {
+            sType: br::vk::VK_STRUCTURE_TYPE_XCB_SURFACE_CREATE_INFO_KHR,
+            pNext: std::ptr::null(),
+            flags: 0,
+            connection: self.connection.get_raw_conn() as _,
+            window: self.window
+        };
+        let mut surface = br::vk::VK_NULL_HANDLE as _;
+        let r = unsafe { br::vk::vkCreateXcbSurfaceKHR(renderer.device().instance(), &surface_cinfo, std::ptr::null(), &mut surface) };
+        vk_check(r, "vkCreateXcbSurfaceKHR failed")?;
+
+        Ok(surface)
+    }
+
+    /// Drains pending events without blocking. Consecutive resizes are reported once.
+    pub fn poll_events(&mut self) -> Vec<WindowEvent> {
+        let mut events = Vec::new();
+        let mut resized = false;
+        while let Some(ev) = self.connection.poll_for_event() {
+            match ev.response_type() & !0x80 {
+                xcb::CONFIGURE_NOTIFY => {
+                    let ev: &xcb::ConfigureNotifyEvent = unsafe { xcb::cast_event(&ev) };
+                    if (ev.width() as u32, ev.height() as u32) != (self.width, self.height) {
+                        self.width = ev.width() as _;
+                        self.height = ev.height() as _;
+                        resized = true;
+                    }
+                },
+                xcb::CLIENT_MESSAGE => {
+                    let ev: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(&ev) };
+                    if ev.type_() == self.wm_protocols && ev.data().data32()[0] == self.wm_delete_window {
+                        events.push(WindowEvent::CloseRequested);
+                    }
+                },
+                _ => ()
+            }
+        }
+        if resized { events.push(WindowEvent::Resized(self.width, self.height)); }
+
+        events
+    }
+}
+impl Drop for XcbWindow {
+    fn drop(&mut self) {
+        xcb::destroy_window(&self.connection, self.window);
+        xcb::free_colormap(&self.connection, self.colormap);
+        self.connection.flush();
+    }
+}
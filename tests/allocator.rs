@@ -0,0 +1,161 @@
+//! Memory type selection against synthetic device memory properties, and sub-allocation bookkeeping; needs no GPU.
+
+use bedrock as br;
+use vk_noredirect_render::{select_memory_type, MemoryStats, MemoryUsage, SubAllocator};
+
+const DEVICE_LOCAL: br::vk::VkMemoryPropertyFlags = br::vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT;
+const HOST_VISIBLE: br::vk::VkMemoryPropertyFlags = br::vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT;
+const HOST_COHERENT: br::vk::VkMemoryPropertyFlags = br::vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT;
+const HOST_CACHED: br::vk::VkMemoryPropertyFlags = br::vk::VK_MEMORY_PROPERTY_HOST_CACHED_BIT;
+
+fn properties(types: &[br::vk::VkMemoryPropertyFlags]) -> br::vk::VkPhysicalDeviceMemoryProperties {
+    let mut props: br::vk::VkPhysicalDeviceMemoryProperties = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+    props.memoryHeapCount = 2;
+    props.memoryHeaps[0].size = 8 << 30;
+    props.memoryHeaps[0].flags = br::vk::VK_MEMORY_HEAP_DEVICE_LOCAL_BIT;
+    props.memoryHeaps[1].size = 16 << 30;
+    props.memoryTypeCount = types.len() as _;
+    for (t, &flags) in props.memoryTypes.iter_mut().zip(types) {
+        t.propertyFlags = flags;
+        t.heapIndex = if (flags & DEVICE_LOCAL) != 0 { 0 } else { 1 };
+    }
+
+    props
+}
+
+/// A discrete GPU with a host visible window into VRAM.
+fn discrete() -> br::vk::VkPhysicalDeviceMemoryProperties {
+    properties(&[
+        DEVICE_LOCAL | HOST_VISIBLE | HOST_COHERENT,
+        DEVICE_LOCAL,
+        HOST_VISIBLE | HOST_COHERENT,
+        HOST_VISIBLE | HOST_COHERENT | HOST_CACHED
+    ])
+}
+
+#[test]
+fn device_local_avoids_host_visible_types() {
+    assert_eq!(select_memory_type(&discrete(), !0, MemoryUsage::DEVICE_LOCAL), Some(1));
+}
+
+#[test]
+fn upload_prefers_coherent_system_memory() {
+    assert_eq!(select_memory_type(&discrete(), !0, MemoryUsage::UPLOAD), Some(2));
+}
+
+#[test]
+fn readback_prefers_cached_memory() {
+    assert_eq!(select_memory_type(&discrete(), !0, MemoryUsage::READBACK), Some(3));
+    // without a cached type, any host visible one does
+    assert_eq!(select_memory_type(&discrete(), 0b0111, MemoryUsage::READBACK), Some(2));
+}
+
+#[test]
+fn type_bits_restrict_the_candidates() {
+    assert_eq!(select_memory_type(&discrete(), 0b0001, MemoryUsage::DEVICE_LOCAL), Some(0));
+    assert_eq!(select_memory_type(&discrete(), 0b1100, MemoryUsage::DEVICE_LOCAL), None);
+    assert_eq!(select_memory_type(&discrete(), 0, MemoryUsage::UPLOAD), None);
+}
+
+#[test]
+fn types_beyond_the_count_are_ignored() {
+    let props = properties(&[DEVICE_LOCAL]);
+    assert_eq!(select_memory_type(&props, !0, MemoryUsage::UPLOAD), None);
+}
+
+#[test]
+fn ties_go_to_the_lowest_index() {
+    // unified memory: every type is both device local and host visible
+    let props = properties(&[
+        DEVICE_LOCAL | HOST_VISIBLE | HOST_COHERENT,
+        DEVICE_LOCAL | HOST_VISIBLE | HOST_COHERENT,
+        DEVICE_LOCAL | HOST_VISIBLE | HOST_COHERENT | HOST_CACHED
+    ]);
+    assert_eq!(select_memory_type(&props, !0, MemoryUsage::DEVICE_LOCAL), Some(0));
+    assert_eq!(select_memory_type(&props, 0b110, MemoryUsage::UPLOAD), Some(1));
+    assert_eq!(select_memory_type(&props, !0, MemoryUsage::READBACK), Some(2));
+}
+
+#[test]
+fn custom_requirements() {
+    let usage = MemoryUsage { required: DEVICE_LOCAL | HOST_VISIBLE, preferred: HOST_COHERENT };
+    assert_eq!(select_memory_type(&discrete(), !0, usage), Some(0));
+    let usage = MemoryUsage { required: HOST_CACHED, preferred: 0 };
+    assert_eq!(select_memory_type(&discrete(), 0b0111, usage), None);
+}
+
+#[test]
+fn sub_allocations_are_aligned_and_packed() {
+    let mut ranges = SubAllocator::new(1);
+    assert_eq!(ranges.allocate(0, true, 100, 16), None);
+    let block = ranges.add_block(0, true, 1024);
+    assert_eq!(ranges.allocate(0, true, 100, 16), Some((block, 0)));
+    assert_eq!(ranges.allocate(0, true, 100, 256), Some((block, 256)));
+    // the gap left by the alignment is used by the next allocation that fits
+    assert_eq!(ranges.allocate(0, true, 50, 4), Some((block, 100)));
+    assert_eq!(ranges.free_ranges(block), [(150, 106), (356, 668)]);
+    assert_eq!(ranges.allocate(0, true, 700, 1), None);
+    // other memory types have blocks of their own
+    assert_eq!(ranges.allocate(1, true, 4, 1), None);
+}
+
+#[test]
+fn neighbouring_frees_coalesce() {
+    let mut ranges = SubAllocator::new(1);
+    let block = ranges.add_block(0, true, 300);
+    let offsets = (0..3).map(|_| ranges.allocate(0, true, 100, 1).unwrap().1).collect::<Vec<_>>();
+    assert_eq!(offsets, [0, 100, 200]);
+
+    ranges.release(block, 0, 100);
+    ranges.release(block, 200, 100);
+    assert_eq!(ranges.free_ranges(block), [(0, 100), (200, 100)]);
+    assert_eq!(ranges.allocate(0, true, 150, 1), None);
+    ranges.release(block, 100, 100);
+    assert_eq!(ranges.free_ranges(block), [(0, 300)]);
+    assert_eq!(ranges.allocate(0, true, 300, 1), Some((block, 0)));
+}
+
+#[test]
+fn linear_and_optimal_resources_are_kept_apart() {
+    let mut ranges = SubAllocator::new(1024);
+    let linear = ranges.add_block(0, true, 4096);
+    assert_eq!(ranges.allocate(0, false, 64, 1), None);
+    let optimal = ranges.add_block(0, false, 4096);
+    assert_eq!(ranges.allocate(0, false, 64, 1), Some((optimal, 0)));
+    assert_eq!(ranges.allocate(0, true, 64, 1), Some((linear, 0)));
+
+    // without a granularity to respect, images share the blocks of buffers
+    let mut ranges = SubAllocator::new(1);
+    let block = ranges.add_block(0, true, 4096);
+    assert_eq!(ranges.allocate(0, false, 64, 1), Some((block, 0)));
+    assert_eq!(ranges.allocate(0, true, 64, 1), Some((block, 64)));
+}
+
+#[test]
+fn stats_add_up() {
+    let mut ranges = SubAllocator::new(1);
+    assert_eq!(ranges.stats(), MemoryStats::default());
+    let first = ranges.add_block(0, true, 1024);
+    ranges.add_block(1, true, 2048);
+    ranges.allocate(0, true, 100, 1);
+    ranges.allocate(0, true, 200, 1);
+    ranges.allocate(1, true, 300, 1);
+    ranges.add_dedicated(1 << 20);
+    ranges.add_dedicated(1 << 10);
+    assert_eq!(ranges.stats(), MemoryStats {
+        block_count: 2,
+        block_bytes: 3072,
+        allocation_count: 3,
+        allocated_bytes: 600,
+        dedicated_count: 2,
+        dedicated_bytes: (1 << 20) + (1 << 10)
+    });
+
+    ranges.release(first, 0, 100);
+    ranges.release_dedicated(1 << 20);
+    let stats = ranges.stats();
+    assert_eq!((stats.allocation_count, stats.allocated_bytes), (2, 500));
+    assert_eq!((stats.dedicated_count, stats.dedicated_bytes), (1, 1 << 10));
+    // blocks are kept
+    assert_eq!((stats.block_count, stats.block_bytes), (2, 3072));
+}
@@ -0,0 +1,22 @@
+//! Draw list recording checks; needs no GPU.
+
+use vk_noredirect_render::{DrawList, DrawRegion, Error};
+
+#[test]
+fn empty_viewports_are_invalid() {
+    assert!(DrawRegion::rect(10, 20, 30, 40).has_valid_viewport());
+    assert!(!DrawRegion::rect(10, 20, 0, 40).has_valid_viewport());
+    assert!(!DrawRegion::rect(10, 20, 30, 0).has_valid_viewport());
+}
+
+#[test]
+fn lists_reject_empty_viewports() {
+    let mut list = DrawList::new();
+    list.set_region(&DrawRegion::rect(10, 20, 30, 40));
+    assert!(list.validate().is_ok());
+
+    list.set_region(&DrawRegion::rect(0, 0, 0, 0));
+    assert!(matches!(list.validate(), Err(Error::Unsupported(_))));
+    list.clear();
+    assert!(list.validate().is_ok());
+}
@@ -0,0 +1,110 @@
+//! Golden-image tests: renders the triangle headlessly at pinned times and compares against `tests/golden/*.png`.
+//!
+//! Needs a Vulkan driver (lavapipe is fine) and the compiled shaders (`make -C assets`).
+//! References hold the raw premultiplied RGBA8 readback. Run with `GOLDEN_BLESS=1` to rewrite them from the
+//! current output; on failure the actual and diff images are written to `target/golden/`.
+
+use bedrock as br;
+use vk_noredirect_render::{HeadlessTarget, Renderer, RendererOptions};
+use std::path::{Path, PathBuf};
+
+const EXTENT: u32 = 128;
+/// Allowed absolute difference per channel.
+const CHANNEL_TOLERANCE: u8 = 2;
+/// Pixels allowed to exceed the tolerance: rasterizers may disagree on coverage exactly along the edges.
+const MAX_MISMATCHED_PIXELS: usize = 16;
+
+fn render(time: f32) -> Vec<u8> {
+    let mut renderer = Renderer::new(&RendererOptions {
+        validation: false,
+        extent: br::vk::VkExtent2D { width: EXTENT, height: EXTENT },
+        .. Default::default()
+    }).expect("Renderer initialization failed");
+    let mut target = HeadlessTarget::new(&renderer).expect("Headless target initialization failed");
+    renderer.attach(&target).expect("Attaching backbuffers failed");
+
+    renderer.set_time(time).expect("Timer update failed");
+    renderer.render_frame(&mut target).expect("Rendering frame failed");
+    renderer.flush(&mut target).expect("Presenting frame failed");
+
+    target.pixels().to_vec()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+fn read_png(path: &Path) -> Vec<u8> {
+    let fp = std::fs::File::open(path).unwrap_or_else(|e| panic!("opening {} failed: {}", path.display(), e));
+    let (info, mut reader) = png::Decoder::new(fp).read_info().expect("decoding png failed");
+    assert_eq!((info.width, info.height), (EXTENT, EXTENT), "{}: unexpected size", path.display());
+    assert_eq!(
+        (info.color_type, info.bit_depth), (png::ColorType::RGBA, png::BitDepth::Eight),
+        "{}: references must be 8-bit RGBA", path.display()
+    );
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).expect("decoding png failed");
+
+    pixels
+}
+fn write_png(path: &Path, pixels: &[u8]) {
+    let fp = std::fs::File::create(path).unwrap_or_else(|e| panic!("creating {} failed: {}", path.display(), e));
+    let mut enc = png::Encoder::new(std::io::BufWriter::new(fp), EXTENT, EXTENT);
+    enc.set_color(png::ColorType::RGBA);
+    enc.set_depth(png::BitDepth::Eight);
+    enc.write_header().and_then(|mut w| w.write_image_data(pixels)).expect("encoding png failed");
+}
+
+fn check_golden(name: &str, actual: &[u8]) {
+    let golden = golden_path(name);
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        write_png(&golden, actual);
+        return;
+    }
+
+    let expected = read_png(&golden);
+    assert_eq!(expected.len(), actual.len());
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    // mismatching pixels in opaque red, the rest as the amplified difference
+    let diff = expected.chunks(4).zip(actual.chunks(4)).flat_map(|(e, a)| {
+        let d = e.iter().zip(a).map(|(&e, &a)| (e as i32 - a as i32).unsigned_abs() as u8).max().unwrap_or(0);
+        max_difference = max_difference.max(d);
+        if d > CHANNEL_TOLERANCE {
+            mismatched += 1;
+            [255, 0, 0, 255]
+        } else {
+            let v = d.saturating_mul(64);
+            [v, v, v, 255]
+        }
+    }).collect::<Vec<_>>();
+
+    if mismatched > MAX_MISMATCHED_PIXELS {
+        let dir = output_dir();
+        std::fs::create_dir_all(&dir).expect("creating output directory failed");
+        write_png(&dir.join(format!("{}.actual.png", name)), actual);
+        write_png(&dir.join(format!("{}.diff.png", name)), &diff);
+        panic!(
+            "{}: {} pixels differ by more than {} (max difference {}); see {}",
+            name, mismatched, CHANNEL_TOLERANCE, max_difference, dir.display()
+        );
+    }
+}
+
+#[test]
+fn triangle_at_rest() {
+    check_golden("triangle_t0", &render(0.0));
+}
+
+#[test]
+fn triangle_rotated() {
+    check_golden("triangle_t0_4", &render(0.4));
+}
+
+#[test]
+fn triangle_rotated_further() {
+    check_golden("triangle_t1_3", &render(1.3));
+}
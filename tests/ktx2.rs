@@ -0,0 +1,99 @@
+//! KTX2 parsing and host mip downsampling; needs no GPU.
+
+use bedrock as br;
+use vk_noredirect_render::{downsample_rgba8, mip_level_count, Ktx2Image};
+
+const R8G8B8A8_UNORM: u32 = br::vk::VK_FORMAT_R8G8B8A8_UNORM as _;
+
+/// A 2D KTX2 file with the given levels and data format descriptor flags, levels stored smallest first.
+fn ktx2(format: u32, width: u32, height: u32, level_count: u32, levels: &[&[u8]], dfd_flags: u8) -> Vec<u8> {
+    let mut bytes = vec![0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a];
+    let index_end = 80 + levels.len() * 24;
+    let dfd = [&28u32.to_le_bytes()[..], &[0; 4], &[2, 0, 24, 0], &[1, 1, 1, dfd_flags], &[0; 12]].concat();
+    for v in [format, 1, width, height, 0, 0, 1, level_count, 0, index_end as u32, dfd.len() as u32, 0, 0] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0; 16]);
+    let mut offset = (index_end + dfd.len()) as u64;
+    let mut offsets = vec![0; levels.len()];
+    for (n, l) in levels.iter().enumerate().rev() {
+        offsets[n] = offset;
+        offset += l.len() as u64;
+    }
+    for (l, o) in levels.iter().zip(&offsets) {
+        for v in [*o, l.len() as u64, l.len() as u64] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(&dfd);
+    for l in levels.iter().rev() {
+        bytes.extend_from_slice(l);
+    }
+
+    bytes
+}
+
+#[test]
+fn levels_are_read_largest_first() {
+    let (level0, level1) = ([1; 16], [2; 4]);
+    let bytes = ktx2(R8G8B8A8_UNORM, 2, 2, 2, &[&level0, &level1], 1);
+    let image = Ktx2Image::parse(&bytes).expect("parsing KTX2 failed");
+    assert_eq!((image.format as u32, image.width, image.height), (R8G8B8A8_UNORM, 2, 2));
+    assert_eq!(image.levels, [&level0[..], &level1[..]]);
+    assert!(!image.generate_mipmaps);
+    assert!(image.premultiplied);
+}
+
+#[test]
+fn zero_level_count_asks_for_generated_mipmaps() {
+    let bytes = ktx2(R8G8B8A8_UNORM, 1, 1, 0, &[&[1, 2, 3, 4]], 0);
+    let image = Ktx2Image::parse(&bytes).expect("parsing KTX2 failed");
+    assert_eq!(image.levels, [&[1, 2, 3, 4][..]]);
+    assert!(image.generate_mipmaps);
+    assert!(!image.premultiplied);
+}
+
+#[test]
+fn unsupported_and_invalid_files_are_errors() {
+    assert!(Ktx2Image::parse(b"not a ktx2 file").is_err());
+    // Basis Universal payloads have no Vulkan format
+    assert!(Ktx2Image::parse(&ktx2(0, 1, 1, 1, &[&[0; 4]], 0)).is_err());
+    // 1D
+    assert!(Ktx2Image::parse(&ktx2(R8G8B8A8_UNORM, 1, 0, 1, &[&[0; 4]], 0)).is_err());
+
+    let mut bytes = ktx2(R8G8B8A8_UNORM, 1, 1, 1, &[&[0; 4]], 0);
+    bytes.truncate(bytes.len() - 1);
+    assert!(Ktx2Image::parse(&bytes).is_err());
+}
+
+#[test]
+fn levels_must_match_their_extent() {
+    // offsets and lengths in range, but level 0 of a 2×2 image is one texel short
+    let bytes = ktx2(R8G8B8A8_UNORM, 2, 2, 2, &[&[1; 12], &[2; 4]], 1);
+    assert!(Ktx2Image::parse(&bytes).is_err());
+    let bytes = ktx2(R8G8B8A8_UNORM, 2, 2, 2, &[&[1; 16], &[2; 3]], 1);
+    assert!(Ktx2Image::parse(&bytes).is_err());
+
+    // BC1: 8 bytes per 4×4 block, partial blocks rounded up
+    const BC1_RGBA_UNORM_BLOCK: u32 = br::vk::VK_FORMAT_BC1_RGBA_UNORM_BLOCK as _;
+    assert!(Ktx2Image::parse(&ktx2(BC1_RGBA_UNORM_BLOCK, 6, 3, 1, &[&[0; 16]], 1)).is_ok());
+    assert!(Ktx2Image::parse(&ktx2(BC1_RGBA_UNORM_BLOCK, 6, 3, 1, &[&[0; 8]], 1)).is_err());
+}
+
+#[test]
+fn mip_level_counts() {
+    assert_eq!(mip_level_count(1, 1), 1);
+    assert_eq!(mip_level_count(2, 1), 2);
+    assert_eq!(mip_level_count(256, 256), 9);
+    assert_eq!(mip_level_count(300, 17), 9);
+}
+
+#[test]
+fn downsampling_averages_quads() {
+    let pixels = [0, 0, 0, 0, 4, 8, 12, 16, 8, 16, 24, 32, 255, 255, 255, 255];
+    assert_eq!(downsample_rgba8(2, 2, &pixels), (1, 1, vec![67, 70, 73, 76]));
+
+    // odd extents drop the last column, a single row is averaged with itself
+    let row = [10, 10, 10, 10, 20, 20, 20, 20, 99, 99, 99, 99];
+    assert_eq!(downsample_rgba8(3, 1, &row), (1, 1, vec![15, 15, 15, 15]));
+}
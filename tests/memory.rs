@@ -0,0 +1,68 @@
+//! Sub-allocation through the device's [`Allocator`](vk_noredirect_render::Allocator).
+//!
+//! Needs a Vulkan driver (lavapipe is fine) and the compiled shaders (`make -C assets`).
+
+use bedrock as br;
+use vk_noredirect_render::{MemoryStats, MemoryUsage, Renderer, RendererOptions};
+
+fn renderer() -> Renderer {
+    Renderer::new(&RendererOptions { validation: false, .. Default::default() }).expect("Renderer initialization failed")
+}
+
+fn requirements(size: u64, alignment: u64) -> br::vk::VkMemoryRequirements {
+    br::vk::VkMemoryRequirements { size, alignment, memoryTypeBits: !0 }
+}
+
+#[test]
+fn small_resources_share_a_block() {
+    let renderer = renderer();
+    let allocator = renderer.device().allocator();
+    let before = allocator.stats();
+
+    let a = allocator.allocate(&requirements(1000, 256), MemoryUsage::DEVICE_LOCAL, true).expect("allocation failed");
+    let b = allocator.allocate(&requirements(1000, 256), MemoryUsage::DEVICE_LOCAL, true).expect("allocation failed");
+    assert!(!a.is_dedicated() && !b.is_dedicated());
+    assert_eq!((a.offset() % 256, b.offset() % 256), (0, 0));
+    if a.memory() == b.memory() {
+        assert!(a.offset() + a.size() <= b.offset() || b.offset() + b.size() <= a.offset());
+    }
+    let during = allocator.stats();
+    assert_eq!(during.allocation_count, before.allocation_count + 2);
+    assert_eq!(during.allocated_bytes, before.allocated_bytes + a.size() + b.size());
+
+    allocator.free(a);
+    allocator.free(b);
+    let after = allocator.stats();
+    assert_eq!(after, MemoryStats { block_count: during.block_count, block_bytes: during.block_bytes, .. before });
+}
+
+#[test]
+fn freed_ranges_are_reused() {
+    let renderer = renderer();
+    let allocator = renderer.device().allocator();
+
+    let a = allocator.allocate(&requirements(4096, 256), MemoryUsage::UPLOAD, true).expect("allocation failed");
+    let (memory, offset) = (a.memory(), a.offset());
+    allocator.free(a);
+    let b = allocator.allocate(&requirements(4096, 256), MemoryUsage::UPLOAD, true).expect("allocation failed");
+    assert_eq!((b.memory(), b.offset()), (memory, offset));
+    assert!(b.mapped_ptr().is_some());
+    allocator.free(b);
+}
+
+#[test]
+fn large_resources_get_dedicated_memory() {
+    let renderer = renderer();
+    let allocator = renderer.device().allocator();
+    let before = allocator.stats();
+
+    let a = allocator.allocate(&requirements(65 << 20, 256), MemoryUsage::DEVICE_LOCAL, false).expect("allocation failed");
+    assert!(a.is_dedicated());
+    assert_eq!(a.offset(), 0);
+    let during = allocator.stats();
+    assert_eq!((during.dedicated_count, during.dedicated_bytes), (before.dedicated_count + 1, before.dedicated_bytes + (65 << 20)));
+    assert_eq!(during.allocation_count, before.allocation_count);
+
+    allocator.free(a);
+    assert_eq!(allocator.stats(), before);
+}
@@ -0,0 +1,121 @@
+//! glTF and OBJ loading into host memory; needs no GPU.
+
+use vk_noredirect_render::{ImageSource, ModelData};
+use std::path::PathBuf;
+
+/// Positions of a right triangle in the XY plane, followed by u16 indices 0, 1, 2 and padding.
+const TRIANGLE_BASE64: &str = "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=";
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vk-noredirect-models-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Creating scratch directory failed");
+
+    dir
+}
+
+fn triangle_gltf(buffer_uri: &str) -> String {
+    format!(r#"{{
+        "asset": {{ "version": "2.0" }},
+        "scene": 0,
+        "scenes": [{{ "nodes": [0] }}],
+        "nodes": [
+            {{ "translation": [1.0, 2.0, 3.0], "children": [1] }},
+            {{ "scale": [2.0, 2.0, 2.0], "mesh": 0 }}
+        ],
+        "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }}],
+        "materials": [{{ "name": "red", "pbrMetallicRoughness": {{ "baseColorFactor": [1.0, 0.0, 0.0, 0.5] }} }}],
+        "accessors": [
+            {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+            {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+        ],
+        "bufferViews": [
+            {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+            {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+        ],
+        "buffers": [{{ "byteLength": 44, "uri": "{}" }}]
+    }}"#, buffer_uri)
+}
+
+fn check_triangle(model: &ModelData) {
+    assert_eq!(model.meshes.len(), 1);
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.indices, [0, 1, 2]);
+    assert_eq!(mesh.vertices.iter().map(|v| v.position).collect::<Vec<_>>(), [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+    // computed from the counter-clockwise face
+    assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0] && v.color == [1.0; 4]));
+    assert_eq!(mesh.material, Some(0));
+    assert_eq!(model.materials[0].name.as_deref(), Some("red"));
+    assert_eq!(model.materials[0].base_color, [1.0, 0.0, 0.0, 0.5]);
+    assert_eq!(model.materials[0].base_color_texture, None);
+
+    assert_eq!(model.instances.len(), 1);
+    assert_eq!(model.instances[0].mesh, 0);
+    assert_eq!(model.instances[0].transform, [[2.0, 0.0, 0.0, 0.0], [0.0, 2.0, 0.0, 0.0], [0.0, 0.0, 2.0, 0.0], [1.0, 2.0, 3.0, 1.0]]);
+}
+
+#[test]
+fn gltf_with_embedded_buffer() {
+    let dir = scratch_dir("embedded");
+    let path = dir.join("triangle.gltf");
+    std::fs::write(&path, triangle_gltf(&format!("data:application/octet-stream;base64,{}", TRIANGLE_BASE64))).unwrap();
+
+    check_triangle(&ModelData::load(&path).expect("Loading glTF failed"));
+}
+
+#[test]
+fn gltf_with_bin_buffer() {
+    let dir = scratch_dir("bin");
+    let path = dir.join("triangle.gltf");
+    std::fs::write(&path, triangle_gltf("triangle.bin")).unwrap();
+    let bin = TRIANGLE_BASE64.as_bytes().chunks(4).flat_map(decode_base64_quad).collect::<Vec<_>>();
+    std::fs::write(dir.join("triangle.bin"), bin).unwrap();
+
+    check_triangle(&ModelData::load(&path).expect("Loading glTF failed"));
+}
+
+#[test]
+fn obj_with_materials() {
+    let dir = scratch_dir("obj");
+    std::fs::write(dir.join("quad.mtl"), "newmtl tinted\nKd 0.5 0.25 1.0\nd 0.75\nmap_Kd textures\\quad.png\n").unwrap();
+    std::fs::write(dir.join("quad.obj"), concat!(
+        "mtllib quad.mtl\n",
+        "o quad\n",
+        "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n",
+        "vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n",
+        "usemtl tinted\n",
+        "f 1/1 2/2 3/3 4/4\n"
+    )).unwrap();
+
+    let model = ModelData::load(&dir.join("quad.obj")).expect("Loading OBJ failed");
+    assert_eq!(model.meshes.len(), 1);
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.indices.len(), 6);
+    // OBJ UVs start at the bottom-left
+    assert_eq!(mesh.vertices[0].uv, [0.0, 1.0]);
+    assert_eq!(mesh.vertices[2].uv, [1.0, 0.0]);
+    assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    assert_eq!(mesh.material, Some(0));
+    assert_eq!(model.materials[0].base_color, [0.5, 0.25, 1.0, 0.75]);
+    assert_eq!(model.materials[0].base_color_texture, Some(0));
+    assert_eq!(model.images, [ImageSource::File(dir.join("textures/quad.png"))]);
+}
+
+#[test]
+fn unknown_extension() {
+    assert!(ModelData::load(std::path::Path::new("model.fbx")).is_err());
+}
+
+fn decode_base64_quad(quad: &[u8]) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        _ => 63
+    } as u32;
+    let padding = quad.iter().filter(|&&c| c == b'=').count();
+    let bits = quad.iter().fold(0, |acc, &c| (acc << 6) | if c == b'=' { 0 } else { value(c) });
+
+    bits.to_be_bytes()[1..4 - padding].to_vec()
+}
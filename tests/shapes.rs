@@ -0,0 +1,132 @@
+//! 2D shape tessellation; needs no GPU.
+
+use vk_noredirect_render::{Paint, Path, Rect, ShapeBuilder, Stroke};
+
+const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+fn aliased() -> ShapeBuilder {
+    let mut shapes = ShapeBuilder::new();
+    shapes.feather = 0.0;
+    shapes
+}
+
+/// Area covered by the triangles, whichever way they wind.
+fn covered_area(shapes: &ShapeBuilder) -> f32 {
+    let v = shapes.vertices();
+    shapes.indices().chunks(3).map(|t| {
+        let (a, b, c) = (v[t[0] as usize].position, v[t[1] as usize].position, v[t[2] as usize].position);
+        ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])).abs() * 0.5
+    }).sum()
+}
+
+#[test]
+fn filled_rect() {
+    let mut shapes = aliased();
+    shapes.fill_rect(Rect::new(10.0, 20.0, 30.0, 40.0), &RED.into());
+    assert_eq!(shapes.vertices().len(), 4);
+    assert_eq!(shapes.indices().len(), 6);
+    assert_eq!(covered_area(&shapes), 1200.0);
+    assert!(shapes.vertices().iter().all(|v| v.color == RED && v.uv == [0.5, 0.5]));
+}
+
+#[test]
+fn feathered_edges_fade_out() {
+    let mut shapes = ShapeBuilder::new();
+    shapes.fill_rect(Rect::new(0.0, 0.0, 10.0, 10.0), &RED.into());
+    let (inner, outer) = shapes.vertices().split_at(4);
+    assert!(inner.iter().all(|v| v.color[3] == 1.0 && v.position[0] > 0.0 && v.position[0] < 10.0));
+    assert!(outer.iter().all(|v| v.color[3] == 0.0 && (v.position[0] < 0.0 || v.position[0] > 10.0)));
+    // the fringe straddles the outline, so the interior shrinks by half the feather on each side
+    let corner = shapes.vertices()[0].position;
+    assert!((corner[0] - 0.5).abs() < 1e-6 && (corner[1] - 0.5).abs() < 1e-6);
+    assert_eq!(shapes.indices().len(), 6 + 4 * 6);
+}
+
+#[test]
+fn concave_polygons_in_either_winding() {
+    let l_shape = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+    for points in [l_shape.to_vec(), l_shape.iter().rev().copied().collect()] {
+        let mut shapes = aliased();
+        shapes.fill_polygon(&points, &RED.into());
+        assert_eq!(shapes.indices().len(), 4 * 3);
+        assert!((covered_area(&shapes) - 3.0).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn circles_and_rounded_rects_follow_the_tolerance() {
+    let mut shapes = aliased();
+    shapes.fill_circle([0.0, 0.0], 100.0, &RED.into());
+    let area = covered_area(&shapes);
+    assert!(area < std::f32::consts::PI * 100.0 * 100.0 && area > std::f32::consts::PI * 99.75 * 99.75);
+    assert!(shapes.vertices().iter().all(|v| (v.position[0].hypot(v.position[1]) - 100.0).abs() < 1e-3));
+
+    let mut shapes = aliased();
+    shapes.fill_rounded_rect(Rect::new(0.0, 0.0, 40.0, 20.0), 50.0, &RED.into());
+    // clamped to a 10 pixel radius: a stadium, with the chords of its ends at most a quarter pixel inside
+    let (area, expected) = (covered_area(&shapes), 20.0 * 20.0 + std::f32::consts::PI * 10.0 * 10.0);
+    assert!(area < expected && area > expected - 0.25 * std::f32::consts::PI * 20.0);
+}
+
+#[test]
+fn strokes_cover_their_width() {
+    let stroke = Stroke { width: 4.0, paint: RED.into() };
+    let mut shapes = aliased();
+    shapes.stroke_polyline(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]], false, &stroke);
+    assert_eq!(shapes.vertices().len(), 6);
+    assert!((covered_area(&shapes) - 80.0).abs() < 1e-4);
+
+    let mut shapes = aliased();
+    shapes.stroke_rect(Rect::new(0.0, 0.0, 10.0, 10.0), &stroke);
+    // a 14×14 square minus a 6×6 hole
+    assert!((covered_area(&shapes) - (196.0 - 36.0)).abs() < 1e-4);
+}
+
+#[test]
+fn hairlines_fade_instead_of_thinning() {
+    let mut shapes = ShapeBuilder::new();
+    shapes.stroke_polyline(&[[0.0, 0.0], [10.0, 0.0]], false, &Stroke { width: 0.25, paint: RED.into() });
+    assert!(shapes.vertices().iter().all(|v| v.color[3] == 0.0 || v.color[3] == 0.25));
+}
+
+#[test]
+fn gradients() {
+    let linear = Paint::LinearGradient { start: [0.0, 0.0], end: [10.0, 0.0], start_color: [0.0; 4], end_color: [1.0; 4] };
+    assert_eq!(linear.color_at([5.0, 3.0]), [0.5; 4]);
+    assert_eq!(linear.color_at([-5.0, 0.0]), [0.0; 4]);
+    assert_eq!(linear.color_at([20.0, 0.0]), [1.0; 4]);
+
+    let radial = Paint::RadialGradient { center: [1.0, 1.0], radius: 2.0, inner_color: RED, outer_color: [0.0; 4] };
+    assert_eq!(radial.color_at([1.0, 1.0]), RED);
+    assert_eq!(radial.color_at([2.0, 1.0]), [0.5, 0.0, 0.0, 0.5]);
+
+    let mut shapes = aliased();
+    shapes.fill_rect(Rect::new(0.0, 0.0, 10.0, 10.0), &linear);
+    assert!(shapes.vertices().iter().all(|v| v.color == [v.position[0] / 10.0; 4]));
+}
+
+#[test]
+fn paths_flatten_curves_within_tolerance() {
+    let mut path = Path::new();
+    path.move_to([0.0, 0.0]).quad_to([50.0, 100.0], [100.0, 0.0]).line_to([100.0, -10.0]).close();
+    path.move_to([200.0, 0.0]).cubic_to([200.0, 50.0], [300.0, 50.0], [300.0, 0.0]);
+    let subpaths = path.flatten(0.25);
+    assert_eq!(subpaths.len(), 2);
+
+    let (quad, closed) = &subpaths[0];
+    assert!(*closed);
+    assert_eq!(quad.first(), Some(&[0.0, 0.0]));
+    assert_eq!(quad.last(), Some(&[100.0, -10.0]));
+    // the quadratic peaks at half its control point height
+    let peak = quad.iter().map(|p| p[1]).fold(0.0, f32::max);
+    assert!((peak - 50.0).abs() < 0.25);
+
+    let (cubic, closed) = &subpaths[1];
+    assert!(!*closed);
+    assert!(cubic.len() > 8);
+    assert_eq!(cubic.last(), Some(&[300.0, 0.0]));
+
+    let mut shapes = aliased();
+    shapes.fill_path(&path, &RED.into()).stroke_path(&path, &Stroke { width: 1.0, paint: RED.into() });
+    assert!(!shapes.is_empty());
+}
@@ -0,0 +1,64 @@
+//! Text layout with the bundled font; needs no GPU.
+
+use vk_noredirect_render::{Font, TextStyle};
+
+fn style(max_width: Option<f32>) -> TextStyle {
+    TextStyle { size: 20.0, max_width, .. TextStyle::default() }
+}
+
+#[test]
+fn whitespace_has_no_glyphs() {
+    let layout = Font::bundled().layout("a b\tc", &style(None));
+    assert_eq!(layout.glyphs.len(), 3);
+    assert!(layout.glyphs.windows(2).all(|g| g[0].x < g[1].x && g[0].y == g[1].y));
+    assert!(layout.glyphs.iter().all(|g| g.line == 0));
+    assert_eq!(layout.color, [1.0; 4]);
+}
+
+#[test]
+fn pairs_are_kerned() {
+    let font = Font::bundled();
+    let pair = font.layout("AV", &style(None));
+    let (a, v) = (font.layout("A", &style(None)), font.layout("V", &style(None)));
+    // DejaVu Sans kerns "AV" tighter than the advances
+    assert!(pair.width < a.width + v.width);
+    assert!(pair.glyphs[1].x < a.width);
+}
+
+#[test]
+fn newlines_break_lines() {
+    let font = Font::bundled();
+    let one = font.layout("label", &style(None));
+    let layout = font.layout("first\r\n\nthird", &style(None));
+    assert_eq!(layout.glyphs.iter().map(|g| g.line).max(), Some(2));
+    assert_eq!(layout.height, one.height * 3.0);
+    let third = layout.glyphs.iter().find(|g| g.line == 2).unwrap();
+    assert_eq!(third.x, 0.0);
+    assert_eq!(third.y - layout.glyphs[0].y, one.height * 2.0);
+}
+
+#[test]
+fn words_wrap_at_max_width() {
+    let font = Font::bundled();
+    let word = font.layout("overlay", &style(None)).width;
+    let layout = font.layout("overlay overlay overlay", &style(Some(word * 2.5)));
+    let lines = layout.glyphs.iter().map(|g| g.line).collect::<Vec<_>>();
+    assert_eq!(lines, [vec![0; 14], vec![1; 7]].concat());
+    assert!(layout.width <= word * 2.5);
+    assert_eq!(layout.glyphs[14].x, 0.0);
+}
+
+#[test]
+fn long_words_break_between_characters() {
+    let font = Font::bundled();
+    let word = font.layout("overlay", &style(None)).width;
+    let layout = font.layout("overlay", &style(Some(word / 2.0)));
+    assert!(layout.glyphs.iter().all(|g| g.line < 3));
+    assert!(layout.glyphs.iter().any(|g| g.line == 1));
+    assert!(layout.width <= word / 2.0);
+}
+
+#[test]
+fn invalid_font_data_is_an_error() {
+    assert!(Font::from_bytes(b"not a font".to_vec()).is_err());
+}
@@ -0,0 +1,51 @@
+//! PNG decoding and alpha premultiplication for textures; needs no GPU.
+
+use vk_noredirect_render::{decode_png, premultiply_alpha};
+
+fn encode_png(width: u32, height: u32, color: png::ColorType, depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    {
+        let mut enc = png::Encoder::new(&mut bytes, width, height);
+        enc.set_color(color);
+        enc.set_depth(depth);
+        let mut writer = enc.write_header().expect("encoding png failed");
+        writer.write_image_data(data).expect("encoding png failed");
+    }
+
+    bytes
+}
+
+#[test]
+fn premultiplies_color_channels() {
+    let mut pixels = [255, 128, 0, 128, 10, 20, 30, 0, 200, 100, 50, 255];
+    premultiply_alpha(&mut pixels);
+    assert_eq!(pixels, [128, 64, 0, 128, 0, 0, 0, 0, 200, 100, 50, 255]);
+}
+
+#[test]
+fn rgba_is_premultiplied() {
+    let png = encode_png(2, 1, png::ColorType::RGBA, png::BitDepth::Eight, &[255, 255, 255, 51, 0, 255, 0, 255]);
+    let (width, height, pixels) = decode_png(&png).expect("decoding png failed");
+    assert_eq!((width, height), (2, 1));
+    assert_eq!(pixels, [51, 51, 51, 51, 0, 255, 0, 255]);
+}
+
+#[test]
+fn rgb_and_grayscale_become_opaque_rgba() {
+    let png = encode_png(1, 2, png::ColorType::RGB, png::BitDepth::Eight, &[1, 2, 3, 4, 5, 6]);
+    assert_eq!(decode_png(&png).expect("decoding png failed"), (1, 2, vec![1, 2, 3, 255, 4, 5, 6, 255]));
+
+    let png = encode_png(2, 1, png::ColorType::GrayscaleAlpha, png::BitDepth::Eight, &[200, 255, 100, 0]);
+    assert_eq!(decode_png(&png).expect("decoding png failed"), (2, 1, vec![200, 200, 200, 255, 0, 0, 0, 0]));
+}
+
+#[test]
+fn sixteen_bit_channels_are_stripped() {
+    let png = encode_png(1, 1, png::ColorType::RGBA, png::BitDepth::Sixteen, &[0xff, 0x00, 0x80, 0x00, 0x00, 0xff, 0xff, 0xff]);
+    assert_eq!(decode_png(&png).expect("decoding png failed"), (1, 1, vec![255, 128, 0, 255]));
+}
+
+#[test]
+fn invalid_data_is_an_error() {
+    assert!(decode_png(b"not a png").is_err());
+}
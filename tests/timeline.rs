@@ -0,0 +1,50 @@
+//! Render completion through exported timeline semaphores.
+//!
+//! Needs a Vulkan driver that shares timeline semaphores as opaque fds (lavapipe is fine) and the compiled shaders
+//! (`make -C assets`).
+
+use bedrock as br;
+use vk_noredirect_render::{FdHandleType, Renderer, RendererOptions, SharedImageTarget, TimelineSemaphore};
+
+const TIMEOUT_NS: u64 = 5_000_000_000;
+
+fn renderer() -> Renderer {
+    let device_extensions = [FdHandleType::OpaqueFd.device_extensions(), TimelineSemaphore::FD_DEVICE_EXTENSIONS].concat();
+    Renderer::new(&RendererOptions {
+        validation: false,
+        device_extensions: &device_extensions,
+        extent: br::vk::VkExtent2D { width: 64, height: 64 },
+        max_frame_latency: 2,
+        .. Default::default()
+    }).expect("Renderer initialization failed")
+}
+
+#[test]
+fn frames_signal_the_exported_timeline() {
+    let mut renderer = renderer();
+    let mut target = SharedImageTarget::new(&renderer, FdHandleType::OpaqueFd, 2).expect("Shared target initialization failed");
+    let fd = target.enable_timeline().expect("Creating timeline failed").export_fd().expect("Exporting timeline failed");
+    let imported = TimelineSemaphore::import_fd(&renderer, fd).expect("Importing timeline failed");
+    renderer.attach(&target).expect("Attaching backbuffers failed");
+
+    for _ in 0..3 {
+        renderer.render_frame(&mut target).expect("Rendering frame failed");
+    }
+    // presented at submission, before the frames have necessarily finished
+    assert_eq!(target.last_presented(), Some(0));
+    let point = target.last_presented_point().expect("no timeline point");
+    assert_eq!(point.value, 3);
+    assert!(imported.wait(point.value, TIMEOUT_NS).expect("Waiting for timeline failed"), "frame never finished");
+    assert!(imported.value().expect("Querying timeline failed") >= point.value);
+
+    renderer.flush(&mut target).expect("Flushing frames failed");
+}
+
+#[test]
+fn unsignaled_value_times_out() {
+    let renderer = renderer();
+    let timeline = TimelineSemaphore::new(&renderer).expect("Creating timeline failed");
+
+    assert_eq!(timeline.value().expect("Querying timeline failed"), 0);
+    assert!(!timeline.wait(1, 1_000_000).expect("Waiting for timeline failed"));
+}
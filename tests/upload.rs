@@ -0,0 +1,65 @@
+//! Layout transitions of batched image uploads; needs no GPU.
+
+use bedrock as br;
+use vk_noredirect_render::{layout_transitions, ImageUpload, LayoutTransition};
+
+fn upload(image: usize, mip_level: u32, y: i32, old_layout: br::vk::VkImageLayout) -> ImageUpload {
+    ImageUpload {
+        image: image as _,
+        aspect_mask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+        mip_level,
+        array_layer: 0,
+        x: 0,
+        y,
+        width: 16,
+        height: 4,
+        old_layout,
+        new_layout: br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL
+    }
+}
+
+#[test]
+fn same_image_twice_before_one_frame() {
+    // a glyph atlas cleared on creation, then written by a label before any frame is submitted
+    let uploads = [
+        upload(1, 0, 0, br::vk::VK_IMAGE_LAYOUT_UNDEFINED),
+        upload(1, 0, 4, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL)
+    ];
+    assert_eq!(layout_transitions(&uploads), [LayoutTransition {
+        image: 1 as _,
+        aspect_mask: br::vk::VK_IMAGE_ASPECT_COLOR_BIT,
+        mip_level: 0,
+        array_layer: 0,
+        old_layout: br::vk::VK_IMAGE_LAYOUT_UNDEFINED,
+        new_layout: br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL
+    }]);
+}
+
+#[test]
+fn last_upload_decides_the_final_layout() {
+    let mut last = upload(1, 0, 0, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL);
+    last.new_layout = br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL;
+    let transitions = layout_transitions(&[upload(1, 0, 0, br::vk::VK_IMAGE_LAYOUT_UNDEFINED), last]);
+    assert_eq!(transitions.len(), 1);
+    assert_eq!(transitions[0].old_layout, br::vk::VK_IMAGE_LAYOUT_UNDEFINED);
+    assert_eq!(transitions[0].new_layout, br::vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL);
+}
+
+#[test]
+fn other_subresources_keep_their_own_transitions() {
+    let uploads = [
+        upload(1, 0, 0, br::vk::VK_IMAGE_LAYOUT_UNDEFINED),
+        upload(2, 0, 0, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL),
+        upload(1, 1, 0, br::vk::VK_IMAGE_LAYOUT_UNDEFINED),
+        upload(2, 0, 8, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL)
+    ];
+    let transitions = layout_transitions(&uploads);
+    assert_eq!(
+        transitions.iter().map(|t| (t.image as usize, t.mip_level, t.old_layout)).collect::<Vec<_>>(),
+        [
+            (1, 0, br::vk::VK_IMAGE_LAYOUT_UNDEFINED),
+            (2, 0, br::vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL),
+            (1, 1, br::vk::VK_IMAGE_LAYOUT_UNDEFINED)
+        ]
+    );
+}